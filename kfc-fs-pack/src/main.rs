@@ -0,0 +1,155 @@
+//! build a kfc-fs disk image with the user apps on the host
+//!
+//! usage : kfc-fs-pack -s <app source dir> -t <app target dir> -o <image> [-m <image size in MB>]
+//! [-l <library dir>]
+//!
+//! the apps are packed into "/", the files in the library dir into "/lib"
+
+use std::{
+    env,
+    fs::{read_dir, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    process::exit,
+    sync::{Arc, Mutex},
+};
+
+use kfc_fs::{BlockDevice, KfcFileSystem, SpinRawLock, BLOCK_SIZE};
+
+const DEFAULT_IMAGE_SIZE_MB: usize = 16;
+const INODE_BITMAP_BLOCKS: u32 = 1;
+
+/// the image file as a block device
+struct BlockFile(Mutex<File>);
+
+impl BlockDevice for BlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
+            .expect("seeking error");
+        file.read_exact(buf).expect("reading block error");
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
+            .expect("seeking error");
+        file.write_all(buf).expect("writing block error");
+    }
+}
+
+struct Args {
+    source: String,
+    target: String,
+    output: String,
+    size_mb: usize,
+    lib: Option<String>,
+}
+
+fn usage() -> ! {
+    eprintln!("usage : kfc-fs-pack -s <app source dir> -t <app target dir> -o <image> [-m <image size in MB>] [-l <library dir>]");
+    exit(1);
+}
+
+fn parse_args() -> Args {
+    let mut source = None;
+    let mut target = None;
+    let mut output = None;
+    let mut size_mb = DEFAULT_IMAGE_SIZE_MB;
+    let mut lib = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "-s" => source = Some(value),
+            "-t" => target = Some(value),
+            "-o" => output = Some(value),
+            "-m" => size_mb = value.parse().unwrap_or_else(|_| usage()),
+            "-l" => lib = Some(value),
+            _ => usage(),
+        }
+    }
+
+    match (source, target, output) {
+        (Some(source), Some(target), Some(output)) => Args {
+            source,
+            target,
+            output,
+            size_mb,
+            lib,
+        },
+        _ => usage(),
+    }
+}
+
+fn main() {
+    let args = parse_args();
+    let total_blocks = args.size_mb * 1024 * 1024 / BLOCK_SIZE;
+
+    let image = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&args.output)
+        .expect("failed to create the image");
+    image
+        .set_len((total_blocks * BLOCK_SIZE) as u64)
+        .expect("failed to set the image size");
+    let block_file: Arc<dyn BlockDevice> = Arc::new(BlockFile(Mutex::new(image)));
+
+    let fs =
+        KfcFileSystem::<SpinRawLock>::create(block_file, total_blocks as u32, INODE_BITMAP_BLOCKS);
+    let root_inode = KfcFileSystem::root_inode(&fs);
+
+    // get all app's name without ext
+    let mut app_list: Vec<_> = read_dir(&args.source)
+        .expect("failed to read the app source dir")
+        .map(|dir_entry| {
+            let mut name_ext = dir_entry.unwrap().file_name().into_string().unwrap();
+            name_ext.drain(name_ext.find('.').unwrap()..name_ext.len());
+            name_ext
+        })
+        .collect();
+    app_list.sort();
+
+    for app in app_list {
+        let elf_path = format!("{}/{}", args.target.trim_end_matches('/'), app);
+        let elf_data = std::fs::read(&elf_path)
+            .unwrap_or_else(|_| panic!("failed to read the app elf {}", elf_path));
+        let inode = root_inode
+            .create(&app)
+            .unwrap_or_else(|| panic!("failed to create the app {} in the image", app));
+        assert_eq!(
+            inode.write_at(0, &elf_data),
+            elf_data.len(),
+            "no space left for the app {}",
+            app
+        );
+        println!("packed {} : {} bytes", app, elf_data.len());
+    }
+
+    // the shared libraries for the dynamic loader
+    if let Some(lib) = args.lib {
+        let lib_inode = root_inode
+            .create_dir("lib")
+            .expect("failed to create /lib in the image");
+        for dir_entry in read_dir(&lib).expect("failed to read the library dir") {
+            let path = dir_entry.unwrap().path();
+            let name = path.file_name().unwrap().to_str().unwrap();
+            let data = std::fs::read(&path)
+                .unwrap_or_else(|_| panic!("failed to read the library {}", name));
+            let inode = lib_inode
+                .create(name)
+                .unwrap_or_else(|| panic!("failed to create the library {} in the image", name));
+            assert_eq!(
+                inode.write_at(0, &data),
+                data.len(),
+                "no space left for the library {}",
+                name
+            );
+            println!("packed /lib/{} : {} bytes", name, data.len());
+        }
+    }
+    root_inode.sync();
+}
@@ -0,0 +1,71 @@
+use crate::{BlockCacheManager, RawLock, BLOCK_SIZE};
+
+const BLOCK_BITS: usize = BLOCK_SIZE * 8;
+
+/// a bitmap block : 4096 bits
+type BitmapBlock = [u64; BLOCK_SIZE / 8];
+
+/// ### bitmap in [start_block_id, start_block_id + blocks)
+/// bit = 1 : allocated
+pub struct Bitmap {
+    start_block_id: usize,
+    blocks: usize,
+}
+
+/// return (block_pos, bits64_pos, inner_pos)
+fn decomposition(mut bit: usize) -> (usize, usize, usize) {
+    let block_pos = bit / BLOCK_BITS;
+    bit %= BLOCK_BITS;
+    (block_pos, bit / 64, bit % 64)
+}
+
+impl Bitmap {
+    pub fn new(start_block_id: usize, blocks: usize) -> Self {
+        Self {
+            start_block_id,
+            blocks,
+        }
+    }
+
+    /// ### find the first zero bit and set it
+    /// None : all bits are allocated
+    pub fn alloc<L: RawLock>(&self, block_cache: &BlockCacheManager<L>) -> Option<usize> {
+        for block_pos in 0..self.blocks {
+            let pos = block_cache
+                .get_block_cache(self.start_block_id + block_pos)
+                .lock()
+                .modify(0, |bitmap_block: &mut BitmapBlock| {
+                    let (bits64_pos, bits64) = bitmap_block
+                        .iter_mut()
+                        .enumerate()
+                        .find(|(_, bits64)| **bits64 != u64::MAX)?;
+                    let inner_pos = bits64.trailing_ones() as usize;
+                    *bits64 |= 1 << inner_pos;
+                    Some(block_pos * BLOCK_BITS + bits64_pos * 64 + inner_pos)
+                });
+            if pos.is_some() {
+                return pos;
+            }
+        }
+        None
+    }
+
+    pub fn dealloc<L: RawLock>(&self, block_cache: &BlockCacheManager<L>, bit: usize) {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        block_cache
+            .get_block_cache(self.start_block_id + block_pos)
+            .lock()
+            .modify(0, |bitmap_block: &mut BitmapBlock| {
+                assert!(
+                    bitmap_block[bits64_pos] & (1 << inner_pos) != 0,
+                    "dealloc a free bit"
+                );
+                bitmap_block[bits64_pos] &= !(1 << inner_pos);
+            });
+    }
+
+    /// the number of bits
+    pub fn maximum(&self) -> usize {
+        self.blocks * BLOCK_BITS
+    }
+}
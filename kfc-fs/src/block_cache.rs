@@ -0,0 +1,115 @@
+use core::mem::size_of;
+
+use crate::{BlockDevice, Mutex, RawLock, BLOCK_SIZE};
+use alloc::{collections::VecDeque, sync::Arc};
+
+const BLOCK_CACHE_SIZE: usize = 16;
+
+/// ### a block in memory
+/// written back to the disk when dropped (or synced) if modified
+#[repr(C, align(8))]
+pub struct BlockCache {
+    // the first field : aligned for the on-disk structures
+    cache: [u8; BLOCK_SIZE],
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+    modified: bool,
+}
+
+impl BlockCache {
+    pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
+        let mut cache = [0u8; BLOCK_SIZE];
+        block_device.read_block(block_id, &mut cache);
+        Self {
+            cache,
+            block_id,
+            block_device,
+            modified: false,
+        }
+    }
+
+    fn addr_of_offset(&self, offset: usize) -> usize {
+        &self.cache[offset] as *const _ as usize
+    }
+
+    pub fn get_ref<T: Sized>(&self, offset: usize) -> &T {
+        assert!(offset + size_of::<T>() <= BLOCK_SIZE, "out of the block");
+        unsafe { &*(self.addr_of_offset(offset) as *const T) }
+    }
+
+    pub fn get_mut<T: Sized>(&mut self, offset: usize) -> &mut T {
+        assert!(offset + size_of::<T>() <= BLOCK_SIZE, "out of the block");
+        self.modified = true;
+        unsafe { &mut *(self.addr_of_offset(offset) as *mut T) }
+    }
+
+    pub fn read<T, V>(&self, offset: usize, f: impl FnOnce(&T) -> V) -> V {
+        f(self.get_ref(offset))
+    }
+
+    pub fn modify<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
+        f(self.get_mut(offset))
+    }
+
+    pub fn sync(&mut self) {
+        if self.modified {
+            self.modified = false;
+            self.block_device.write_block(self.block_id, &self.cache);
+        }
+    }
+}
+
+impl Drop for BlockCache {
+    fn drop(&mut self) {
+        self.sync()
+    }
+}
+
+/// ### caches of a block device
+/// - when full, a cache not used by others is replaced (FIFO)
+/// - if all of them are in use, more are kept until some are released
+pub struct BlockCacheManager<L: RawLock> {
+    block_device: Arc<dyn BlockDevice>,
+    queue: Mutex<VecDeque<(usize, Arc<Mutex<BlockCache, L>>)>, L>,
+}
+
+impl<L: RawLock> BlockCacheManager<L> {
+    pub fn new(block_device: Arc<dyn BlockDevice>) -> Self {
+        Self {
+            block_device,
+            queue: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn get_block_cache(&self, block_id: usize) -> Arc<Mutex<BlockCache, L>> {
+        let mut queue = self.queue.lock();
+        if let Some((_, cache)) = queue.iter().find(|(id, _)| *id == block_id) {
+            return cache.clone();
+        }
+
+        while queue.len() >= BLOCK_CACHE_SIZE {
+            match queue
+                .iter()
+                .position(|(_, cache)| Arc::strong_count(cache) == 1)
+            {
+                // the replaced cache is written back here
+                Some(idx) => drop(queue.remove(idx)),
+                None => break,
+            }
+        }
+
+        let cache = Arc::new(Mutex::new(BlockCache::new(
+            block_id,
+            self.block_device.clone(),
+        )));
+        queue.push_back((block_id, cache.clone()));
+        cache
+    }
+
+    /// write all the modified caches back to the disk
+    pub fn sync_all(&self) {
+        for (_, cache) in self.queue.lock().iter() {
+            cache.lock().sync();
+        }
+    }
+}
@@ -0,0 +1,8 @@
+pub const BLOCK_SIZE: usize = 512;
+
+/// ### a disk read and written by blocks
+/// the buffer's length should be `BLOCK_SIZE`
+pub trait BlockDevice: Send + Sync {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]);
+    fn write_block(&self, block_id: usize, buf: &[u8]);
+}
@@ -0,0 +1,298 @@
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
+
+use crate::{
+    layout::{DirEntry, DiskInode, DiskInodeType, DIRENT_SIZE},
+    BlockCacheManager, KfcFileSystem, Mutex, MutexGuard, RawLock,
+};
+
+/// ### an inode in memory
+/// the position of the `DiskInode`, all operations go to the block caches
+pub struct Inode<L: RawLock> {
+    inode_id: u32,
+    block_id: usize,
+    block_offset: usize,
+    fs: Arc<Mutex<KfcFileSystem<L>, L>>,
+    block_cache: Arc<BlockCacheManager<L>>,
+}
+
+impl<L: RawLock> Inode<L> {
+    pub fn new(
+        inode_id: u32,
+        block_id: u32,
+        block_offset: usize,
+        fs: Arc<Mutex<KfcFileSystem<L>, L>>,
+        block_cache: Arc<BlockCacheManager<L>>,
+    ) -> Self {
+        Self {
+            inode_id,
+            block_id: block_id as usize,
+            block_offset,
+            fs,
+            block_cache,
+        }
+    }
+
+    fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
+        self.block_cache
+            .get_block_cache(self.block_id)
+            .lock()
+            .read(self.block_offset, f)
+    }
+
+    fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskInode) -> V) -> V {
+        self.block_cache
+            .get_block_cache(self.block_id)
+            .lock()
+            .modify(self.block_offset, f)
+    }
+
+    /// ### find the dirent by name in a directory
+    /// return (index of the dirent, inode id), None : not found, or not a directory
+    fn find_dirent(&self, name: &str, disk_inode: &DiskInode) -> Option<(usize, u32)> {
+        if !disk_inode.is_dir() {
+            return None;
+        }
+        let dirent_num = disk_inode.size as usize / DIRENT_SIZE;
+        let mut dirent = DirEntry::empty();
+        for i in 0..dirent_num {
+            assert_eq!(
+                disk_inode.read_at(i * DIRENT_SIZE, dirent.as_bytes_mut(), &self.block_cache),
+                DIRENT_SIZE
+            );
+            if !dirent.is_empty() && dirent.name() == name {
+                return Some((i, dirent.inode_number()));
+            }
+        }
+        None
+    }
+
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        self.find_dirent(name, disk_inode)
+            .map(|(_, inode_id)| inode_id)
+    }
+
+    fn inode_by_id(&self, fs: &MutexGuard<KfcFileSystem<L>, L>, inode_id: u32) -> Arc<Inode<L>> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        Arc::new(Self::new(
+            inode_id,
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_cache.clone(),
+        ))
+    }
+
+    /// ### grow the inode to `new_size`
+    /// Err : no enough data blocks, nothing changes
+    fn increase_size(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<KfcFileSystem<L>, L>,
+    ) -> Result<(), ()> {
+        if new_size <= disk_inode.size {
+            return Ok(());
+        }
+        let blocks_needed = disk_inode.blocks_num_needed(new_size);
+        let mut new_blocks = Vec::with_capacity(blocks_needed);
+        for _ in 0..blocks_needed {
+            match fs.alloc_data() {
+                Some(block_id) => new_blocks.push(block_id),
+                None => {
+                    new_blocks
+                        .into_iter()
+                        .for_each(|block_id| fs.dealloc_data(block_id));
+                    return Err(());
+                }
+            }
+        }
+        disk_inode.increase_size(new_size, new_blocks, &self.block_cache);
+        Ok(())
+    }
+
+    pub fn inode_id(&self) -> u32 {
+        self.inode_id
+    }
+
+    /// find a file in this directory
+    pub fn find(&self, name: &str) -> Option<Arc<Inode<L>>> {
+        let fs = self.fs.lock();
+        let inode_id = self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))?;
+        Some(self.inode_by_id(&fs, inode_id))
+    }
+
+    /// ### create a file in this directory
+    /// None : the name exists, or no space left
+    pub fn create(&self, name: &str) -> Option<Arc<Inode<L>>> {
+        self.create_inode(name, DiskInodeType::File)
+    }
+
+    /// ### create a directory in this directory
+    /// None : the name exists, or no space left
+    pub fn create_dir(&self, name: &str) -> Option<Arc<Inode<L>>> {
+        self.create_inode(name, DiskInodeType::Directory)
+    }
+
+    fn create_inode(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode<L>>> {
+        let mut fs = self.fs.lock();
+        let exists = self.read_disk_inode(|disk_inode| {
+            !disk_inode.is_dir() || self.find_inode_id(name, disk_inode).is_some()
+        });
+        if exists {
+            return None;
+        }
+
+        let new_inode_id = fs.alloc_inode()?;
+        let (new_block_id, new_block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        self.block_cache
+            .get_block_cache(new_block_id as usize)
+            .lock()
+            .modify(new_block_offset, |new_inode: &mut DiskInode| {
+                new_inode.initialize(type_);
+            });
+
+        let res: Result<(), ()> = self.modify_disk_inode(|dir_inode| {
+            // reuse the slot of a removed dirent, or append a new one
+            let dirent_num = dir_inode.size as usize / DIRENT_SIZE;
+            let mut dirent = DirEntry::empty();
+            let index = (0..dirent_num)
+                .find(|&i| {
+                    dir_inode.read_at(i * DIRENT_SIZE, dirent.as_bytes_mut(), &self.block_cache);
+                    dirent.is_empty()
+                })
+                .unwrap_or(dirent_num);
+            let offset = index * DIRENT_SIZE;
+            self.increase_size((offset + DIRENT_SIZE) as u32, dir_inode, &mut fs)?;
+            let dirent = DirEntry::new(name, new_inode_id);
+            dir_inode.write_at(offset, dirent.as_bytes(), &self.block_cache);
+            Ok(())
+        });
+        if res.is_err() {
+            fs.dealloc_inode(new_inode_id);
+            return None;
+        }
+
+        self.block_cache.sync_all();
+        Some(self.inode_by_id(&fs, new_inode_id))
+    }
+
+    /// ### remove a file or directory from this directory, and release its inode
+    /// false : not found. a directory should be checked empty by the caller
+    pub fn unlink(&self, name: &str) -> bool {
+        match self.remove_dirent(name) {
+            Some(inode) => {
+                inode.release();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// ### remove a file or directory from this directory, but keep its inode
+    /// - return the removed inode, it should be `release`d once nobody uses it
+    /// - None : not found. a directory should be checked empty by the caller
+    pub fn remove_dirent(&self, name: &str) -> Option<Arc<Inode<L>>> {
+        let fs = self.fs.lock();
+        let inode_id = self.modify_disk_inode(|dir_inode| {
+            let (index, inode_id) = self.find_dirent(name, dir_inode)?;
+            dir_inode.write_at(
+                index * DIRENT_SIZE,
+                DirEntry::empty().as_bytes(),
+                &self.block_cache,
+            );
+            Some(inode_id)
+        })?;
+        self.block_cache.sync_all();
+        Some(self.inode_by_id(&fs, inode_id))
+    }
+
+    /// ### free the data blocks and the inode
+    /// the dirent should have been removed by `remove_dirent`
+    pub fn release(&self) {
+        let mut fs = self.fs.lock();
+        let data_blocks =
+            self.modify_disk_inode(|disk_inode| disk_inode.clear_size(&self.block_cache));
+        for block_id in data_blocks {
+            fs.dealloc_data(block_id);
+        }
+        fs.dealloc_inode(self.inode_id);
+        self.block_cache.sync_all();
+    }
+
+    /// (name, inode id) of the files in this directory
+    pub fn entries(&self) -> Vec<(String, u32)> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() {
+                return Vec::new();
+            }
+            let dirent_num = disk_inode.size as usize / DIRENT_SIZE;
+            let mut ret = Vec::with_capacity(dirent_num);
+            let mut dirent = DirEntry::empty();
+            for i in 0..dirent_num {
+                disk_inode.read_at(i * DIRENT_SIZE, dirent.as_bytes_mut(), &self.block_cache);
+                if !dirent.is_empty() {
+                    ret.push((String::from(dirent.name()), dirent.inode_number()));
+                }
+            }
+            ret
+        })
+    }
+
+    /// names of the files in this directory
+    pub fn ls(&self) -> Vec<String> {
+        self.entries().into_iter().map(|(name, _)| name).collect()
+    }
+
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_cache))
+    }
+
+    /// ### write and grow the file if needed
+    /// - return the number of bytes written, may be less than `buf.len()` if no space left
+    /// - the data stays in the block caches until replaced or `sync`ed
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            let new_size = (offset + buf.len()) as u32;
+            // on failure, only write to the blocks we have
+            let _ = self.increase_size(new_size, disk_inode, &mut fs);
+            disk_inode.write_at(offset, buf, &self.block_cache)
+        })
+    }
+
+    /// write the modified blocks of the whole filesystem back to the disk
+    pub fn sync(&self) {
+        self.block_cache.sync_all();
+    }
+
+    /// the whole file
+    pub fn read_all(&self) -> Vec<u8> {
+        let mut ret = vec![0u8; self.size()];
+        let len = self.read_at(0, &mut ret);
+        ret.truncate(len);
+        ret
+    }
+
+    /// shrink the file to 0, the data blocks are released
+    pub fn clear(&self) {
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            for block_id in disk_inode.clear_size(&self.block_cache) {
+                fs.dealloc_data(block_id);
+            }
+        });
+        self.block_cache.sync_all();
+    }
+
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+}
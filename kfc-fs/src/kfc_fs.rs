@@ -0,0 +1,175 @@
+use core::mem::size_of;
+
+use alloc::sync::Arc;
+
+use crate::{
+    bitmap::Bitmap,
+    layout::{DiskInode, DiskInodeType, SuperBlock},
+    BlockCacheManager, BlockDevice, Inode, Mutex, RawLock, BLOCK_SIZE,
+};
+
+const INODES_PER_BLOCK: usize = BLOCK_SIZE / size_of::<DiskInode>();
+// a data bitmap block manages 4096 data blocks
+const DATA_BLOCKS_PER_BITMAP_BLOCK: usize = BLOCK_SIZE * 8;
+
+/// ### the filesystem on a block device
+/// manage the bitmaps and the positions of inodes and data blocks
+pub struct KfcFileSystem<L: RawLock> {
+    pub block_cache: Arc<BlockCacheManager<L>>,
+    inode_bitmap: Bitmap,
+    data_bitmap: Bitmap,
+    inode_area_start_block: u32,
+    data_area_start_block: u32,
+    data_area_blocks: u32,
+}
+
+impl<L: RawLock> KfcFileSystem<L> {
+    /// ### make a new filesystem on the device
+    /// the root directory is inode 0
+    pub fn create(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+    ) -> Arc<Mutex<Self, L>> {
+        let block_cache = Arc::new(BlockCacheManager::new(block_device));
+
+        let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
+        let inode_num = inode_bitmap.maximum();
+        let inode_area_blocks = ((inode_num + INODES_PER_BLOCK - 1) / INODES_PER_BLOCK) as u32;
+        let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
+
+        // the rest blocks : data bitmap + data area
+        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
+        let data_bitmap_blocks = (data_total_blocks as usize + DATA_BLOCKS_PER_BITMAP_BLOCK)
+            / (DATA_BLOCKS_PER_BITMAP_BLOCK + 1);
+        let data_area_blocks = data_total_blocks - data_bitmap_blocks as u32;
+        let data_bitmap = Bitmap::new((1 + inode_total_blocks) as usize, data_bitmap_blocks);
+
+        let mut fs = Self {
+            block_cache: block_cache.clone(),
+            inode_bitmap,
+            data_bitmap,
+            inode_area_start_block: 1 + inode_bitmap_blocks,
+            data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks as u32,
+            data_area_blocks,
+        };
+
+        // clear all the blocks
+        for i in 0..total_blocks {
+            block_cache.get_block_cache(i as usize).lock().modify(
+                0,
+                |data_block: &mut [u8; BLOCK_SIZE]| {
+                    data_block.iter_mut().for_each(|byte| *byte = 0);
+                },
+            );
+        }
+
+        block_cache
+            .get_block_cache(0)
+            .lock()
+            .modify(0, |super_block: &mut SuperBlock| {
+                super_block.initialize(
+                    total_blocks,
+                    inode_bitmap_blocks,
+                    inode_area_blocks,
+                    data_bitmap_blocks as u32,
+                    data_area_blocks,
+                );
+            });
+
+        // the root directory
+        assert_eq!(fs.alloc_inode(), Some(0), "root inode is not 0");
+        let (root_block_id, root_offset) = fs.get_disk_inode_pos(0);
+        block_cache
+            .get_block_cache(root_block_id as usize)
+            .lock()
+            .modify(root_offset, |disk_inode: &mut DiskInode| {
+                disk_inode.initialize(DiskInodeType::Directory);
+            });
+        block_cache.sync_all();
+
+        Arc::new(Mutex::new(fs))
+    }
+
+    /// open the filesystem made before on the device
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self, L>> {
+        let block_cache = Arc::new(BlockCacheManager::new(block_device));
+        let fs = block_cache
+            .get_block_cache(0)
+            .lock()
+            .read(0, |super_block: &SuperBlock| {
+                assert!(super_block.is_valid(), "invalid kfc-fs super block");
+                let inode_total_blocks =
+                    super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
+                Self {
+                    block_cache: block_cache.clone(),
+                    inode_bitmap: Bitmap::new(1, super_block.inode_bitmap_blocks as usize),
+                    data_bitmap: Bitmap::new(
+                        (1 + inode_total_blocks) as usize,
+                        super_block.data_bitmap_blocks as usize,
+                    ),
+                    inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
+                    data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    data_area_blocks: super_block.data_area_blocks,
+                }
+            });
+        Arc::new(Mutex::new(fs))
+    }
+
+    pub fn root_inode(fs: &Arc<Mutex<Self, L>>) -> Inode<L> {
+        let fs_locked = fs.lock();
+        let (block_id, block_offset) = fs_locked.get_disk_inode_pos(0);
+        Inode::new(
+            0,
+            block_id,
+            block_offset,
+            fs.clone(),
+            fs_locked.block_cache.clone(),
+        )
+    }
+
+    /// return (block_id, offset in the block)
+    pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
+        let inode_id = inode_id as usize;
+        (
+            self.inode_area_start_block + (inode_id / INODES_PER_BLOCK) as u32,
+            (inode_id % INODES_PER_BLOCK) * size_of::<DiskInode>(),
+        )
+    }
+
+    pub fn alloc_inode(&mut self) -> Option<u32> {
+        self.inode_bitmap
+            .alloc(&self.block_cache)
+            .map(|inode_id| inode_id as u32)
+    }
+
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.inode_bitmap
+            .dealloc(&self.block_cache, inode_id as usize)
+    }
+
+    /// return the block id of a new data block
+    pub fn alloc_data(&mut self) -> Option<u32> {
+        let bit = self.data_bitmap.alloc(&self.block_cache)?;
+        // the last bitmap block may have more bits than the data blocks
+        if bit >= self.data_area_blocks as usize {
+            self.data_bitmap.dealloc(&self.block_cache, bit);
+            return None;
+        }
+        Some(self.data_area_start_block + bit as u32)
+    }
+
+    /// the block is cleared for the next user
+    pub fn dealloc_data(&mut self, block_id: u32) {
+        self.block_cache
+            .get_block_cache(block_id as usize)
+            .lock()
+            .modify(0, |data_block: &mut [u8; BLOCK_SIZE]| {
+                data_block.iter_mut().for_each(|byte| *byte = 0);
+            });
+        self.data_bitmap.dealloc(
+            &self.block_cache,
+            (block_id - self.data_area_start_block) as usize,
+        )
+    }
+}
@@ -0,0 +1,366 @@
+use core::{cmp::min, mem::size_of};
+
+use alloc::vec::Vec;
+
+use crate::{BlockCacheManager, RawLock, BLOCK_SIZE};
+
+const KFC_FS_MAGIC: u32 = 0x4b46_4346; // "KFCF"
+
+const INODE_DIRECT_COUNT: usize = 28;
+const INODE_INDIRECT1_COUNT: usize = BLOCK_SIZE / 4;
+const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
+const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
+
+/// the name's last byte is always '\0'
+pub const NAME_LENGTH_LIMIT: usize = 27;
+pub const DIRENT_SIZE: usize = size_of::<DirEntry>();
+
+/// block ids of the next level
+type IndirectBlock = [u32; INODE_INDIRECT1_COUNT];
+type DataBlock = [u8; BLOCK_SIZE];
+
+/// ### the first block of the disk
+/// the numbers of blocks of each area
+#[repr(C)]
+pub struct SuperBlock {
+    magic: u32,
+    pub total_blocks: u32,
+    pub inode_bitmap_blocks: u32,
+    pub inode_area_blocks: u32,
+    pub data_bitmap_blocks: u32,
+    pub data_area_blocks: u32,
+}
+
+impl SuperBlock {
+    pub fn initialize(
+        &mut self,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        inode_area_blocks: u32,
+        data_bitmap_blocks: u32,
+        data_area_blocks: u32,
+    ) {
+        *self = Self {
+            magic: KFC_FS_MAGIC,
+            total_blocks,
+            inode_bitmap_blocks,
+            inode_area_blocks,
+            data_bitmap_blocks,
+            data_area_blocks,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.magic == KFC_FS_MAGIC
+    }
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiskInodeType {
+    File,
+    Directory,
+}
+
+/// ### an inode on disk : 128 bytes, 4 inodes in a block
+/// data blocks : `direct` --> `indirect1` --> `indirect2`
+#[repr(C)]
+pub struct DiskInode {
+    pub size: u32,
+    direct: [u32; INODE_DIRECT_COUNT],
+    indirect1: u32,
+    indirect2: u32,
+    type_: DiskInodeType,
+}
+
+impl DiskInode {
+    /// the data blocks are bounded later by `increase_size`
+    pub fn initialize(&mut self, type_: DiskInodeType) {
+        self.size = 0;
+        self.direct.iter_mut().for_each(|v| *v = 0);
+        self.indirect1 = 0;
+        self.indirect2 = 0;
+        self.type_ = type_;
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.type_ == DiskInodeType::Directory
+    }
+
+    fn data_blocks(size: u32) -> usize {
+        (size as usize + BLOCK_SIZE - 1) / BLOCK_SIZE
+    }
+
+    /// data blocks and index blocks
+    fn total_blocks(size: u32) -> usize {
+        let data_blocks = Self::data_blocks(size);
+        let mut total = data_blocks;
+        if data_blocks > DIRECT_BOUND {
+            total += 1;
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            total += 1;
+            total +=
+                (data_blocks - INDIRECT1_BOUND + INODE_INDIRECT1_COUNT - 1) / INODE_INDIRECT1_COUNT;
+        }
+        total
+    }
+
+    /// the number of blocks to be allocated when growing to `new_size`
+    pub fn blocks_num_needed(&self, new_size: u32) -> usize {
+        assert!(new_size >= self.size);
+        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    }
+
+    /// ### the block id of the `inner_id`-th data block
+    pub fn get_block_id<L: RawLock>(
+        &self,
+        inner_id: usize,
+        block_cache: &BlockCacheManager<L>,
+    ) -> u32 {
+        if inner_id < DIRECT_BOUND {
+            self.direct[inner_id]
+        } else if inner_id < INDIRECT1_BOUND {
+            block_cache
+                .get_block_cache(self.indirect1 as usize)
+                .lock()
+                .read(0, |indirect1: &IndirectBlock| {
+                    indirect1[inner_id - DIRECT_BOUND]
+                })
+        } else {
+            let last = inner_id - INDIRECT1_BOUND;
+            let indirect1 = block_cache
+                .get_block_cache(self.indirect2 as usize)
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    indirect2[last / INODE_INDIRECT1_COUNT]
+                });
+            block_cache
+                .get_block_cache(indirect1 as usize)
+                .lock()
+                .read(0, |indirect1: &IndirectBlock| {
+                    indirect1[last % INODE_INDIRECT1_COUNT]
+                })
+        }
+    }
+
+    fn set_block_id<L: RawLock>(
+        &mut self,
+        inner_id: usize,
+        block_id: u32,
+        block_cache: &BlockCacheManager<L>,
+    ) {
+        if inner_id < DIRECT_BOUND {
+            self.direct[inner_id] = block_id;
+        } else if inner_id < INDIRECT1_BOUND {
+            block_cache
+                .get_block_cache(self.indirect1 as usize)
+                .lock()
+                .modify(0, |indirect1: &mut IndirectBlock| {
+                    indirect1[inner_id - DIRECT_BOUND] = block_id;
+                });
+        } else {
+            let last = inner_id - INDIRECT1_BOUND;
+            let indirect1 = block_cache
+                .get_block_cache(self.indirect2 as usize)
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    indirect2[last / INODE_INDIRECT1_COUNT]
+                });
+            block_cache
+                .get_block_cache(indirect1 as usize)
+                .lock()
+                .modify(0, |indirect1: &mut IndirectBlock| {
+                    indirect1[last % INODE_INDIRECT1_COUNT] = block_id;
+                });
+        }
+    }
+
+    /// ### grow to `new_size`
+    /// `new_blocks` should have `blocks_num_needed(new_size)` blocks,
+    /// index blocks are taken just before the first data block using them
+    pub fn increase_size<L: RawLock>(
+        &mut self,
+        new_size: u32,
+        new_blocks: Vec<u32>,
+        block_cache: &BlockCacheManager<L>,
+    ) {
+        let old_data_blocks = Self::data_blocks(self.size);
+        let new_data_blocks = Self::data_blocks(new_size);
+        self.size = new_size;
+
+        let mut new_blocks = new_blocks.into_iter();
+        for inner_id in old_data_blocks..new_data_blocks {
+            if inner_id == DIRECT_BOUND {
+                self.indirect1 = new_blocks.next().unwrap();
+            }
+            if inner_id == INDIRECT1_BOUND {
+                self.indirect2 = new_blocks.next().unwrap();
+            }
+            if inner_id >= INDIRECT1_BOUND
+                && (inner_id - INDIRECT1_BOUND) % INODE_INDIRECT1_COUNT == 0
+            {
+                let indirect1 = new_blocks.next().unwrap();
+                block_cache
+                    .get_block_cache(self.indirect2 as usize)
+                    .lock()
+                    .modify(0, |indirect2: &mut IndirectBlock| {
+                        indirect2[(inner_id - INDIRECT1_BOUND) / INODE_INDIRECT1_COUNT] = indirect1;
+                    });
+            }
+            self.set_block_id(inner_id, new_blocks.next().unwrap(), block_cache);
+        }
+        assert!(
+            new_blocks.next().is_none(),
+            "too many blocks for increasing"
+        );
+    }
+
+    /// ### shrink to 0
+    /// return all the data blocks and index blocks to be deallocated
+    pub fn clear_size<L: RawLock>(&mut self, block_cache: &BlockCacheManager<L>) -> Vec<u32> {
+        let data_blocks = Self::data_blocks(self.size);
+        let mut ret: Vec<u32> = (0..data_blocks)
+            .map(|inner_id| self.get_block_id(inner_id, block_cache))
+            .collect();
+
+        if data_blocks > DIRECT_BOUND {
+            ret.push(self.indirect1);
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            let indirect1_num =
+                (data_blocks - INDIRECT1_BOUND + INODE_INDIRECT1_COUNT - 1) / INODE_INDIRECT1_COUNT;
+            block_cache
+                .get_block_cache(self.indirect2 as usize)
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    ret.extend_from_slice(&indirect2[..indirect1_num]);
+                });
+            ret.push(self.indirect2);
+        }
+
+        self.initialize(self.type_);
+        ret
+    }
+
+    /// ### read data from `offset` into `buf`
+    /// return the number of bytes read, stop at the end of the file
+    pub fn read_at<L: RawLock>(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_cache: &BlockCacheManager<L>,
+    ) -> usize {
+        let end = min(offset + buf.len(), self.size as usize);
+        let mut start = offset;
+        let mut read_len = 0;
+
+        while start < end {
+            // the end of the current block
+            let block_end = min((start / BLOCK_SIZE + 1) * BLOCK_SIZE, end);
+            let len = block_end - start;
+            let dst = &mut buf[read_len..read_len + len];
+            block_cache
+                .get_block_cache(self.get_block_id(start / BLOCK_SIZE, block_cache) as usize)
+                .lock()
+                .read(0, |data_block: &DataBlock| {
+                    let src = &data_block[start % BLOCK_SIZE..start % BLOCK_SIZE + len];
+                    dst.copy_from_slice(src);
+                });
+            read_len += len;
+            start = block_end;
+        }
+        read_len
+    }
+
+    /// ### write data in `buf` from `offset`
+    /// the size should have been increased, return the number of bytes written
+    pub fn write_at<L: RawLock>(
+        &mut self,
+        offset: usize,
+        buf: &[u8],
+        block_cache: &BlockCacheManager<L>,
+    ) -> usize {
+        let end = min(offset + buf.len(), self.size as usize);
+        let mut start = offset;
+        let mut write_len = 0;
+
+        while start < end {
+            let block_end = min((start / BLOCK_SIZE + 1) * BLOCK_SIZE, end);
+            let len = block_end - start;
+            let src = &buf[write_len..write_len + len];
+            block_cache
+                .get_block_cache(self.get_block_id(start / BLOCK_SIZE, block_cache) as usize)
+                .lock()
+                .modify(0, |data_block: &mut DataBlock| {
+                    let dst = &mut data_block[start % BLOCK_SIZE..start % BLOCK_SIZE + len];
+                    dst.copy_from_slice(src);
+                });
+            write_len += len;
+            start = block_end;
+        }
+        write_len
+    }
+}
+
+/// ### an entry in a directory's data : 32 bytes
+#[repr(C)]
+pub struct DirEntry {
+    name: [u8; NAME_LENGTH_LIMIT + 1],
+    inode_number: u32,
+}
+
+impl DirEntry {
+    pub fn empty() -> Self {
+        Self {
+            name: [0u8; NAME_LENGTH_LIMIT + 1],
+            inode_number: 0,
+        }
+    }
+
+    /// ### the name is truncated to `NAME_LENGTH_LIMIT` bytes
+    /// the callers should reject the longer names, the truncation keeps whole characters
+    pub fn new(name: &str, inode_number: u32) -> Self {
+        let mut bytes = [0u8; NAME_LENGTH_LIMIT + 1];
+        let mut len = min(name.len(), NAME_LENGTH_LIMIT);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+        Self {
+            name: bytes,
+            inode_number,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as usize as *const u8, DIRENT_SIZE) }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as usize as *mut u8, DIRENT_SIZE) }
+    }
+
+    /// a removed dirent, the slot can be reused
+    pub fn is_empty(&self) -> bool {
+        self.name[0] == 0
+    }
+
+    /// a corrupted name is cut at its first invalid byte
+    pub fn name(&self) -> &str {
+        let len = self
+            .name
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(self.name.len());
+        let bytes = &self.name[..len];
+        match core::str::from_utf8(bytes) {
+            Ok(name) => name,
+            Err(err) => core::str::from_utf8(&bytes[..err.valid_up_to()]).unwrap_or_default(),
+        }
+    }
+
+    pub fn inode_number(&self) -> u32 {
+        self.inode_number
+    }
+}
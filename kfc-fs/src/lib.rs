@@ -0,0 +1,23 @@
+//! a simple filesystem shared by the kernel and the host packer
+//!
+//! disk layout (in blocks) :
+//! | super block | inode bitmap | inode area | data bitmap | data area |
+
+#![no_std]
+
+extern crate alloc;
+
+mod bitmap;
+mod block_cache;
+mod block_dev;
+mod inode;
+mod kfc_fs;
+mod layout;
+mod lock;
+
+pub use block_cache::{BlockCache, BlockCacheManager};
+pub use block_dev::{BlockDevice, BLOCK_SIZE};
+pub use inode::Inode;
+pub use kfc_fs::KfcFileSystem;
+pub use layout::NAME_LENGTH_LIMIT;
+pub use lock::{Mutex, MutexGuard, RawLock, SpinRawLock};
@@ -0,0 +1,104 @@
+use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// ### the lock used by the filesystem
+/// implemented by the user of the crate, so the kernel can make it preempt-aware
+///
+/// ### Safety
+/// - `lock` returns only when the lock is held by the caller
+/// - `unlock` is called only by the holder
+pub unsafe trait RawLock: Send + Sync {
+    /// an unlocked lock
+    const INIT: Self;
+
+    fn lock(&self);
+
+    /// ### Safety
+    /// the lock is held by the caller
+    unsafe fn unlock(&self);
+}
+
+/// ### a plain spin lock
+/// for a host program, where the threads are scheduled by the OS anyway
+pub struct SpinRawLock {
+    locked: AtomicBool,
+}
+
+unsafe impl RawLock for SpinRawLock {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        locked: AtomicBool::new(false),
+    };
+
+    fn lock(&self) {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            spin_loop();
+        }
+    }
+
+    unsafe fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+/// a mutex over the lock `L`
+pub struct Mutex<T, L: RawLock> {
+    raw: L,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send, L: RawLock> Sync for Mutex<T, L> {}
+unsafe impl<T: Send, L: RawLock> Send for Mutex<T, L> {}
+
+impl<T, L: RawLock> Mutex<T, L> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            raw: L::INIT,
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T, L> {
+        self.raw.lock();
+        MutexGuard {
+            mutex: self,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// unlock when dropped
+pub struct MutexGuard<'a, T, L: RawLock> {
+    mutex: &'a Mutex<T, L>,
+    // unlocked by the holder
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T, L: RawLock> Deref for MutexGuard<'_, T, L> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T, L: RawLock> DerefMut for MutexGuard<'_, T, L> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T, L: RawLock> Drop for MutexGuard<'_, T, L> {
+    fn drop(&mut self) {
+        unsafe { self.mutex.raw.unlock() }
+    }
+}
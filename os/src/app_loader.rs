@@ -1,70 +1,63 @@
-use core::slice;
+use alloc::{string::String, vec::Vec};
 
-use alloc::vec::Vec;
-use lazy_static::lazy_static;
+use crate::{
+    fs::{find_inode, open_file, InodeType, OpenFlags},
+    syscall_impl::errno::ENOEXEC,
+};
 
-pub struct AppData {
-    pub name: &'static str,
-    pub layout: (usize, usize),
-}
+/// the "#!" line is limited like BINPRM_BUF_SIZE of Linux
+const SHEBANG_LINE_MAX: usize = 256;
+/// scripts interpreted by scripts, at most
+pub const MAX_SHEBANG_DEPTH: usize = 4;
 
-fn app_data_init() -> Vec<AppData> {
-    extern "C" {
-        fn _num_app();
-        fn _app_names();
-    }
-    unsafe {
-        let app_num = (_num_app as *const usize).read_volatile();
-        let app_layout_list = slice::from_raw_parts((_num_app as *const usize).add(1), app_num + 1);
-        let mut name_ptr = _app_names as *const u8;
-        let mut ret = Vec::new();
-        for i in 0..app_num {
-            let mut end = name_ptr;
-            while end.read_volatile() != '\0' as u8 {
-                end = end.add(1);
-            }
-            let slice = slice::from_raw_parts(name_ptr, end as usize - name_ptr as usize);
-            let name_string = core::str::from_utf8(slice).expect("app name is not utf8");
-            ret.push(AppData {
-                name: name_string,
-                layout: (app_layout_list[i], app_layout_list[i + 1]),
-            });
-            name_ptr = end.add(1);
-        }
-        // for i in 0..app_num {
-        //     debug!("app_{}: {}", i, ret[i].name);
-        // }
-        ret
+/// ### read the app's elf file by path, any file on the filesystem can be loaded
+/// a relative path starts from `cwd`, a bare name not found there is searched in "/"
+pub fn load_app_by_name(cwd: &str, name: &str) -> Option<Vec<u8>> {
+    let file = match open_file(cwd, name, OpenFlags::RDONLY) {
+        Ok(file) => file,
+        Err(_) if !name.contains('/') => open_file("/", name, OpenFlags::RDONLY).ok()?,
+        Err(_) => return None,
+    };
+    if file.is_dir() {
+        return None;
     }
+    Some(file.read_all())
 }
 
-lazy_static! {
-    static ref APP_DATA: Vec<AppData> = app_data_init();
-}
-
-// get idx-th app's data
-fn load_app(idx: usize) -> Option<&'static [u8]> {
-    if idx >= APP_DATA.len() {
+/// ### parse the "#!" line of a script : "#!interpreter [argument]"
+/// the rest of the line after the interpreter is one argument, as Linux does
+/// - None : not a script
+/// - Some(Err) : ENOEXEC, no interpreter or the line is too long
+pub fn parse_shebang(data: &[u8]) -> Option<Result<(String, Option<String>), isize>> {
+    if !data.starts_with(b"#!") {
         return None;
     }
-    unsafe {
-        Some(slice::from_raw_parts(
-            APP_DATA[idx].layout.0 as *const u8,
-            APP_DATA[idx].layout.1 - APP_DATA[idx].layout.0,
-        ))
+    let data = &data[2..data.len().min(SHEBANG_LINE_MAX)];
+    let line = match data.iter().position(|&b| b == b'\n') {
+        Some(end) => &data[..end],
+        None if data.len() < SHEBANG_LINE_MAX - 2 => data,
+        None => return Some(Err(ENOEXEC)),
+    };
+    let line = match core::str::from_utf8(line) {
+        Ok(line) => line.trim_matches(|c| c == ' ' || c == '\t' || c == '\r'),
+        Err(_) => return Some(Err(ENOEXEC)),
+    };
+    if line.is_empty() {
+        return Some(Err(ENOEXEC));
     }
+    Some(Ok(match line.split_once(|c| c == ' ' || c == '\t') {
+        Some((interp, arg)) => (interp.into(), Some(arg.trim_start().into())),
+        None => (line.into(), None),
+    }))
 }
 
-pub fn load_app_by_name(name: &str) -> Option<&'static [u8]> {
-    (0..APP_DATA.len())
-        .find(|&i| APP_DATA[i].name == name)
-        .map_or(None, |idx| Some(load_app(idx)?))
-}
-
-pub fn get_app_names() -> Vec<&'static str> {
-    let mut ret = Vec::new();
-    for it in APP_DATA.iter() {
-        ret.push(it.name)
-    }
-    ret
+/// the files in "/"
+pub fn get_app_names() -> Vec<String> {
+    find_inode("/", "/")
+        .expect("no root directory")
+        .entries()
+        .into_iter()
+        .filter(|entry| entry.inode_type == InodeType::File)
+        .map(|entry| entry.name)
+        .collect()
 }
@@ -4,7 +4,11 @@ use core::ops::Range;
 
 use crate::mm::VirtAddr;
 
-pub const BOOT_STACK_SIZE: usize = 0x10000; // 64KB
+pub const BOOT_STACK_SIZE: usize = 0x10000; // 64KB for each hart
+
+// SMP : the harts with larger ids are parked forever
+pub const MAX_HARTS: usize = 4;
+
 #[cfg(feature = "larger_memory")]
 pub const MEMORY_END: usize = 0x84000000; // 64 MB
 #[cfg(not(feature = "larger_memory"))]
@@ -22,7 +26,7 @@ pub const VP_INDEX_NUM: usize = 512;
 pub const VP_INDEX_BITS: usize = 9;
 
 // SV39 : PTE
-pub const PTE_FLAGS_MASK: usize = (1 << 8) - 1;
+pub const PTE_FLAGS_MASK: usize = (1 << 10) - 1; // with RSW bits
 pub const PTE_PPN_RANGE: Range<usize> = 10..54;
 
 // SV39 : PP
@@ -30,9 +34,26 @@ pub const PP_PPN_RANGE: Range<usize> = 12..56;
 
 // Stack
 pub const USER_STACK_SIZE: usize = 0x2000; // 8KB
+/// the strings and pointers of argv and envp take at most half of the user stack
+pub const ARG_MAX: usize = USER_STACK_SIZE / 2;
 pub const KERNEL_STACK_SIZE: usize = 0x2000; // 8KB
 
 // trap
 pub const VIRT_ADDR_MAX: VirtAddr = VirtAddr(usize::MAX);
 pub const TRAMPOLINE_VIRT_ADDR: VirtAddr = VirtAddr(VIRT_ADDR_MAX.0 - PAGE_BYTES + 1);
 pub const TRAP_CTX_VIRT_ADDR: VirtAddr = VirtAddr(TRAMPOLINE_VIRT_ADDR.0 - PAGE_BYTES);
+
+// threads : the trap context of thread `tid` is `tid` pages below TRAP_CTX_VIRT_ADDR
+pub const MAX_THREADS: usize = 16;
+pub const TRAP_CTX_BOTTOM_VIRT_ADDR: VirtAddr =
+    VirtAddr(TRAP_CTX_VIRT_ADDR.0 - (MAX_THREADS - 1) * PAGE_BYTES);
+
+// Sv39 : the user addresses are in [0, 256GB), the rest of the low half is not canonical
+pub const USER_SPACE_END_VIRT_ADDR: VirtAddr = VirtAddr(0x40_0000_0000);
+
+// mmap : anonymous areas are placed from here when no address hint fits
+pub const MMAP_BASE_VIRT_ADDR: VirtAddr = VirtAddr(0x10_0000_0000);
+
+// elf : the load base of position-independent executables (ET_DYN) and their interpreters
+pub const PIE_BASE_VIRT_ADDR: VirtAddr = VirtAddr(0x1000_0000);
+pub const INTERP_BASE_VIRT_ADDR: VirtAddr = VirtAddr(0x2000_0000);
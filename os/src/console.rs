@@ -1,6 +1,13 @@
 use core::fmt::{Result, Write};
 
-use crate::kfc_sbi::uart;
+use alloc::collections::VecDeque;
+use lazy_static::lazy_static;
+
+use crate::{
+    kfc_sbi::uart,
+    kfc_util::{spin_lock::IrqSpinLock, up_safe_cell::UPSafeCell},
+    task::wait_queue::WaitQueue,
+};
 
 pub fn console_putc(c: u8) {
     uart::uart_putc_sync(c);
@@ -10,6 +17,47 @@ pub fn console_getc() -> u8 {
     uart::uart_getc()
 }
 
+lazy_static! {
+    /// the input polled by the idle loop, not read by any task yet
+    static ref INPUT_BUFFER: UPSafeCell<VecDeque<u8>> = UPSafeCell::new("INPUT_BUFFER", VecDeque::new());
+    /// the tasks waiting for the console input
+    static ref CONSOLE_READERS: WaitQueue = WaitQueue::new();
+}
+
+/// ### read a byte, block the current task until the console has input
+/// None : the thread is killed while waiting
+pub fn console_getc_blocking() -> Option<u8> {
+    let mut c = 0;
+    while c == 0 {
+        let alive = CONSOLE_READERS.wait_killable_if(|| {
+            c = INPUT_BUFFER
+                .exclusive_access()
+                .pop_front()
+                .unwrap_or_else(console_getc);
+            c == 0
+        });
+        if !alive {
+            return None;
+        }
+    }
+    Some(c)
+}
+
+/// ### called by the idle control flow
+/// wake up the readers if the console has input
+pub fn console_poll() {
+    if CONSOLE_READERS.is_empty() {
+        return;
+    }
+    let mut buffer = INPUT_BUFFER.exclusive_access();
+    let c = console_getc();
+    if c != 0 {
+        buffer.push_back(c);
+        drop(buffer);
+        CONSOLE_READERS.wake_all();
+    }
+}
+
 struct Stdout;
 
 impl Write for Stdout {
@@ -21,7 +69,11 @@ impl Write for Stdout {
     }
 }
 
+/// the output of a hart is not interleaved with the others
+static PRINT_LOCK: IrqSpinLock<()> = IrqSpinLock::new("PRINT_LOCK", ());
+
 pub fn print(args: core::fmt::Arguments) {
+    let _guard = PRINT_LOCK.lock();
     Stdout.write_fmt(args).unwrap();
 }
 
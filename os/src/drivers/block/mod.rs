@@ -0,0 +1,42 @@
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
+
+pub use kfc_fs::{BlockDevice, BLOCK_SIZE};
+
+#[cfg(feature = "ramdisk")]
+pub mod ram_disk;
+#[cfg(not(feature = "ramdisk"))]
+pub mod virtio_blk;
+
+#[cfg(feature = "ramdisk")]
+type BlockDeviceImpl = ram_disk::RamDisk;
+#[cfg(not(feature = "ramdisk"))]
+type BlockDeviceImpl = virtio_blk::VirtIOBlock;
+
+lazy_static! {
+    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new());
+}
+
+/// ### write a block and read it back, then restore the old data
+/// block 0 is the superblock, nothing else should access the device meanwhile
+#[cfg(feature = "block_test")]
+pub fn block_device_test() {
+    info!("block_device_test start!");
+    let block_id = 0;
+    let mut old_data = [0u8; BLOCK_SIZE];
+    let mut read_buf = [0u8; BLOCK_SIZE];
+    let mut write_buf = [0u8; BLOCK_SIZE];
+
+    BLOCK_DEVICE.read_block(block_id, &mut old_data);
+    for (i, byte) in write_buf.iter_mut().enumerate() {
+        *byte = (i % 256) as u8 ^ old_data[i];
+    }
+    BLOCK_DEVICE.write_block(block_id, &write_buf);
+    BLOCK_DEVICE.read_block(block_id, &mut read_buf);
+    assert_eq!(read_buf, write_buf, "block read back is not the same");
+
+    BLOCK_DEVICE.write_block(block_id, &old_data);
+    BLOCK_DEVICE.read_block(block_id, &mut read_buf);
+    assert_eq!(read_buf, old_data, "block restored is not the same");
+    info!("block_device_test passed!");
+}
@@ -0,0 +1,59 @@
+// the disk image is embedded in the kernel's .data section
+// so the filesystem works without any driver
+
+use core::{arch::global_asm, slice};
+
+use crate::kfc_util::up_safe_cell::UPSafeCell;
+
+use super::{BlockDevice, BLOCK_SIZE};
+
+// the path is relative to the crate root : os/
+global_asm!(
+    r#"
+    .section .data
+    .global sfs_img
+    .global efs_img
+    .align 12
+sfs_img:
+    .incbin "target/fs.img"
+efs_img:
+"#
+);
+
+pub struct RamDisk {
+    image: UPSafeCell<&'static mut [u8]>,
+}
+
+impl RamDisk {
+    pub fn new() -> Self {
+        extern "C" {
+            fn sfs_img();
+            fn efs_img();
+        }
+        let image = unsafe {
+            slice::from_raw_parts_mut(
+                sfs_img as usize as *mut u8,
+                efs_img as usize - sfs_img as usize,
+            )
+        };
+        Self {
+            image: UPSafeCell::new("ram_disk", image),
+        }
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        assert_eq!(buf.len(), BLOCK_SIZE, "read_block: buffer size error");
+        let image = self.image.exclusive_access();
+        let start = block_id * BLOCK_SIZE;
+        buf.copy_from_slice(&image[start..start + BLOCK_SIZE]);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        assert_eq!(buf.len(), BLOCK_SIZE, "write_block: buffer size error");
+        let mut image = self.image.exclusive_access();
+        let start = block_id * BLOCK_SIZE;
+        image[start..start + BLOCK_SIZE].copy_from_slice(buf);
+    }
+}
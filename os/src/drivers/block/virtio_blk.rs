@@ -0,0 +1,322 @@
+// virtio block device over MMIO, simulated by qemu (virtio-blk-device)
+// both legacy (version 1) and modern (version 2) interfaces are supported
+// requests are handled one by one, polling instead of interrupts
+
+use core::{
+    mem::size_of,
+    sync::atomic::{fence, Ordering},
+};
+
+use crate::{
+    config::PAGE_BYTES,
+    kfc_util::up_safe_cell::UPSafeCell,
+    mm::{frame_alloc, FrameTracker},
+};
+
+use super::{BlockDevice, BLOCK_SIZE};
+
+pub const VIRTIO0: usize = 0x1000_1000;
+
+// MMIO registers
+const MAGIC_VALUE: usize = 0x000; // 0x74726976 : "virt"
+const VERSION: usize = 0x004; // 1 : legacy, 2 : modern
+const DEVICE_ID: usize = 0x008; // 2 : block device
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028; // legacy only
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c; // legacy only
+const QUEUE_PFN: usize = 0x040; // legacy only
+const QUEUE_READY: usize = 0x044; // modern only
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080; // modern only
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+
+const VIRTIO_MAGIC: u32 = 0x7472_6976;
+const VIRTIO_DEVICE_BLOCK: u32 = 2;
+
+// device status
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+
+// feature bit 32, selected by DRIVER_FEATURES_SEL = 1
+const VIRTIO_F_VERSION_1: u32 = 1 << 0;
+
+// descriptor flags
+const VIRTQ_DESC_F_NEXT: u16 = 1;
+const VIRTQ_DESC_F_WRITE: u16 = 2; // device writes (otherwise reads)
+
+// request types
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_S_OK: u8 = 0;
+
+// the sector size of virtio-blk is always 512
+const SECTOR_SIZE: usize = 512;
+
+// a request only needs 3 descriptors : header, data, status
+const QUEUE_SIZE: usize = 8;
+
+// the whole virtqueue lives in one frame :
+// descriptor table at 0, available ring follows it, used ring at `USED_OFFSET`
+const USED_OFFSET: usize = PAGE_BYTES / 2;
+const AVAIL_OFFSET: usize = QUEUE_SIZE * size_of::<VirtqDesc>();
+
+// the request lives in another frame
+const REQ_HEADER_OFFSET: usize = 0;
+const REQ_STATUS_OFFSET: usize = size_of::<VirtioBlkReqHeader>();
+const REQ_DATA_OFFSET: usize = SECTOR_SIZE;
+
+#[repr(C)]
+struct VirtqDesc {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct VirtqAvail {
+    flags: u16,
+    idx: u16,
+    ring: [u16; QUEUE_SIZE],
+    used_event: u16,
+}
+
+#[repr(C)]
+struct VirtqUsedElem {
+    id: u32,
+    len: u32,
+}
+
+#[repr(C)]
+struct VirtqUsed {
+    flags: u16,
+    idx: u16,
+    ring: [VirtqUsedElem; QUEUE_SIZE],
+    avail_event: u16,
+}
+
+#[repr(C)]
+struct VirtioBlkReqHeader {
+    req_type: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+fn read_reg(reg: usize) -> u32 {
+    unsafe { ((VIRTIO0 + reg) as *const u32).read_volatile() }
+}
+
+fn write_reg(reg: usize, data: u32) {
+    unsafe { ((VIRTIO0 + reg) as *mut u32).write_volatile(data) }
+}
+
+/// the frames are identically mapped in kernel space : physical address == virtual address
+struct VirtIOBlockInner {
+    /// descriptor table, available ring, used ring
+    queue_frame: FrameTracker,
+    /// header, status and data of the request
+    dma_frame: FrameTracker,
+    /// the last used index we have seen
+    used_idx: u16,
+}
+
+pub struct VirtIOBlock {
+    inner: UPSafeCell<VirtIOBlockInner>,
+}
+
+impl VirtIOBlockInner {
+    fn desc_mut(&self, i: usize) -> &'static mut VirtqDesc {
+        unsafe { &mut *((self.queue_frame.0 .0 + i * size_of::<VirtqDesc>()) as *mut VirtqDesc) }
+    }
+
+    fn avail_mut(&self) -> &'static mut VirtqAvail {
+        unsafe { &mut *((self.queue_frame.0 .0 + AVAIL_OFFSET) as *mut VirtqAvail) }
+    }
+
+    fn used(&self) -> *const VirtqUsed {
+        (self.queue_frame.0 .0 + USED_OFFSET) as *const VirtqUsed
+    }
+
+    fn dma_addr(&self, offset: usize) -> usize {
+        self.dma_frame.0 .0 + offset
+    }
+
+    /// ### send a request of one block and wait for it
+    /// the data has been put at `REQ_DATA_OFFSET` for writing
+    fn request(&mut self, block_id: usize, is_write: bool) {
+        let header = unsafe { &mut *(self.dma_addr(REQ_HEADER_OFFSET) as *mut VirtioBlkReqHeader) };
+        header.req_type = if is_write {
+            VIRTIO_BLK_T_OUT
+        } else {
+            VIRTIO_BLK_T_IN
+        };
+        header.reserved = 0;
+        header.sector = (block_id * (BLOCK_SIZE / SECTOR_SIZE)) as u64;
+        let status = self.dma_addr(REQ_STATUS_OFFSET) as *mut u8;
+        unsafe { status.write_volatile(0xff) };
+
+        // header --> data --> status
+        let desc = self.desc_mut(0);
+        desc.addr = self.dma_addr(REQ_HEADER_OFFSET) as u64;
+        desc.len = size_of::<VirtioBlkReqHeader>() as u32;
+        desc.flags = VIRTQ_DESC_F_NEXT;
+        desc.next = 1;
+
+        let desc = self.desc_mut(1);
+        desc.addr = self.dma_addr(REQ_DATA_OFFSET) as u64;
+        desc.len = BLOCK_SIZE as u32;
+        desc.flags = if is_write {
+            VIRTQ_DESC_F_NEXT
+        } else {
+            VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_WRITE
+        };
+        desc.next = 2;
+
+        let desc = self.desc_mut(2);
+        desc.addr = self.dma_addr(REQ_STATUS_OFFSET) as u64;
+        desc.len = 1;
+        desc.flags = VIRTQ_DESC_F_WRITE;
+        desc.next = 0;
+
+        // put the head of the chain into the available ring
+        let avail = self.avail_mut();
+        avail.ring[avail.idx as usize % QUEUE_SIZE] = 0;
+        fence(Ordering::SeqCst);
+        avail.idx = avail.idx.wrapping_add(1);
+        fence(Ordering::SeqCst);
+        write_reg(QUEUE_NOTIFY, 0);
+
+        // polling until the device uses it
+        let used_idx = unsafe { core::ptr::addr_of!((*self.used()).idx) };
+        while unsafe { used_idx.read_volatile() } == self.used_idx {
+            core::hint::spin_loop();
+        }
+        fence(Ordering::SeqCst);
+        self.used_idx = self.used_idx.wrapping_add(1);
+        write_reg(INTERRUPT_ACK, read_reg(INTERRUPT_STATUS) & 0x3);
+
+        assert_eq!(
+            unsafe { status.read_volatile() },
+            VIRTIO_BLK_S_OK,
+            "virtio block request failed, block id = {}",
+            block_id
+        );
+    }
+}
+
+impl VirtIOBlock {
+    pub fn new() -> Self {
+        assert_eq!(
+            read_reg(MAGIC_VALUE),
+            VIRTIO_MAGIC,
+            "no virtio device found"
+        );
+        assert_eq!(
+            read_reg(DEVICE_ID),
+            VIRTIO_DEVICE_BLOCK,
+            "the virtio device is not a block device (no -drive given?)"
+        );
+        let version = read_reg(VERSION);
+        assert!(
+            version == 1 || version == 2,
+            "unsupported virtio version {}",
+            version
+        );
+
+        // reset, then tell the device that we know how to drive it
+        write_reg(STATUS, 0);
+        let mut status = STATUS_ACKNOWLEDGE | STATUS_DRIVER;
+        write_reg(STATUS, status);
+
+        // no optional features needed, modern devices require VERSION_1
+        write_reg(DRIVER_FEATURES_SEL, 0);
+        write_reg(DRIVER_FEATURES, 0);
+        write_reg(DRIVER_FEATURES_SEL, 1);
+        write_reg(
+            DRIVER_FEATURES,
+            if version == 2 { VIRTIO_F_VERSION_1 } else { 0 },
+        );
+        if version == 2 {
+            status |= STATUS_FEATURES_OK;
+            write_reg(STATUS, status);
+            assert!(
+                read_reg(STATUS) & STATUS_FEATURES_OK != 0,
+                "virtio block device does not accept the features"
+            );
+        }
+
+        // the frames have been cleared by frame_alloc
+        let inner = VirtIOBlockInner {
+            queue_frame: frame_alloc().unwrap(),
+            dma_frame: frame_alloc().unwrap(),
+            used_idx: 0,
+        };
+
+        // initialize queue 0
+        write_reg(QUEUE_SEL, 0);
+        assert!(
+            read_reg(QUEUE_NUM_MAX) as usize >= QUEUE_SIZE,
+            "virtio block queue is too small"
+        );
+        write_reg(QUEUE_NUM, QUEUE_SIZE as u32);
+        let queue_addr = inner.queue_frame.0 .0;
+        if version == 1 {
+            write_reg(GUEST_PAGE_SIZE, PAGE_BYTES as u32);
+            write_reg(QUEUE_ALIGN, USED_OFFSET as u32);
+            write_reg(QUEUE_PFN, (queue_addr / PAGE_BYTES) as u32);
+        } else {
+            let desc = queue_addr as u64;
+            let avail = (queue_addr + AVAIL_OFFSET) as u64;
+            let used = (queue_addr + USED_OFFSET) as u64;
+            write_reg(QUEUE_DESC_LOW, desc as u32);
+            write_reg(QUEUE_DESC_HIGH, (desc >> 32) as u32);
+            write_reg(QUEUE_DRIVER_LOW, avail as u32);
+            write_reg(QUEUE_DRIVER_HIGH, (avail >> 32) as u32);
+            write_reg(QUEUE_DEVICE_LOW, used as u32);
+            write_reg(QUEUE_DEVICE_HIGH, (used >> 32) as u32);
+            write_reg(QUEUE_READY, 1);
+        }
+
+        status |= STATUS_DRIVER_OK;
+        write_reg(STATUS, status);
+
+        Self {
+            inner: UPSafeCell::new("virtio_blk", inner),
+        }
+    }
+}
+
+impl BlockDevice for VirtIOBlock {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        assert_eq!(buf.len(), BLOCK_SIZE, "read_block: buffer size error");
+        let mut inner = self.inner.exclusive_access();
+        inner.request(block_id, false);
+        let data = unsafe {
+            core::slice::from_raw_parts(inner.dma_addr(REQ_DATA_OFFSET) as *const u8, BLOCK_SIZE)
+        };
+        buf.copy_from_slice(data);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        assert_eq!(buf.len(), BLOCK_SIZE, "write_block: buffer size error");
+        let mut inner = self.inner.exclusive_access();
+        let data = unsafe {
+            core::slice::from_raw_parts_mut(inner.dma_addr(REQ_DATA_OFFSET) as *mut u8, BLOCK_SIZE)
+        };
+        data.copy_from_slice(buf);
+        inner.request(block_id, true);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod block;
+
+pub use block::{BlockDevice, BLOCK_DEVICE};
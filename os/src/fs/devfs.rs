@@ -0,0 +1,151 @@
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{
+    console::{console_getc_blocking, console_putc},
+    syscall_impl::errno::EPERM,
+};
+
+use super::vfs::{DirEntry, FileSystem, InodeType, VfsInode};
+
+/// ### the devices
+/// the device files can't be created or removed
+pub struct DevFs {
+    root: Arc<DevDir>,
+}
+
+impl DevFs {
+    pub fn new() -> Self {
+        Self {
+            root: Arc::new(DevDir {
+                devices: [
+                    ("console", Arc::new(Console) as Arc<dyn VfsInode>),
+                    ("null", Arc::new(Null)),
+                    ("zero", Arc::new(Zero)),
+                ],
+            }),
+        }
+    }
+}
+
+impl FileSystem for DevFs {
+    fn fs_type(&self) -> &'static str {
+        "devfs"
+    }
+
+    fn root_inode(&self) -> Arc<dyn VfsInode> {
+        self.root.clone()
+    }
+}
+
+pub struct DevDir {
+    devices: [(&'static str, Arc<dyn VfsInode>); 3],
+}
+
+impl VfsInode for DevDir {
+    fn inode_type(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        self.devices
+            .iter()
+            .find(|(dev_name, _)| *dev_name == name)
+            .map(|(_, dev)| dev.clone())
+    }
+
+    fn create(&self, _name: &str, _inode_type: InodeType) -> Result<Arc<dyn VfsInode>, isize> {
+        Err(EPERM)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<(), isize> {
+        Err(EPERM)
+    }
+
+    fn entries(&self) -> Vec<DirEntry> {
+        self.devices
+            .iter()
+            .enumerate()
+            .map(|(idx, (name, dev))| DirEntry {
+                name: (*name).into(),
+                // the root is 1
+                inode_id: idx + 2,
+                inode_type: dev.inode_type(),
+            })
+            .collect()
+    }
+}
+
+/// stdin, stdout and stderr
+pub struct Console;
+
+/// reading gets nothing, writing always succeeds
+pub struct Null;
+
+/// reading gets zeros, writing always succeeds
+pub struct Zero;
+
+impl VfsInode for Console {
+    fn inode_type(&self) -> InodeType {
+        InodeType::CharDevice
+    }
+
+    /// read one byte each time, block until the console has input
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        if buf.is_empty() {
+            return 0;
+        }
+
+        match console_getc_blocking() {
+            Some(c) => {
+                unsafe { buf.as_mut_ptr().write_volatile(c) }
+                1
+            }
+            // killed, the thread exits before back to user space
+            None => 0,
+        }
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        for &c in buf.iter() {
+            console_putc(c);
+        }
+        buf.len()
+    }
+}
+
+impl VfsInode for Null {
+    fn inode_type(&self) -> InodeType {
+        InodeType::CharDevice
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        buf.len()
+    }
+}
+
+impl VfsInode for Zero {
+    fn inode_type(&self) -> InodeType {
+        InodeType::CharDevice
+    }
+
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        buf.fill(0);
+        buf.len()
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        buf.len()
+    }
+}
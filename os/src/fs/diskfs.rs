@@ -0,0 +1,163 @@
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+use kfc_fs::{Inode, KfcFileSystem};
+use lazy_static::lazy_static;
+
+use crate::{
+    drivers::BLOCK_DEVICE,
+    kfc_util::spin_lock::{RawSpinLock, SpinLock},
+    syscall_impl::errno::{ENOENT, ENOSPC},
+};
+
+use super::vfs::{DirEntry, FileSystem, InodeType, VfsInode};
+
+/// kfc-fs locked by the kernel spin locks, so a task is not switched out holding them
+type KfcInode = Inode<RawSpinLock>;
+
+lazy_static! {
+    /// there's only one disk, mounting it again shares the same filesystem
+    pub static ref DISK_FS: Arc<DiskFs> = Arc::new(DiskFs::new());
+    /// ### the inodes in use, keyed by the inode id
+    /// looking up an inode twice gets the same `DiskInode`, so an unlinked one knows
+    /// whether it's still opened
+    static ref DISK_INODES: SpinLock<BTreeMap<u32, Weak<DiskInode>>> =
+        SpinLock::new("disk_inodes", BTreeMap::new());
+}
+
+/// kfc-fs on `BLOCK_DEVICE`
+pub struct DiskFs {
+    root: Arc<DiskInode>,
+}
+
+impl DiskFs {
+    fn new() -> Self {
+        let fs = KfcFileSystem::<RawSpinLock>::open(BLOCK_DEVICE.clone());
+        Self {
+            root: DiskInode::get(Arc::new(KfcFileSystem::root_inode(&fs))),
+        }
+    }
+}
+
+/// ### an inode of kfc-fs shared by its users
+/// an unlinked inode is released when the last user drops it
+pub struct DiskInode {
+    inode: Arc<KfcInode>,
+    unlinked: AtomicBool,
+}
+
+impl DiskInode {
+    /// the `DiskInode` in use with the same id, or a new one
+    fn get(inode: Arc<KfcInode>) -> Arc<Self> {
+        let mut inodes = DISK_INODES.lock();
+        if let Some(disk_inode) = inodes.get(&inode.inode_id()).and_then(Weak::upgrade) {
+            return disk_inode;
+        }
+        let disk_inode = Arc::new(Self {
+            inode,
+            unlinked: AtomicBool::new(false),
+        });
+        inodes.insert(disk_inode.inode.inode_id(), Arc::downgrade(&disk_inode));
+        disk_inode
+    }
+}
+
+impl Drop for DiskInode {
+    fn drop(&mut self) {
+        let inode_id = self.inode.inode_id();
+        let mut inodes = DISK_INODES.lock();
+        // a new `DiskInode` may have taken the slot after the last strong reference is gone
+        if let Some(weak) = inodes.get(&inode_id) {
+            if weak.strong_count() == 0 {
+                inodes.remove(&inode_id);
+            }
+        }
+        drop(inodes);
+        if self.unlinked.load(Ordering::Acquire) {
+            self.inode.release();
+        }
+    }
+}
+
+impl FileSystem for DiskFs {
+    fn fs_type(&self) -> &'static str {
+        "kfcfs"
+    }
+
+    fn root_inode(&self) -> Arc<dyn VfsInode> {
+        self.root.clone()
+    }
+
+    fn sync(&self) {
+        self.root.inode.sync();
+    }
+}
+
+impl VfsInode for DiskInode {
+    fn inode_type(&self) -> InodeType {
+        if self.inode.is_dir() {
+            InodeType::Dir
+        } else {
+            InodeType::File
+        }
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.inode.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.inode.write_at(offset, buf)
+    }
+
+    fn truncate(&self) {
+        self.inode.clear();
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        self.inode
+            .find(name)
+            .map(|inode| DiskInode::get(inode) as Arc<dyn VfsInode>)
+    }
+
+    fn create(&self, name: &str, inode_type: InodeType) -> Result<Arc<dyn VfsInode>, isize> {
+        let inode = match inode_type {
+            InodeType::Dir => self.inode.create_dir(name),
+            _ => self.inode.create(name),
+        };
+        inode
+            .map(|inode| DiskInode::get(inode) as Arc<dyn VfsInode>)
+            .ok_or(ENOSPC)
+    }
+
+    /// the opened files keep using the inode, it's released after they are closed
+    fn unlink(&self, name: &str) -> Result<(), isize> {
+        let inode = self.inode.remove_dirent(name).ok_or(ENOENT)?;
+        DiskInode::get(inode)
+            .unlinked
+            .store(true, Ordering::Release);
+        Ok(())
+    }
+
+    fn entries(&self) -> Vec<DirEntry> {
+        self.inode
+            .entries()
+            .into_iter()
+            .map(|(name, inode_id): (String, u32)| {
+                let inode_type = match self.inode.find(&name) {
+                    Some(inode) if inode.is_dir() => InodeType::Dir,
+                    _ => InodeType::File,
+                };
+                DirEntry {
+                    name,
+                    inode_id: inode_id as usize,
+                    inode_type,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,299 @@
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use bitflags::bitflags;
+
+use crate::{
+    kfc_util::up_safe_cell::UPSafeCell,
+    mm::page_cache::invalidate_pages,
+    syscall_impl::errno::{EBUSY, EEXIST, EINVAL, EISDIR, ENOENT, ENOTDIR, ENOTEMPTY},
+};
+
+use super::{
+    copy_to_bufs,
+    mount::{is_mount_point, mount_root},
+    vfs::{Dentry, InodeType, VfsInode},
+    File,
+};
+
+bitflags! {
+    /// the same as Linux
+    pub struct OpenFlags: u32 {
+        const RDONLY = 0;
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        const CREAT = 1 << 6;
+        const TRUNC = 1 << 9;
+    }
+}
+
+impl OpenFlags {
+    /// return (readable, writable)
+    pub fn read_write(&self) -> (bool, bool) {
+        if self.contains(Self::WRONLY) {
+            (false, true)
+        } else if self.contains(Self::RDWR) {
+            (true, true)
+        } else {
+            (true, false)
+        }
+    }
+}
+
+// d_type of linux_dirent64
+const DT_CHR: u8 = 2;
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+
+/// ### an opened file of the vfs
+/// the offset is shared by the fds after dup or fork
+/// - for a directory, the offset is the index of the next entry
+pub struct OSInode {
+    readable: bool,
+    writable: bool,
+    dentry: Dentry,
+    inner: UPSafeCell<OSInodeInner>,
+}
+
+pub struct OSInodeInner {
+    offset: usize,
+}
+
+impl OSInode {
+    pub fn new(readable: bool, writable: bool, dentry: Dentry) -> Self {
+        Self {
+            readable,
+            writable,
+            dentry,
+            inner: UPSafeCell::new("os_inode", OSInodeInner { offset: 0 }),
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.dentry.inode.is_dir()
+    }
+
+    /// from the current offset to the end
+    pub fn read_all(&self) -> Vec<u8> {
+        let mut inner = self.inner.exclusive_access();
+        let mut buffer = [0u8; 512];
+        let mut ret = Vec::new();
+        loop {
+            let len = self.dentry.inode.read_at(inner.offset, &mut buffer);
+            if len == 0 {
+                break;
+            }
+            inner.offset += len;
+            ret.extend_from_slice(&buffer[..len]);
+        }
+        ret
+    }
+}
+
+/// ### the components of the absolute path
+/// relative paths start from `cwd`, "." and ".." are resolved
+pub fn resolve_path(cwd: &str, path: &str) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::new();
+    let full_path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        cwd.to_string() + "/" + path
+    };
+    for name in full_path.split('/') {
+        match name {
+            "" | "." => {}
+            // the parent of root is root
+            ".." => {
+                ret.pop();
+            }
+            _ => ret.push(name.to_string()),
+        }
+    }
+    ret
+}
+
+/// the path built from components : "/a/b"
+pub fn path_string(names: &[String]) -> String {
+    if names.is_empty() {
+        return "/".to_string();
+    }
+    names.iter().map(|name| "/".to_string() + name).collect()
+}
+
+/// ### walk from the root of the innermost mounted filesystem
+/// Err : errno (positive)
+fn walk(names: &[String]) -> Result<Arc<dyn VfsInode>, isize> {
+    let (mut inode, covered) = mount_root(names);
+    for name in &names[covered..] {
+        if !inode.is_dir() {
+            return Err(ENOTDIR);
+        }
+        inode = inode.lookup(name).ok_or(ENOENT)?;
+    }
+    Ok(inode)
+}
+
+pub fn lookup(cwd: &str, path: &str) -> Result<Dentry, isize> {
+    let names = resolve_path(cwd, path);
+    Ok(Dentry {
+        inode: walk(&names)?,
+        path: path_string(&names),
+    })
+}
+
+/// find the inode of a file or directory by path
+pub fn find_inode(cwd: &str, path: &str) -> Result<Arc<dyn VfsInode>, isize> {
+    walk(&resolve_path(cwd, path))
+}
+
+/// return (parent directory, name), Err : the path is root, or the parent is not found
+fn find_parent(cwd: &str, path: &str) -> Result<(Arc<dyn VfsInode>, String), isize> {
+    let mut names = resolve_path(cwd, path);
+    let name = names.pop().ok_or(EEXIST)?;
+    let parent = walk(&names)?;
+    if !parent.is_dir() {
+        return Err(ENOTDIR);
+    }
+    Ok((parent, name))
+}
+
+/// ### find the file by path, or create it
+/// directories can only be opened as read-only
+pub fn open_file(cwd: &str, path: &str, flags: OpenFlags) -> Result<Arc<OSInode>, isize> {
+    let (readable, writable) = flags.read_write();
+
+    let dentry = match lookup(cwd, path) {
+        Ok(dentry) => {
+            if dentry.inode.is_dir() && (writable || flags.contains(OpenFlags::TRUNC)) {
+                return Err(EISDIR);
+            }
+            if flags.contains(OpenFlags::TRUNC) {
+                dentry.inode.truncate();
+                invalidate_pages(&dentry.inode);
+            }
+            dentry
+        }
+        Err(ENOENT) if flags.contains(OpenFlags::CREAT) => {
+            let (parent, name) = find_parent(cwd, path)?;
+            Dentry {
+                inode: parent.create(&name, InodeType::File)?,
+                path: path_string(&resolve_path(cwd, path)),
+            }
+        }
+        Err(errno) => return Err(errno),
+    };
+    Ok(Arc::new(OSInode::new(readable, writable, dentry)))
+}
+
+pub fn make_dir(cwd: &str, path: &str) -> Result<(), isize> {
+    let (parent, name) = find_parent(cwd, path)?;
+    if parent.lookup(&name).is_some() {
+        return Err(EEXIST);
+    }
+    parent.create(&name, InodeType::Dir).map(|_| ())
+}
+
+/// ### remove a file, or an empty directory if `is_dir`
+pub fn remove_inode(cwd: &str, path: &str, is_dir: bool) -> Result<(), isize> {
+    let (parent, name) = find_parent(cwd, path).map_err(|errno| match errno {
+        // can't remove the root
+        EEXIST => EBUSY,
+        errno => errno,
+    })?;
+    let inode = parent.lookup(&name).ok_or(ENOENT)?;
+    match (is_dir, inode.is_dir()) {
+        (true, false) => return Err(ENOTDIR),
+        (false, true) => return Err(EISDIR),
+        (true, true) if is_mount_point(&resolve_path(cwd, path)) => return Err(EBUSY),
+        (true, true) if !inode.entries().is_empty() => return Err(ENOTEMPTY),
+        _ => {}
+    }
+    parent.unlink(&name)
+}
+
+impl File for OSInode {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, mut bufs: Vec<&'static mut [u8]>) -> usize {
+        let mut total = 0;
+        for slice in bufs.iter_mut() {
+            // not locked while reading, a device may block the task
+            let offset = self.inner.exclusive_access().offset;
+            let len = self.dentry.inode.read_at(offset, slice);
+            self.inner.exclusive_access().offset = offset + len;
+            total += len;
+            // end of file, or a device has no more data now
+            if len < slice.len() {
+                break;
+            }
+        }
+        total
+    }
+
+    fn write(&self, bufs: Vec<&'static mut [u8]>) -> Result<usize, isize> {
+        let mut inner = self.inner.exclusive_access();
+        let mut total = 0;
+        for slice in bufs.iter() {
+            let len = self.dentry.inode.write_at(inner.offset, slice);
+            inner.offset += len;
+            total += len;
+            // no space left
+            if len < slice.len() {
+                break;
+            }
+        }
+        // only the regular files can be mapped
+        if total > 0 && self.dentry.inode.inode_type() == InodeType::File {
+            invalidate_pages(&self.dentry.inode);
+        }
+        Ok(total)
+    }
+
+    fn getdents(&self, bufs: Vec<&'static mut [u8]>) -> Result<usize, isize> {
+        if !self.is_dir() {
+            return Err(ENOTDIR);
+        }
+        let mut inner = self.inner.exclusive_access();
+        let buf_len = bufs.iter().map(|slice| slice.len()).sum::<usize>();
+        let mut data = Vec::new();
+
+        // "." and ".." are not listed
+        for entry in self.dentry.inode.entries().into_iter().skip(inner.offset) {
+            // d_ino, d_off, d_reclen, d_type, d_name with '\0', aligned to 8
+            let reclen = (8 + 8 + 2 + 1 + entry.name.len() + 1 + 7) & !7;
+            if data.len() + reclen > buf_len {
+                // not even one entry fits, it's not the end of the directory
+                if data.is_empty() {
+                    return Err(EINVAL);
+                }
+                break;
+            }
+            let d_type = match entry.inode_type {
+                InodeType::File => DT_REG,
+                InodeType::Dir => DT_DIR,
+                InodeType::CharDevice => DT_CHR,
+            };
+            inner.offset += 1;
+            let start = data.len();
+            data.extend_from_slice(&(entry.inode_id as u64).to_le_bytes());
+            data.extend_from_slice(&(inner.offset as i64).to_le_bytes());
+            data.extend_from_slice(&(reclen as u16).to_le_bytes());
+            data.push(d_type);
+            data.extend_from_slice(entry.name.as_bytes());
+            data.resize(start + reclen, 0);
+        }
+        Ok(copy_to_bufs(bufs, &data))
+    }
+
+    fn dentry(&self) -> Option<Dentry> {
+        Some(self.dentry.clone())
+    }
+}
@@ -0,0 +1,55 @@
+use alloc::vec::Vec;
+
+use crate::syscall_impl::errno::ENOTDIR;
+
+pub mod devfs;
+pub mod diskfs;
+pub mod inode;
+pub mod mount;
+pub mod pipe;
+pub mod procfs;
+pub mod tmpfs;
+pub mod vfs;
+
+pub use inode::{
+    find_inode, make_dir, open_file, path_string, remove_inode, resolve_path, OSInode, OpenFlags,
+};
+pub use mount::{mount, umount};
+pub use pipe::make_pipe;
+pub use vfs::{Dentry, InodeType};
+
+/// ### everything can be read or written by a file descriptor
+/// buffers are the user's memory translated into kernel's, may be split by pages
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+    /// return the number of bytes read
+    fn read(&self, bufs: Vec<&'static mut [u8]>) -> usize;
+    /// ### return the number of bytes written
+    /// Err : errno (positive), nothing written
+    fn write(&self, bufs: Vec<&'static mut [u8]>) -> Result<usize, isize>;
+    /// ### fill the buffers with `linux_dirent64`s from the current position
+    /// - return the number of bytes filled, 0 : end of the directory
+    /// - Err : errno (positive), the buffers can't hold the next entry, or not a directory
+    fn getdents(&self, _bufs: Vec<&'static mut [u8]>) -> Result<usize, isize> {
+        Err(ENOTDIR)
+    }
+    /// the path of an opened file of the vfs
+    fn dentry(&self) -> Option<Dentry> {
+        None
+    }
+}
+
+/// copy `data` into the buffers, return the number of bytes copied
+pub fn copy_to_bufs(bufs: Vec<&'static mut [u8]>, data: &[u8]) -> usize {
+    let mut copied = 0;
+    for slice in bufs {
+        if copied == data.len() {
+            break;
+        }
+        let len = slice.len().min(data.len() - copied);
+        slice[..len].copy_from_slice(&data[copied..copied + len]);
+        copied += len;
+    }
+    copied
+}
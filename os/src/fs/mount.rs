@@ -0,0 +1,117 @@
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
+use lazy_static::lazy_static;
+
+use crate::{
+    kfc_util::mutex::Mutex,
+    syscall_impl::errno::{EBUSY, EINVAL, ENODEV, ENOTDIR},
+};
+
+use super::{
+    devfs::DevFs,
+    diskfs::DISK_FS,
+    inode::{find_inode, resolve_path},
+    procfs::ProcFs,
+    tmpfs::TmpFs,
+    vfs::{FileSystem, InodeType, VfsInode},
+};
+
+/// a filesystem covering the directory at `path`
+pub struct MountPoint {
+    /// the components of the absolute path
+    pub path: Vec<String>,
+    pub fs: Arc<dyn FileSystem>,
+}
+
+lazy_static! {
+    /// ### the disk at "/", tmpfs at "/tmp", devfs at "/dev", procfs at "/proc"
+    /// the mount point directories are created on the disk if missing
+    pub static ref MOUNT_TABLE: Mutex<Vec<MountPoint>> = {
+        let root = DISK_FS.root_inode();
+        for name in ["tmp", "dev", "proc"] {
+            if root.lookup(name).is_none() {
+                root.create(name, InodeType::Dir)
+                    .expect("failed to create the mount point");
+            }
+        }
+        Mutex::new(
+            "MOUNT_TABLE",
+            vec![
+                MountPoint {
+                    path: Vec::new(),
+                    fs: DISK_FS.clone(),
+                },
+                MountPoint {
+                    path: vec!["tmp".into()],
+                    fs: Arc::new(TmpFs::new()),
+                },
+                MountPoint {
+                    path: vec!["dev".into()],
+                    fs: Arc::new(DevFs::new()),
+                },
+                MountPoint {
+                    path: vec!["proc".into()],
+                    fs: Arc::new(ProcFs),
+                },
+            ],
+        )
+    };
+}
+
+/// ### the root inode of the innermost filesystem holding the path
+/// return (root inode, the number of components covered by the mount point)
+pub fn mount_root(names: &[String]) -> (Arc<dyn VfsInode>, usize) {
+    let table = MOUNT_TABLE.lock();
+    let mount_point = table
+        .iter()
+        .filter(|mp| names.starts_with(&mp.path))
+        .max_by_key(|mp| mp.path.len())
+        .expect("no filesystem mounted at /");
+    (mount_point.fs.root_inode(), mount_point.path.len())
+}
+
+pub fn is_mount_point(names: &[String]) -> bool {
+    MOUNT_TABLE.lock().iter().any(|mp| mp.path == names)
+}
+
+fn new_fs(fs_type: &str) -> Result<Arc<dyn FileSystem>, isize> {
+    match fs_type {
+        "kfcfs" => Ok(DISK_FS.clone()),
+        "tmpfs" => Ok(Arc::new(TmpFs::new())),
+        "devfs" => Ok(Arc::new(DevFs::new())),
+        "procfs" => Ok(Arc::new(ProcFs)),
+        _ => Err(ENODEV),
+    }
+}
+
+/// ### mount a new filesystem of `fs_type` on the directory
+/// the content of the directory is hidden until umount
+pub fn mount(cwd: &str, target: &str, fs_type: &str) -> Result<(), isize> {
+    if !find_inode(cwd, target)?.is_dir() {
+        return Err(ENOTDIR);
+    }
+    let path = resolve_path(cwd, target);
+    if is_mount_point(&path) {
+        return Err(EBUSY);
+    }
+    let fs = new_fs(fs_type)?;
+    MOUNT_TABLE.lock().push(MountPoint { path, fs });
+    Ok(())
+}
+
+/// ### remove the filesystem mounted on the directory
+/// the opened files of it are still available, the cached data is written back
+pub fn umount(cwd: &str, target: &str) -> Result<(), isize> {
+    let path = resolve_path(cwd, target);
+    let mut table = MOUNT_TABLE.lock();
+    let idx = table.iter().position(|mp| mp.path == path).ok_or(EINVAL)?;
+    // the root, or another filesystem is mounted inside
+    if path.is_empty()
+        || table
+            .iter()
+            .any(|mp| mp.path.len() > path.len() && mp.path.starts_with(&path))
+    {
+        return Err(EBUSY);
+    }
+    table.remove(idx).fs.sync();
+    Ok(())
+}
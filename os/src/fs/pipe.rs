@@ -0,0 +1,209 @@
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+use crate::{
+    kfc_util::up_safe_cell::UPSafeCell,
+    syscall_impl::errno::EPIPE,
+    task::{suspend_cur_run_next, PROCESSOR},
+};
+
+use super::File;
+
+const PIPE_BUFFER_SIZE: usize = 0x1000; // 4KB
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RingBufferStatus {
+    Full,
+    Empty,
+    Normal,
+}
+
+pub struct PipeRingBuffer {
+    buf: [u8; PIPE_BUFFER_SIZE],
+    head: usize,
+    tail: usize,
+    status: RingBufferStatus,
+    /// all the read ends closed : no one reads, writers should stop
+    read_end: Weak<Pipe>,
+    /// all the write ends closed : readers get EOF
+    write_end: Weak<Pipe>,
+}
+
+impl PipeRingBuffer {
+    fn new() -> Self {
+        Self {
+            buf: [0; PIPE_BUFFER_SIZE],
+            head: 0,
+            tail: 0,
+            status: RingBufferStatus::Empty,
+            read_end: Weak::new(),
+            write_end: Weak::new(),
+        }
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let c = self.buf[self.head];
+        self.head = (self.head + 1) % PIPE_BUFFER_SIZE;
+        self.status = if self.head == self.tail {
+            RingBufferStatus::Empty
+        } else {
+            RingBufferStatus::Normal
+        };
+        c
+    }
+
+    fn write_byte(&mut self, c: u8) {
+        self.buf[self.tail] = c;
+        self.tail = (self.tail + 1) % PIPE_BUFFER_SIZE;
+        self.status = if self.head == self.tail {
+            RingBufferStatus::Full
+        } else {
+            RingBufferStatus::Normal
+        };
+    }
+
+    fn available_read(&self) -> usize {
+        match self.status {
+            RingBufferStatus::Empty => 0,
+            RingBufferStatus::Full => PIPE_BUFFER_SIZE,
+            RingBufferStatus::Normal => {
+                (self.tail + PIPE_BUFFER_SIZE - self.head) % PIPE_BUFFER_SIZE
+            }
+        }
+    }
+
+    fn available_write(&self) -> usize {
+        PIPE_BUFFER_SIZE - self.available_read()
+    }
+
+    fn all_read_ends_closed(&self) -> bool {
+        self.read_end.upgrade().is_none()
+    }
+
+    fn all_write_ends_closed(&self) -> bool {
+        self.write_end.upgrade().is_none()
+    }
+}
+
+/// the current thread should stop waiting for the pipe and exit
+fn cur_killed() -> bool {
+    PROCESSOR
+        .current_arc()
+        .map_or(false, |task| task.is_killed())
+}
+
+/// ### one end of a pipe
+/// the ends are shared by `Arc` after dup or fork, and closed when the last one is dropped
+pub struct Pipe {
+    readable: bool,
+    writable: bool,
+    buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+}
+
+/// return (`read_end`, `write_end`)
+pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+    let buffer = Arc::new(UPSafeCell::new("pipe", PipeRingBuffer::new()));
+    let read_end = Arc::new(Pipe {
+        readable: true,
+        writable: false,
+        buffer: buffer.clone(),
+    });
+    let write_end = Arc::new(Pipe {
+        readable: false,
+        writable: true,
+        buffer: buffer.clone(),
+    });
+    let mut ring_buffer = buffer.exclusive_access();
+    ring_buffer.read_end = Arc::downgrade(&read_end);
+    ring_buffer.write_end = Arc::downgrade(&write_end);
+    drop(ring_buffer);
+    (read_end, write_end)
+}
+
+impl File for Pipe {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    /// yield while the pipe is empty, return 0 (EOF) if all the write ends are closed
+    fn read(&self, bufs: Vec<&'static mut [u8]>) -> usize {
+        assert!(self.readable);
+        let want = bufs.iter().map(|slice| slice.len()).sum::<usize>();
+        if want == 0 {
+            return 0;
+        }
+
+        let mut bytes = bufs.into_iter().flat_map(|slice| slice.iter_mut());
+        loop {
+            let mut ring_buffer = self.buffer.exclusive_access();
+            let available = ring_buffer.available_read();
+            if available == 0 {
+                if ring_buffer.all_write_ends_closed() {
+                    return 0;
+                }
+                // the buffer must be released before switching
+                drop(ring_buffer);
+                // the process has exited, never back to user space
+                if cur_killed() {
+                    return 0;
+                }
+                suspend_cur_run_next();
+                continue;
+            }
+
+            // read what we have now, no waiting for the whole buffer
+            let mut read_len = 0;
+            for _ in 0..available {
+                match bytes.next() {
+                    Some(byte) => *byte = ring_buffer.read_byte(),
+                    None => break,
+                }
+                read_len += 1;
+            }
+            return read_len;
+        }
+    }
+
+    /// ### yield while the pipe is full, stop early if all the read ends are closed
+    /// EPIPE : all the read ends are closed before any byte is written
+    fn write(&self, bufs: Vec<&'static mut [u8]>) -> Result<usize, isize> {
+        assert!(self.writable);
+        let want = bufs.iter().map(|slice| slice.len()).sum::<usize>();
+
+        let mut bytes = bufs.into_iter().flat_map(|slice| slice.iter());
+        let mut write_len = 0;
+        while write_len < want {
+            let mut ring_buffer = self.buffer.exclusive_access();
+            if ring_buffer.all_read_ends_closed() {
+                if write_len == 0 {
+                    return Err(EPIPE);
+                }
+                break;
+            }
+            let available = ring_buffer.available_write();
+            if available == 0 {
+                drop(ring_buffer);
+                if cur_killed() {
+                    break;
+                }
+                suspend_cur_run_next();
+                continue;
+            }
+
+            for _ in 0..available {
+                match bytes.next() {
+                    Some(&byte) => ring_buffer.write_byte(byte),
+                    None => break,
+                }
+                write_len += 1;
+            }
+        }
+        Ok(write_len)
+    }
+}
@@ -0,0 +1,254 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use crate::{
+    config::PAGE_BYTES,
+    kfc_sbi::timer::{get_time, CLOCK_FREQ},
+    mm::{frame_stats, heap_stats, MapPerm},
+    syscall_impl::errno::EPERM,
+    task::{all_processes, find_process, PROCESSOR},
+};
+
+use super::vfs::{DirEntry, FileSystem, InodeType, VfsInode};
+
+/// ### the kernel state as read-only files
+/// the content is generated from the live state on each read
+pub struct ProcFs;
+
+impl FileSystem for ProcFs {
+    fn fs_type(&self) -> &'static str {
+        "procfs"
+    }
+
+    fn root_inode(&self) -> Arc<dyn VfsInode> {
+        Arc::new(ProcRoot)
+    }
+}
+
+/// "/proc" : meminfo, uptime, self and a directory for each process
+pub struct ProcRoot;
+
+/// "/proc/<pid>" : status and maps
+pub struct ProcPidDir {
+    pid: usize,
+}
+
+#[derive(Clone, Copy)]
+pub enum ProcFile {
+    Meminfo,
+    Uptime,
+    Status(usize),
+    Maps(usize),
+}
+
+// inode ids : the root is 1, a process's directory and files take a group of 4
+fn pid_dir_inode_id(pid: usize) -> usize {
+    (pid + 1) * 4
+}
+
+impl ProcFile {
+    fn inode_id(&self) -> usize {
+        match self {
+            ProcFile::Meminfo => 2,
+            ProcFile::Uptime => 3,
+            ProcFile::Status(pid) => pid_dir_inode_id(*pid) + 1,
+            ProcFile::Maps(pid) => pid_dir_inode_id(*pid) + 2,
+        }
+    }
+
+    /// empty if the process has been reaped
+    fn content(&self) -> String {
+        match *self {
+            ProcFile::Meminfo => meminfo(),
+            ProcFile::Uptime => {
+                let ticks = get_time();
+                format!(
+                    "{}.{:02}\n",
+                    ticks / CLOCK_FREQ,
+                    ticks % CLOCK_FREQ * 100 / CLOCK_FREQ
+                )
+            }
+            ProcFile::Status(pid) => find_process(pid).map_or(String::new(), |process| {
+                let children = process
+                    .get_children()
+                    .iter()
+                    .map(|child| child.pid.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let (rss, shared) = process.resident_pages();
+                format!(
+                    "Name:\t{}\nPid:\t{}\nPPid:\t{}\nState:\t{:?}\nChildren:\t{}\nExitCode:\t{}\nVmRSS:\t{} kB\nRssShared:\t{} kB\n",
+                    process.get_name(),
+                    *process.pid,
+                    process.get_parent().map_or(0, |parent| *parent.pid),
+                    process.status(),
+                    children,
+                    process.get_exit_code(),
+                    rss * PAGE_BYTES / 1024,
+                    shared * PAGE_BYTES / 1024,
+                )
+            }),
+            ProcFile::Maps(pid) => find_process(pid).map_or(String::new(), |process| {
+                process
+                    .map_areas_info()
+                    .iter()
+                    .map(|(vp_range, map_perm, map_type)| {
+                        let perm = |flag, c| if map_perm.contains(flag) { c } else { '-' };
+                        format!(
+                            "{:016x}-{:016x} {}{}{}{} {}\n",
+                            vp_range.start.0,
+                            vp_range.end.0,
+                            perm(MapPerm::R, 'r'),
+                            perm(MapPerm::W, 'w'),
+                            perm(MapPerm::X, 'x'),
+                            perm(MapPerm::U, 'u'),
+                            map_type,
+                        )
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+fn meminfo() -> String {
+    // the counters are copied out before formatting, which allocates on the heap
+    let (total_frames, free_frames) = frame_stats();
+    let (heap_user, heap_real, heap_total) = heap_stats();
+    format!(
+        "FrameTotal:\t{} kB\nFrameFree:\t{} kB\nHeapUser:\t{} kB\nHeapReal:\t{} kB\nHeapTotal:\t{} kB\n",
+        total_frames * PAGE_BYTES / 1024,
+        free_frames * PAGE_BYTES / 1024,
+        heap_user / 1024,
+        heap_real / 1024,
+        heap_total / 1024,
+    )
+}
+
+impl VfsInode for ProcFile {
+    fn inode_type(&self) -> InodeType {
+        InodeType::File
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let content = self.content();
+        let content = content.as_bytes();
+        if offset >= content.len() {
+            return 0;
+        }
+        let len = buf.len().min(content.len() - offset);
+        buf[..len].copy_from_slice(&content[offset..offset + len]);
+        len
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+}
+
+impl VfsInode for ProcRoot {
+    fn inode_type(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        match name {
+            "meminfo" => Some(Arc::new(ProcFile::Meminfo)),
+            "uptime" => Some(Arc::new(ProcFile::Uptime)),
+            "self" => {
+                let pid = *PROCESSOR.current_process()?.pid;
+                Some(Arc::new(ProcPidDir { pid }))
+            }
+            _ => {
+                let pid = name.parse::<usize>().ok()?;
+                find_process(pid)?;
+                Some(Arc::new(ProcPidDir { pid }))
+            }
+        }
+    }
+
+    fn create(&self, _name: &str, _inode_type: InodeType) -> Result<Arc<dyn VfsInode>, isize> {
+        Err(EPERM)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<(), isize> {
+        Err(EPERM)
+    }
+
+    fn entries(&self) -> Vec<DirEntry> {
+        let mut ret = Vec::from([
+            DirEntry {
+                name: "meminfo".into(),
+                inode_id: ProcFile::Meminfo.inode_id(),
+                inode_type: InodeType::File,
+            },
+            DirEntry {
+                name: "uptime".into(),
+                inode_id: ProcFile::Uptime.inode_id(),
+                inode_type: InodeType::File,
+            },
+        ]);
+        ret.extend(all_processes().iter().map(|process| DirEntry {
+            name: process.pid.to_string(),
+            inode_id: pid_dir_inode_id(*process.pid),
+            inode_type: InodeType::Dir,
+        }));
+        ret
+    }
+}
+
+impl VfsInode for ProcPidDir {
+    fn inode_type(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        match name {
+            "status" => Some(Arc::new(ProcFile::Status(self.pid))),
+            "maps" => Some(Arc::new(ProcFile::Maps(self.pid))),
+            _ => None,
+        }
+    }
+
+    fn create(&self, _name: &str, _inode_type: InodeType) -> Result<Arc<dyn VfsInode>, isize> {
+        Err(EPERM)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<(), isize> {
+        Err(EPERM)
+    }
+
+    fn entries(&self) -> Vec<DirEntry> {
+        [
+            ("status", ProcFile::Status(self.pid)),
+            ("maps", ProcFile::Maps(self.pid)),
+        ]
+        .iter()
+        .map(|(name, file)| DirEntry {
+            name: (*name).into(),
+            inode_id: file.inode_id(),
+            inode_type: InodeType::File,
+        })
+        .collect()
+    }
+}
@@ -0,0 +1,130 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+
+use crate::kfc_util::up_safe_cell::UPSafeCell;
+
+use super::vfs::{DirEntry, FileSystem, InodeType, VfsInode};
+
+/// inode ids are unique among all the tmpfs instances
+static NEXT_INODE_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// ### a filesystem in the kernel heap
+/// the content is lost after umount
+pub struct TmpFs {
+    root: Arc<TmpInode>,
+}
+
+impl TmpFs {
+    pub fn new() -> Self {
+        Self {
+            root: Arc::new(TmpInode::new(InodeType::Dir)),
+        }
+    }
+}
+
+impl FileSystem for TmpFs {
+    fn fs_type(&self) -> &'static str {
+        "tmpfs"
+    }
+
+    fn root_inode(&self) -> Arc<dyn VfsInode> {
+        self.root.clone()
+    }
+}
+
+pub struct TmpInode {
+    inode_id: usize,
+    inode_type: InodeType,
+    inner: UPSafeCell<TmpInodeInner>,
+}
+
+pub struct TmpInodeInner {
+    /// file only
+    data: Vec<u8>,
+    /// directory only
+    children: BTreeMap<String, Arc<TmpInode>>,
+}
+
+impl TmpInode {
+    fn new(inode_type: InodeType) -> Self {
+        Self {
+            inode_id: NEXT_INODE_ID.fetch_add(1, Ordering::Relaxed),
+            inode_type,
+            inner: UPSafeCell::new(
+                "tmpfs_inode",
+                TmpInodeInner {
+                    data: Vec::new(),
+                    children: BTreeMap::new(),
+                },
+            ),
+        }
+    }
+}
+
+impl VfsInode for TmpInode {
+    fn inode_type(&self) -> InodeType {
+        self.inode_type
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let inner = self.inner.exclusive_access();
+        if offset >= inner.data.len() {
+            return 0;
+        }
+        let len = buf.len().min(inner.data.len() - offset);
+        buf[..len].copy_from_slice(&inner.data[offset..offset + len]);
+        len
+    }
+
+    /// the hole before `offset` is filled with zeros
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let end = offset + buf.len();
+        if end > inner.data.len() {
+            inner.data.resize(end, 0);
+        }
+        inner.data[offset..end].copy_from_slice(buf);
+        buf.len()
+    }
+
+    fn truncate(&self) {
+        self.inner.exclusive_access().data.clear();
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        self.inner
+            .exclusive_access()
+            .children
+            .get(name)
+            .map(|inode| inode.clone() as Arc<dyn VfsInode>)
+    }
+
+    fn create(&self, name: &str, inode_type: InodeType) -> Result<Arc<dyn VfsInode>, isize> {
+        let inode = Arc::new(TmpInode::new(inode_type));
+        self.inner
+            .exclusive_access()
+            .children
+            .insert(name.into(), inode.clone());
+        Ok(inode)
+    }
+
+    /// the opened files keep the inode alive
+    fn unlink(&self, name: &str) -> Result<(), isize> {
+        self.inner.exclusive_access().children.remove(name);
+        Ok(())
+    }
+
+    fn entries(&self) -> Vec<DirEntry> {
+        self.inner
+            .exclusive_access()
+            .children
+            .iter()
+            .map(|(name, inode)| DirEntry {
+                name: name.clone(),
+                inode_id: inode.inode_id,
+                inode_type: inode.inode_type,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,65 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::syscall_impl::errno::ENOTDIR;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InodeType {
+    File,
+    Dir,
+    CharDevice,
+}
+
+/// an entry listed in a directory
+pub struct DirEntry {
+    pub name: String,
+    pub inode_id: usize,
+    pub inode_type: InodeType,
+}
+
+/// ### a file, directory or device of some filesystem
+/// - the offset is ignored by devices
+/// - directory operations are only called on directories, the vfs checks the types and names
+pub trait VfsInode: Send + Sync {
+    fn inode_type(&self) -> InodeType;
+    /// return the number of bytes read, 0 : end of file
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize;
+    /// return the number of bytes written, may be less when there's no space left
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize;
+    /// discard the content of a file
+    fn truncate(&self) {}
+
+    fn lookup(&self, _name: &str) -> Option<Arc<dyn VfsInode>> {
+        None
+    }
+    /// Err : errno (positive)
+    fn create(&self, _name: &str, _inode_type: InodeType) -> Result<Arc<dyn VfsInode>, isize> {
+        Err(ENOTDIR)
+    }
+    /// a removed directory should be empty
+    fn unlink(&self, _name: &str) -> Result<(), isize> {
+        Err(ENOTDIR)
+    }
+    fn entries(&self) -> Vec<DirEntry> {
+        Vec::new()
+    }
+
+    fn is_dir(&self) -> bool {
+        self.inode_type() == InodeType::Dir
+    }
+}
+
+/// ### a filesystem which can be mounted
+pub trait FileSystem: Send + Sync {
+    fn fs_type(&self) -> &'static str;
+    fn root_inode(&self) -> Arc<dyn VfsInode>;
+    /// write the cached data back to the device
+    fn sync(&self) {}
+}
+
+/// ### an inode found by its absolute path
+/// relative paths can start from an opened directory
+#[derive(Clone)]
+pub struct Dentry {
+    pub path: String,
+    pub inode: Arc<dyn VfsInode>,
+}
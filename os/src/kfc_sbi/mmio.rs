@@ -10,9 +10,14 @@ const UART_MMIO: VPRange = VPRange {
     end: Page(0x1000_0000 + 0x1000),
 };
 
+const VIRTIO0_MMIO: VPRange = VPRange {
+    start: Page(0x1000_1000),
+    end: Page(0x1000_1000 + 0x1000),
+};
+
 const CLINT_MMIO: VPRange = VPRange {
     start: Page(0x200_0000),
     end: Page(0x200_0000 + 0x10000),
 };
 
-pub const MMIO: [VPRange; 3] = [VIRT_MMIO, UART_MMIO, CLINT_MMIO];
+pub const MMIO: [VPRange; 4] = [VIRT_MMIO, UART_MMIO, VIRTIO0_MMIO, CLINT_MMIO];
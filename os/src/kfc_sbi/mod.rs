@@ -27,6 +27,15 @@ const fn eid_from_str(name: &str) -> i32 {
     }
 }
 
+/// ### the id of the current hart
+/// kept in `tp` since `machine_start`, restored from the trap context when trapped from user
+#[inline(always)]
+pub fn hart_id() -> usize {
+    let id;
+    unsafe { asm!("mv {}, tp", out(reg) id) };
+    id
+}
+
 #[inline(always)]
 fn sbi_call(eid: usize, fid: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     let mut ret;
@@ -2,33 +2,48 @@ use core::arch::asm;
 
 use riscv::register::{mie, mscratch, mstatus, mtvec};
 
+use crate::{config::MAX_HARTS, kfc_sbi::hart_id};
+
 // clock configuration
 pub const CLOCK_FREQ: usize = 1250_0000;
 pub const MSEC_PER_SEC: usize = 1000;
+pub const NSEC_PER_SEC: usize = 1_000_000_000;
 const TICKS_PER_SEC: usize = 100; // 10ms per tick
-const INTERVAL: usize = CLOCK_FREQ / TICKS_PER_SEC;
+/// the default time slice of a task
+pub const INTERVAL: usize = CLOCK_FREQ / TICKS_PER_SEC;
 
-// clint : core local interruptor
+// clint : core local interruptor, a mtimecmp for each hart
 const CLINT: usize = 0x200_0000;
 const CLINT_MTIMECMP: usize = CLINT + 0x4000;
 const CLINT_MTIME: usize = CLINT + 0xBFF8;
 
-static mut TIMER_SCRATCH: [usize; 5] = [0; 5];
+static mut TIMER_SCRATCH: [[usize; 5]; MAX_HARTS] = [[0; 5]; MAX_HARTS];
+
+fn mtimecmp(hart_id: usize) -> *mut usize {
+    (CLINT_MTIMECMP + hart_id * 8) as *mut usize
+}
 
 pub fn get_time() -> usize {
     unsafe { (CLINT_MTIME as *const usize).read_volatile() }
 }
 
 pub fn get_time_cmp() -> usize {
-    unsafe { (CLINT_MTIMECMP as *const usize).read_volatile() }
+    unsafe { mtimecmp(hart_id()).read_volatile() }
+}
+
+/// ### the timer interrupt of this hart comes after `interval`
+/// reprogrammed for each task switched in, with the task's time slice
+pub fn set_next_trigger(interval: usize) {
+    unsafe { mtimecmp(hart_id()).write_volatile(get_time() + interval) }
 }
 
-pub fn timer_init() {
+/// called in M-mode by each hart
+pub fn timer_init(hart_id: usize) {
     unsafe {
         // save timer_scratch pointer
-        mscratch::write(&TIMER_SCRATCH as *const _ as usize);
+        mscratch::write(&TIMER_SCRATCH[hart_id] as *const _ as usize);
         // set initial trigger
-        *(CLINT_MTIMECMP as *mut usize) = *(CLINT_MTIME as *const usize) + INTERVAL;
+        mtimecmp(hart_id).write_volatile(get_time() + INTERVAL);
         // set mtvec
         mtvec::write(mtimer as usize, mtvec::TrapMode::Direct);
         // enable m-mode interrupts
@@ -49,14 +64,14 @@ pub extern "C" fn mtimer() {
         csrrw sp, mscratch, sp # now sp -> timer_scrath
         sd a0, 0(sp)
         sd a1, 8(sp)
-        sd a2, 16(sp)
 
-        # set next time
-        li a0, {mtimecmp}
-        ld a1, 0(a0) # a1 = mtimecmp
-        li a2, {interval}
-        add a1, a1, a2
-        sd a1, 0(a0) # a1 = next trigger
+        # no more interrupts until the next task is switched in
+        csrr a0, mhartid
+        slli a0, a0, 3
+        li a1, {mtimecmp}
+        add a0, a0, a1
+        li a1, -1
+        sd a1, 0(a0)
 
         # delegate a supervisor-timer-interrupt
         li a0, {mip_ssip}
@@ -65,13 +80,11 @@ pub extern "C" fn mtimer() {
         # restore
         ld a0, 0(sp)
         ld a1, 8(sp)
-        ld a2, 16(sp)
         csrrw sp, mscratch, sp
 
         mret
         "#, 
         mtimecmp = const CLINT_MTIMECMP,
-        interval = const INTERVAL,
         mip_ssip = const 2,
         options(noreturn))
     }
@@ -0,0 +1,185 @@
+//! ### lock-order checking, enabled by the feature "lock_debug"
+//! - the locks held by each hart are recorded by their names
+//! - locking B while holding A records the order A -> B
+//! - locking B while holding A, when A is reachable from B, is an inversion :
+//!   two harts taking the locks in both orders may deadlock, even if they never did yet
+//! - the sleeping locks (`Mutex`) held by a task move with it when it is switched out,
+//!   the spin locks and `UPSafeCell`s are never held across a switch
+
+use core::{
+    hint::spin_loop,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{config::MAX_HARTS, kfc_sbi::hart_id};
+
+/// the locks held by a hart at the same time
+const MAX_HELD: usize = 16;
+/// the different orders recorded, panic if there are more
+const MAX_ORDERS: usize = 128;
+
+/// ### the locks held by a hart, or by a task switched out
+/// the task may sleep only if all of them are sleeping locks
+pub struct HeldLocks {
+    names: [&'static str; MAX_HELD],
+    sleeping: [bool; MAX_HELD],
+    len: usize,
+}
+
+/// only accessed by its own hart
+static mut HELD_LOCKS: [HeldLocks; MAX_HARTS] = [NO_HELD_LOCKS; MAX_HARTS];
+
+const NO_HELD_LOCKS: HeldLocks = HeldLocks {
+    names: [""; MAX_HELD],
+    sleeping: [false; MAX_HELD],
+    len: 0,
+};
+
+struct LockOrders {
+    /// (held, locked)
+    orders: [(&'static str, &'static str); MAX_ORDERS],
+    len: usize,
+}
+
+/// shared by the harts, guarded by `LOCK_ORDERS_LOCKED` instead of a checked lock
+static mut LOCK_ORDERS: LockOrders = LockOrders {
+    orders: [("", ""); MAX_ORDERS],
+    len: 0,
+};
+static LOCK_ORDERS_LOCKED: AtomicBool = AtomicBool::new(false);
+
+impl LockOrders {
+    fn contains(&self, held: &str, locked: &str) -> bool {
+        self.orders[..self.len]
+            .iter()
+            .any(|&(a, b)| a == held && b == locked)
+    }
+
+    /// depth first search through the recorded orders, small enough for the kernel stack
+    fn reachable(&self, from: &str, to: &str) -> bool {
+        // orders[i] has been pushed
+        let mut visited = [false; MAX_ORDERS];
+        let mut stack = [0u8; MAX_ORDERS];
+        let mut top = 0;
+        let mut cur = from;
+        loop {
+            if cur == to {
+                return true;
+            }
+            for (i, &(held, _)) in self.orders[..self.len].iter().enumerate() {
+                if held == cur && !visited[i] {
+                    visited[i] = true;
+                    stack[top] = i as u8;
+                    top += 1;
+                }
+            }
+            if top == 0 {
+                return false;
+            }
+            top -= 1;
+            cur = self.orders[stack[top] as usize].1;
+        }
+    }
+
+    /// record `held` -> `locked`
+    fn record(&mut self, held: &'static str, locked: &'static str) -> Result<(), OrderError> {
+        if self.contains(held, locked) {
+            return Ok(());
+        }
+        if self.reachable(locked, held) {
+            return Err(OrderError::Inversion(held));
+        }
+        if self.len == MAX_ORDERS {
+            return Err(OrderError::Full);
+        }
+        self.orders[self.len] = (held, locked);
+        self.len += 1;
+        Ok(())
+    }
+}
+
+enum OrderError {
+    /// the held lock is reachable from the one locked
+    Inversion(&'static str),
+    /// no room for a new order, it would be left unchecked
+    Full,
+}
+
+/// ### called before spinning on the lock named `name`
+/// panic on a lock-order inversion
+pub fn on_lock(name: &'static str) {
+    lock(name, false);
+}
+
+/// ### called before sleeping on the lock named `name`
+/// panic if a spin lock is held, or on a lock-order inversion
+pub fn on_lock_sleeping(name: &'static str) {
+    on_sleep(name);
+    lock(name, true);
+}
+
+fn lock(name: &'static str, sleeping: bool) {
+    let held = unsafe { &mut HELD_LOCKS[hart_id()] };
+    while LOCK_ORDERS_LOCKED
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        spin_loop();
+    }
+    let lock_orders = unsafe { &mut LOCK_ORDERS };
+    // the locks of the same name, as those of different tasks, are not ordered
+    let error = held.names[..held.len]
+        .iter()
+        .filter(|&&held_name| held_name != name)
+        .find_map(|&held_name| lock_orders.record(held_name, name).err());
+    // released before panicking, the console locks are checked too
+    LOCK_ORDERS_LOCKED.store(false, Ordering::Release);
+
+    match error {
+        Some(OrderError::Inversion(held_name)) => panic!(
+            "lock order inversion : locking {} while holding {}, but {} has been locked while holding {}",
+            name, held_name, held_name, name
+        ),
+        Some(OrderError::Full) => panic!("too many lock orders, raise MAX_ORDERS"),
+        None => {}
+    }
+    assert!(held.len < MAX_HELD, "too many locks held");
+    held.names[held.len] = name;
+    held.sleeping[held.len] = sleeping;
+    held.len += 1;
+}
+
+/// called after the lock named `name` is released, not necessarily the last one locked
+pub fn on_unlock(name: &'static str) {
+    let held = unsafe { &mut HELD_LOCKS[hart_id()] };
+    let idx = held.names[..held.len]
+        .iter()
+        .rposition(|&held_name| held_name == name)
+        .expect("unlocking a lock not held");
+    held.names.copy_within(idx + 1..held.len, idx);
+    held.sleeping.copy_within(idx + 1..held.len, idx);
+    held.len -= 1;
+}
+
+/// ### called before sleeping on `name`
+/// panic if a spin lock is held : the hart may switch to a task spinning on it
+pub fn on_sleep(name: &'static str) {
+    let held = unsafe { &HELD_LOCKS[hart_id()] };
+    if let Some(idx) = held.sleeping[..held.len]
+        .iter()
+        .rposition(|&sleeping| !sleeping)
+    {
+        panic!("sleeping on {} while holding {}", name, held.names[idx]);
+    }
+}
+
+/// ### take the locks held by the current task before it is switched out
+/// only sleeping locks are left then, the hart switches to the next task with none
+pub fn switch_out() -> HeldLocks {
+    core::mem::replace(unsafe { &mut HELD_LOCKS[hart_id()] }, NO_HELD_LOCKS)
+}
+
+/// give the locks taken by `switch_out` back to the task, on the hart it is resumed on
+pub fn switch_in(held: HeldLocks) {
+    unsafe { HELD_LOCKS[hart_id()] = held };
+}
@@ -1 +1,6 @@
+#[cfg(feature = "lock_debug")]
+pub mod lock_debug;
+pub mod mutex;
+pub mod random;
+pub mod spin_lock;
 pub mod up_safe_cell;
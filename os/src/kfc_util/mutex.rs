@@ -0,0 +1,78 @@
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::task::wait_queue::WaitQueue;
+
+#[cfg(feature = "lock_debug")]
+use super::lock_debug;
+
+/// ### a lock blocking the task until it is released
+/// - for the data held for a long time, as the harts are not kept spinning
+/// - only contended in a task : the idle control flow can't block
+/// - no spin lock can be held while locking it
+/// - `name` is used by the lock-order checking, as a `SpinLock`
+pub struct Mutex<T> {
+    #[cfg_attr(not(feature = "lock_debug"), allow(dead_code))]
+    name: &'static str,
+    locked: AtomicBool,
+    waiters: WaitQueue,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for Mutex<T> {}
+unsafe impl<T: Send> Send for Mutex<T> {}
+
+/// released when dropped, waking up a waiter
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    pub fn new(name: &'static str, data: T) -> Self {
+        Self {
+            name,
+            locked: AtomicBool::new(false),
+            waiters: WaitQueue::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<T> {
+        #[cfg(feature = "lock_debug")]
+        lock_debug::on_lock_sleeping(self.name);
+        while self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // checked again with the waiters locked, the wakeup can't be missed
+            self.waiters.wait_if(|| self.locked.load(Ordering::Relaxed));
+        }
+        MutexGuard { mutex: self }
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.locked.store(false, Ordering::Release);
+        #[cfg(feature = "lock_debug")]
+        lock_debug::on_unlock(self.mutex.name);
+        self.mutex.waiters.wake_one();
+    }
+}
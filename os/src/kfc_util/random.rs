@@ -0,0 +1,20 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::kfc_sbi::timer::get_time;
+
+/// 0 : not seeded yet
+static STATE: AtomicU64 = AtomicU64::new(0);
+
+/// ### xorshift64, seeded by the time of the first call
+/// NOT cryptographically secure
+pub fn rand_u64() -> u64 {
+    let mut x = STATE.load(Ordering::Relaxed);
+    if x == 0 {
+        x = get_time() as u64 | 1;
+    }
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    STATE.store(x, Ordering::Relaxed);
+    x
+}
@@ -0,0 +1,174 @@
+use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use riscv::register::sstatus;
+
+use crate::task::preempt::{preempt_disable, preempt_enable};
+
+#[cfg(feature = "lock_debug")]
+use super::lock_debug;
+
+/// ### the bare lock of a `SpinLock`, without the data
+/// - also the lock of kfc-fs, so its holder is not preempted either
+/// - `name` is used by the lock-order checking
+pub struct RawSpinLock {
+    #[cfg_attr(not(feature = "lock_debug"), allow(dead_code))]
+    name: &'static str,
+    locked: AtomicBool,
+}
+
+impl RawSpinLock {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    pub fn lock(&self) {
+        preempt_disable();
+        #[cfg(feature = "lock_debug")]
+        lock_debug::on_lock(self.name);
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // wait without writing the cache line
+            while self.locked.load(Ordering::Relaxed) {
+                spin_loop();
+            }
+        }
+    }
+
+    /// ### Safety
+    /// the lock is held by the caller
+    pub unsafe fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+        #[cfg(feature = "lock_debug")]
+        lock_debug::on_unlock(self.name);
+        preempt_enable();
+    }
+}
+
+unsafe impl kfc_fs::RawLock for RawSpinLock {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self::new("kfc_fs");
+
+    fn lock(&self) {
+        RawSpinLock::lock(self)
+    }
+
+    unsafe fn unlock(&self) {
+        RawSpinLock::unlock(self)
+    }
+}
+
+/// ### a lock shared by the harts, spinning until it is released
+/// - not reentrant : locking it twice on the same hart spins forever
+/// - the task holding it is not preempted, the release may be a preemption point
+/// - `name` is used by the lock-order checking
+pub struct SpinLock<T> {
+    raw: RawSpinLock,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+unsafe impl<T: Send> Send for SpinLock<T> {}
+
+/// released when dropped
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<T> SpinLock<T> {
+    pub const fn new(name: &'static str, data: T) -> Self {
+        Self {
+            raw: RawSpinLock::new(name),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> SpinLockGuard<T> {
+        self.raw.lock();
+        SpinLockGuard { lock: self }
+    }
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe { self.lock.raw.unlock() }
+    }
+}
+
+/// ### a `SpinLock` with the interrupts of the hart disabled while held
+/// for the data also used by the interrupt handlers, which would spin forever
+/// on the lock held by the code they interrupted
+pub struct IrqSpinLock<T> {
+    lock: SpinLock<T>,
+}
+
+/// released when dropped, then `sstatus.SIE` is restored
+pub struct IrqSpinLockGuard<'a, T> {
+    guard: ManuallyDrop<SpinLockGuard<'a, T>>,
+    /// `sstatus.SIE` before locking
+    sie: bool,
+}
+
+impl<T> IrqSpinLock<T> {
+    pub const fn new(name: &'static str, data: T) -> Self {
+        Self {
+            lock: SpinLock::new(name, data),
+        }
+    }
+
+    pub fn lock(&self) -> IrqSpinLockGuard<T> {
+        let sie = sstatus::read().sie();
+        unsafe { sstatus::clear_sie() };
+        IrqSpinLockGuard {
+            guard: ManuallyDrop::new(self.lock.lock()),
+            sie,
+        }
+    }
+}
+
+impl<T> Deref for IrqSpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for IrqSpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for IrqSpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        // release the lock before an interrupt may come
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        if self.sie {
+            unsafe { sstatus::set_sie() };
+        }
+    }
+}
@@ -1,22 +1,84 @@
-use core::cell::{RefCell, RefMut};
+use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-// multiple threads : not allowed
-// we just assert that UPSafeCell is Sync
+use crate::{
+    kfc_sbi::hart_id,
+    task::preempt::{preempt_disable, preempt_enable},
+};
+
+#[cfg(feature = "lock_debug")]
+use super::lock_debug;
+
+// multiple harts : wait until the other hart releases it
+// the same hart borrowing it twice : panic, as a RefCell
+// the task borrowing it is not preempted, as a `SpinLock`
+// `name` is used by the lock-order checking, as a `SpinLock`
+
+/// not borrowed by any hart
+const NO_OWNER: usize = usize::MAX;
 
 pub struct UPSafeCell<T> {
-    inner: RefCell<T>,
+    #[cfg_attr(not(feature = "lock_debug"), allow(dead_code))]
+    name: &'static str,
+    /// the hart borrowing it
+    owner: AtomicUsize,
+    inner: UnsafeCell<T>,
 }
 
-// unsafe impl Sync so that compiler would not check it
+// the harts are serialized by `owner`
 unsafe impl<T> Sync for UPSafeCell<T> {}
 
+/// released when dropped
+pub struct UPSafeRefMut<'a, T> {
+    cell: &'a UPSafeCell<T>,
+}
+
 impl<T> UPSafeCell<T> {
-    pub const fn new(item: T) -> Self {
+    pub const fn new(name: &'static str, item: T) -> Self {
         Self {
-            inner: RefCell::new(item),
+            name,
+            owner: AtomicUsize::new(NO_OWNER),
+            inner: UnsafeCell::new(item),
+        }
+    }
+    pub fn exclusive_access(&self) -> UPSafeRefMut<T> {
+        preempt_disable();
+        let hart = hart_id();
+        #[cfg(feature = "lock_debug")]
+        lock_debug::on_lock(self.name);
+        while let Err(owner) =
+            self.owner
+                .compare_exchange_weak(NO_OWNER, hart, Ordering::Acquire, Ordering::Relaxed)
+        {
+            assert_ne!(owner, hart, "already borrowed");
+            spin_loop();
         }
+        UPSafeRefMut { cell: self }
     }
-    pub fn exclusive_access(&self) -> RefMut<T> {
-        self.inner.borrow_mut()
+}
+
+impl<T> Deref for UPSafeRefMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.cell.inner.get() }
+    }
+}
+
+impl<T> DerefMut for UPSafeRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.cell.inner.get() }
+    }
+}
+
+impl<T> Drop for UPSafeRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.cell.owner.store(NO_OWNER, Ordering::Release);
+        #[cfg(feature = "lock_debug")]
+        lock_debug::on_unlock(self.cell.name);
+        preempt_enable();
     }
 }
@@ -11,6 +11,8 @@ mod kfc_logger;
 
 mod app_loader;
 mod config;
+mod drivers;
+mod fs;
 mod kfc_sbi;
 mod kfc_util;
 mod lang_items;
@@ -21,13 +23,17 @@ mod trap;
 
 extern crate alloc;
 
-use core::arch::{asm, global_asm};
+use core::{
+    arch::asm,
+    hint::spin_loop,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use riscv::register::{mepc, mstatus, satp, stvec};
 
 use crate::{
-    config::{BOOT_STACK_SIZE, MEMORY_END},
-    kfc_sbi::timer,
+    config::{BOOT_STACK_SIZE, MAX_HARTS, MEMORY_END},
+    kfc_sbi::{hart_id, timer},
     task::processor::proc_schedule,
     trap::kernel_trap::kernelvec,
 };
@@ -38,19 +44,31 @@ use crate::{
 // naked should extern "C"...
 pub unsafe extern "C" fn _start() -> ! {
     #[link_section = ".bss.stack"]
-    static mut BOOT_STACK: [u8; BOOT_STACK_SIZE] = [0; BOOT_STACK_SIZE];
+    static mut BOOT_STACK: [u8; BOOT_STACK_SIZE * MAX_HARTS] = [0; BOOT_STACK_SIZE * MAX_HARTS];
 
+    // each hart has its own boot stack, a0 = hart id
     asm!(
-    "la sp, {stack} + {stack_size}",
+    "csrr a0, mhartid",
+    "li t0, {max_harts}",
+    "bgeu a0, t0, 2f",
+    "addi t0, a0, 1",
+    "li t1, {stack_size}",
+    "mul t0, t0, t1",
+    "la sp, {stack}",
+    "add sp, sp, t0",
     "call {m_start}",
+    // more harts than MAX_HARTS
+    "2:",
+    "wfi",
+    "j 2b",
     stack = sym BOOT_STACK,
     stack_size = const BOOT_STACK_SIZE,
+    max_harts = const MAX_HARTS,
     m_start = sym machine_start,
     options(noreturn));
 }
 
 // global_asm!(include_str!("entry.S"));
-global_asm!(include_str!("link_app.S"));
 
 // global/static variables are located in .bss section
 // so .bss should be cleared
@@ -67,10 +85,24 @@ pub fn clear_bss() {
     })
 }
 
+/// ### the secondary harts are parked until the boot hart has done `kernel_init`
+/// in .data : read by the secondary harts while the boot hart is clearing .bss
+#[link_section = ".data"]
+static KERNEL_READY: AtomicBool = AtomicBool::new(false);
+
 #[no_mangle]
-pub fn machine_start() -> ! {
-    clear_bss();
+pub fn machine_start(hart_id: usize) -> ! {
+    if hart_id == 0 {
+        clear_bss();
+    } else {
+        while !KERNEL_READY.load(Ordering::Acquire) {
+            spin_loop();
+        }
+    }
     unsafe {
+        // the hart id is kept in tp, see `kfc_sbi::hart_id`
+        asm!("mv tp, {}", in(reg) hart_id);
+
         // set previous mode m-mode
         mstatus::set_mpp(mstatus::MPP::Supervisor);
 
@@ -96,7 +128,7 @@ pub fn machine_start() -> ! {
         stvec::write(kernelvec as usize, stvec::TrapMode::Direct);
 
         // timer interrupt init
-        timer::timer_init();
+        timer::timer_init(hart_id);
 
         asm!("mret");
     }
@@ -105,7 +137,14 @@ pub fn machine_start() -> ! {
 
 #[no_mangle]
 pub fn kernel_main() -> ! {
-    kernel_init();
+    if hart_id() == 0 {
+        kernel_init();
+        KERNEL_READY.store(true, Ordering::Release);
+    } else {
+        // the kernel space has been built by the boot hart
+        mm::kernel_space::activate_kernel_space();
+        info!("Hart {} is running!", hart_id());
+    }
     // not need to enable s-mode interrupt here
     // unsafe { sstatus::set_sie() };
     proc_schedule();
@@ -117,6 +156,8 @@ pub fn kernel_init() {
     info!("Entering into kernel_main function!");
     info!("MEMORY END ADDRESS is {:#X}", MEMORY_END);
     mm::mm_init();
+    #[cfg(feature = "block_test")]
+    drivers::block::block_device_test();
     task::task_init();
     // test for kernel trap
     // debug!("test read CLINT : {:#X?}", unsafe {
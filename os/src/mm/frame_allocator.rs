@@ -3,7 +3,7 @@ use lazy_static::lazy_static;
 
 use crate::{
     config::{MEMORY_END, PAGE_BYTES},
-    kfc_util::up_safe_cell::UPSafeCell,
+    kfc_util::spin_lock::SpinLock,
 };
 
 use super::{Frame, PhysAddr};
@@ -17,6 +17,8 @@ pub struct StackFrameAllocator {
     start: Frame,
     end: Frame,
     recycled: Vec<Frame>,
+    /// the number of frames managed
+    total: usize,
 }
 
 impl StackFrameAllocator {
@@ -25,11 +27,17 @@ impl StackFrameAllocator {
             start: Frame(0),
             end: Frame(0),
             recycled: Vec::new(),
+            total: 0,
         }
     }
     pub fn init(&mut self, start: Frame, end: Frame) {
         self.start = start;
         self.end = end;
+        self.total = (end.0 - start.0) / PAGE_BYTES;
+    }
+    /// the never allocated ones and the recycled ones
+    pub fn free_frames(&self) -> usize {
+        (self.end.0 - self.start.0) / PAGE_BYTES + self.recycled.len()
     }
 }
 
@@ -70,10 +78,9 @@ impl Drop for FrameTracker {
     }
 }
 
+/// None : no frame left
 pub fn frame_alloc() -> Option<FrameTracker> {
-    let res = FRAME_ALLOCATOR.exclusive_access().alloc();
-    assert!(res.is_ok(), "Frame allocation failed!");
-    let res_frame = res.ok().unwrap();
+    let res_frame = FRAME_ALLOCATOR.lock().alloc().ok()?;
     let bytes_array_mut = res_frame.get_bytes_array_mut();
     // always clear the bytes array
     for i in 0..PAGE_BYTES {
@@ -83,13 +90,19 @@ pub fn frame_alloc() -> Option<FrameTracker> {
 }
 
 pub fn frame_dealloc(ft: &mut FrameTracker) {
-    let res = FRAME_ALLOCATOR.exclusive_access().dealloc(ft.0);
+    let res = FRAME_ALLOCATOR.lock().dealloc(ft.0);
     assert!(res.is_ok(), "Frame deallocation failed!");
 }
 
+/// return (total frames, free frames)
+pub fn frame_stats() -> (usize, usize) {
+    let allocator = FRAME_ALLOCATOR.lock();
+    (allocator.total, allocator.free_frames())
+}
+
 lazy_static! {
-    static ref FRAME_ALLOCATOR: UPSafeCell<StackFrameAllocator> =
-        UPSafeCell::new(StackFrameAllocator::new_empty());
+    static ref FRAME_ALLOCATOR: SpinLock<StackFrameAllocator> =
+        SpinLock::new("FRAME_ALLOCATOR", StackFrameAllocator::new_empty());
 }
 
 extern "C" {
@@ -97,7 +110,7 @@ extern "C" {
 }
 
 pub fn frame_allocator_init() {
-    FRAME_ALLOCATOR.exclusive_access().init(
+    FRAME_ALLOCATOR.lock().init(
         PhysAddr(ekernel as usize).ceil_frame(),
         PhysAddr(MEMORY_END).floor_frame(),
     )
@@ -22,6 +22,9 @@ pub struct Heap {
     total: usize, // total memory
 }
 
+// the free lists point into the heap space it owns
+unsafe impl Send for Heap {}
+
 impl Debug for Heap {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Heap")
@@ -59,6 +62,11 @@ impl Heap {
         }
     }
 
+    /// return (user, real, total) in bytes
+    pub fn stats(&self) -> (usize, usize, usize) {
+        (self.user, self.real, self.total)
+    }
+
     pub unsafe fn init(&mut self, start: usize, size: usize) {
         self.add_to_heap(start, start + size);
     }
@@ -0,0 +1,38 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ops::Deref,
+    ptr::{null_mut, NonNull},
+};
+
+use crate::kfc_util::spin_lock::IrqSpinLock;
+
+use super::buddy_allocator::Heap;
+
+// the heap shared by the harts, and the interrupt handlers may allocate
+pub struct LockedHeap(IrqSpinLock<Heap>);
+
+impl Deref for LockedHeap {
+    type Target = IrqSpinLock<Heap>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl LockedHeap {
+    pub const fn new() -> Self {
+        LockedHeap(IrqSpinLock::new("HEAP", Heap::new()))
+    }
+}
+
+unsafe impl GlobalAlloc for LockedHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.lock()
+            .alloc(layout)
+            .ok()
+            .map_or(null_mut(), |x| x.as_ptr())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.lock().dealloc(NonNull::new_unchecked(ptr), layout);
+    }
+}
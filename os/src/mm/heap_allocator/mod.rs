@@ -1,15 +1,15 @@
 use crate::config::KERNEL_HEAP_SIZE;
 
-use self::up_safe_allocator::UPSafeHeap;
+use self::locked_allocator::LockedHeap;
 
 pub mod buddy_allocator;
 pub mod heap_test;
 pub mod instrusive_linked_list;
-pub mod up_safe_allocator;
+pub mod locked_allocator;
 
 // heap_allocator instance
 #[global_allocator]
-static HEAP_ALLOCATOR: UPSafeHeap = UPSafeHeap::new();
+static HEAP_ALLOCATOR: LockedHeap = LockedHeap::new();
 
 // heap space for kernel
 static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
@@ -22,7 +22,12 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
 pub fn heap_init() {
     unsafe {
         HEAP_ALLOCATOR
-            .exclusive_access()
+            .lock()
             .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE)
     };
 }
+
+/// the kernel heap's (user, real, total) in bytes
+pub fn heap_stats() -> (usize, usize, usize) {
+    HEAP_ALLOCATOR.lock().stats()
+}
@@ -5,19 +5,22 @@ use riscv::register::satp;
 use crate::{
     config::MEMORY_END,
     kfc_sbi::mmio::MMIO,
-    kfc_util::up_safe_cell::UPSafeCell,
+    kfc_util::spin_lock::SpinLock,
     mm::{MapArea, MapPerm, MapType, VPRange, VirtAddr},
 };
 
 use super::{Frame, MemorySet, PageTable};
 
 pub struct KernelSpace {
-    inner: UPSafeCell<MemorySet>,
+    inner: SpinLock<MemorySet>,
 }
 
 lazy_static! {
     pub static ref KERNEL_SPACE: KernelSpace = KernelSpace {
-        inner: UPSafeCell::new(MemorySet::new_bare()),
+        inner: SpinLock::new(
+            "KERNEL_SPACE",
+            MemorySet::new_bare().expect("no frame for the kernel page table"),
+        ),
     };
 }
 
@@ -122,20 +125,21 @@ impl MemorySet {
 
 impl KernelSpace {
     pub fn pt_entry(&self) -> Frame {
-        self.inner.exclusive_access().page_table.entry
+        self.inner.lock().page_table.entry
     }
 
-    pub fn add_kernel_stack(&self, stack: MapArea) {
-        self.inner.exclusive_access().insert_new_map_area(stack);
+    /// Err : no frame left for the page table
+    pub fn add_kernel_stack(&self, stack: MapArea) -> Result<(), ()> {
+        self.inner.lock().try_insert_map_area(stack)
     }
 
     pub fn remove_map_area(&self, vp_range: &VPRange) {
-        self.inner.exclusive_access().relase_area(vp_range);
+        self.inner.lock().relase_area(vp_range);
     }
 }
 
 pub fn kernel_space_init() {
-    KERNEL_SPACE.inner.exclusive_access().kernel_init()
+    KERNEL_SPACE.inner.lock().kernel_init()
 }
 
 pub fn activate_kernel_space() {
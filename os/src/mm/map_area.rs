@@ -1,14 +1,18 @@
 use core::{cmp::min, fmt::Debug};
 
-use alloc::collections::BTreeMap;
+use alloc::{collections::BTreeMap, sync::Arc};
 use bitflags::bitflags;
 
 use crate::{
-    config::{TRAMPOLINE_VIRT_ADDR, VIRT_ADDR_MAX},
+    config::{PAGE_BYTES, TRAMPOLINE_VIRT_ADDR, VIRT_ADDR_MAX},
+    fs::vfs::VfsInode,
     trap::trampoline_frame,
 };
 
-use super::{frame_alloc, Frame, FrameTracker, Page, VARange, VPRange, VirtAddr};
+use super::{
+    frame_alloc, memory_set::PageFaultError, page_cache::get_page, Frame, FrameTracker, PTEFlags,
+    Page, VARange, VPRange, VirtAddr,
+};
 
 bitflags! {
     pub struct MapPerm : usize{
@@ -21,7 +25,8 @@ bitflags! {
 
 pub enum MapType {
     Identical,
-    Framed(BTreeMap<Page, FrameTracker>),
+    /// frames may be shared between address spaces after fork (copy on write)
+    Framed(BTreeMap<Page, Arc<FrameTracker>>),
     Target(Frame),
 }
 
@@ -35,10 +40,21 @@ impl Debug for MapType {
     }
 }
 
+/// ### the file mapped by a private file mapping
+/// the pages are taken from the page cache, and copied before written
+#[derive(Clone)]
+pub struct MappedFile {
+    pub inode: Arc<dyn VfsInode>,
+    /// the offset in the file of the area's first page, aligned to pages
+    pub offset: usize,
+}
+
 pub struct MapArea {
     pub vp_range: VPRange,
     pub map_perm: MapPerm,
     pub map_type: MapType,
+    /// Some : the pages are bounded from the file
+    pub file: Option<MappedFile>,
 }
 
 impl Debug for MapArea {
@@ -47,6 +63,7 @@ impl Debug for MapArea {
             .field("vp_range", &self.vp_range)
             .field("map_perm", &self.map_perm)
             .field("map_type", &self.map_type)
+            .field("file", &self.file.as_ref().map(|file| file.offset))
             .finish()
     }
 }
@@ -65,19 +82,57 @@ impl MapArea {
             vp_range,
             map_perm,
             map_type,
+            file: None,
         }
     }
 
     /// #### bound each page to a physical frame
     /// then this map_area can manage the physical frames
     /// frames being allocated in this function
-    fn bound_frames(&mut self) {
+    fn bound_frames(&mut self) -> Option<()> {
         if let MapType::Framed(ref mut mem_src) = self.map_type {
             for it in self.vp_range.iter() {
                 let page = it.value();
-                let frame = frame_alloc().unwrap();
-                mem_src.insert(page, frame);
+                let frame = frame_alloc()?;
+                mem_src.insert(page, Arc::new(frame));
+            }
+            Some(())
+        } else {
+            panic!("map_type is not Framed when binding frames");
+        }
+    }
+
+    /// #### bound a single page to a physical frame on demand
+    /// - the frame has been cleared by `frame_alloc`
+    /// - None : no frame left
+    fn bound_frame(&mut self, vp: Page) -> Option<Frame> {
+        if let MapType::Framed(ref mut mem_src) = self.map_type {
+            if let Some(frame) = mem_src.get(&vp) {
+                return Some(frame.0);
+            }
+            let frame = frame_alloc()?;
+            let ret = frame.0;
+            mem_src.insert(vp, Arc::new(frame));
+            Some(ret)
+        } else {
+            panic!("map_type is not Framed when binding frames");
+        }
+    }
+
+    /// #### bound a page of a file mapping to the frame in the page cache
+    /// None : no frame left
+    fn bound_file_page(&mut self, vp: Page) -> Option<Frame> {
+        let file = self.file.as_ref().expect("not a file mapping");
+        let index = file.offset / PAGE_BYTES + (vp.0 - self.vp_range.start.0);
+        if let MapType::Framed(ref mut mem_src) = self.map_type {
+            // a private copy may have been made
+            if let Some(frame) = mem_src.get(&vp) {
+                return Some(frame.0);
             }
+            let frame = get_page(&file.inode, index)?;
+            let ret = frame.0;
+            mem_src.insert(vp, frame);
+            Some(ret)
         } else {
             panic!("map_type is not Framed when binding frames");
         }
@@ -85,7 +140,8 @@ impl MapArea {
 
     /// assume that start and end are not aligned
     /// data's va_range can be smaller than map_area's va_range
-    fn fill_with_data(&mut self, fill_data: FillData) {
+    /// for lazy areas, only the pages holding data are bounded here
+    fn fill_with_data(&mut self, fill_data: FillData) -> Option<()> {
         let mut cur_va = fill_data.fill_va_range.start;
         let mut cur_offset = 0 as usize;
 
@@ -97,7 +153,7 @@ impl MapArea {
             ) - cur_va.0;
 
             let src = &fill_data.data[cur_offset..cur_offset + cur_slice_len];
-            let dst = &mut self.mapped_to(cur_va.floor_page()).get_bytes_array_mut()
+            let dst = &mut self.bound_frame(cur_va.floor_page())?.get_bytes_array_mut()
                 [cur_va.get_offset()..cur_va.get_offset() + cur_slice_len];
 
             dst.copy_from_slice(src);
@@ -105,6 +161,7 @@ impl MapArea {
             cur_va.0 += cur_slice_len;
             cur_offset += cur_slice_len;
         }
+        Some(())
     }
 }
 
@@ -120,25 +177,195 @@ impl MapArea {
         // debug!("new: map_perm={:#X?}", map_perm);
         let mut ret = Self::new_bare(vp_range, map_type, map_perm);
         if let MapType::Framed(_) = ret.map_type {
-            ret.bound_frames();
+            ret.bound_frames().expect("no frame left for the area");
         }
         if let Some(data) = fill_data {
             // trace!("filling data");
-            ret.fill_with_data(data);
+            ret.fill_with_data(data)
+                .expect("no frame left for the data");
         }
         ret
     }
 
+    /// ### a framed area with all its pages bounded now
+    /// None : no frame left
+    pub fn new_framed(vp_range: VPRange, map_perm: MapPerm) -> Option<Self> {
+        let mut ret = Self::new_bare(vp_range, MapType::Framed(BTreeMap::new()), map_perm);
+        ret.bound_frames()?;
+        Some(ret)
+    }
+
+    /// ### a framed area starting unbacked
+    /// pages are bounded when first accessed (page fault)
+    pub fn new_lazy(vp_range: VPRange, map_perm: MapPerm) -> Self {
+        Self::new_bare(vp_range, MapType::Framed(BTreeMap::new()), map_perm)
+    }
+
+    /// ### a lazy area with the pages of `fill_data` bounded and filled now
+    /// None : no frame left for the data
+    pub fn new_filled(vp_range: VPRange, map_perm: MapPerm, fill_data: FillData) -> Option<Self> {
+        let mut ret = Self::new_lazy(vp_range, map_perm);
+        ret.fill_with_data(fill_data)?;
+        Some(ret)
+    }
+
+    /// ### a private mapping of `file` from `offset`, bounded to the pages when first accessed
+    pub fn new_file(vp_range: VPRange, map_perm: MapPerm, file: MappedFile) -> Self {
+        let mut ret = Self::new_lazy(vp_range, map_perm);
+        ret.file = Some(file);
+        ret
+    }
+
     /// ### get the physical frame of a virtual page
     /// 1. if map_type is identical, then vp == pp
     /// 2. if map_type is framed, then vp is the key of `mem_frames` : we assume that this pp has been allocated before
     pub fn mapped_to(&self, vp: Page) -> Frame {
+        self.try_mapped_to(vp).expect("frame not found")
+    }
+
+    /// `None` : the page of a lazy area has not been bounded yet
+    pub fn try_mapped_to(&self, vp: Page) -> Option<Frame> {
         match self.map_type {
-            MapType::Identical => vp.into(),
-            MapType::Framed(ref mem_frames) => mem_frames.get(&vp).expect("frame not found").0,
-            MapType::Target(frame) => frame,
+            MapType::Identical => Some(vp.into()),
+            MapType::Framed(ref mem_frames) => mem_frames.get(&vp).map(|ft| ft.0),
+            MapType::Target(frame) => Some(frame),
         }
     }
+
+    pub fn pte_flags(&self) -> PTEFlags {
+        PTEFlags::from_bits(self.map_perm.bits()).unwrap()
+    }
+
+    /// ### PROT_NONE : no R, W or X
+    /// its pages are not mapped in the page table, as a valid pte with no R/W/X
+    /// points to the next level, the frames are kept
+    pub fn is_accessible(&self) -> bool {
+        self.map_perm
+            .intersects(MapPerm::R | MapPerm::W | MapPerm::X)
+    }
+
+    /// ### the frame of `vp` is shared, writing to it should copy
+    /// shared with other address spaces, or kept in the page cache
+    pub fn is_shared(&self, vp: Page) -> bool {
+        match self.map_type {
+            MapType::Framed(ref mem_frames) => mem_frames
+                .get(&vp)
+                .map_or(false, |frame| !is_exclusive(frame)),
+            _ => false,
+        }
+    }
+
+    /// ### resolve a page fault on an unbacked page of a lazy area
+    /// - the new frame is returned for mapping
+    /// - a page of a file mapping is shared with the page cache, see `is_shared`
+    pub fn lazy_alloc(&mut self, vp: Page) -> Result<Frame, PageFaultError> {
+        match self.map_type {
+            MapType::Framed(_) if self.file.is_some() => {
+                self.bound_file_page(vp).ok_or(PageFaultError::NoMemory)
+            }
+            MapType::Framed(_) => self.bound_frame(vp).ok_or(PageFaultError::NoMemory),
+            _ => Err(PageFaultError::Denied),
+        }
+    }
+
+    /// (bounded pages, pages shared with other address spaces)
+    pub fn resident_pages(&self) -> (usize, usize) {
+        match self.map_type {
+            MapType::Framed(ref mem_frames) => (
+                mem_frames.len(),
+                mem_frames
+                    .values()
+                    .filter(|frame| Arc::strong_count(frame) > 1)
+                    .count(),
+            ),
+            _ => (0, 0),
+        }
+    }
+
+    /// ### move the end of a lazy area
+    /// frames out of the new range are released, the page table should be updated by caller
+    pub fn resize(&mut self, new_end: Page) {
+        if let MapType::Framed(ref mut mem_frames) = self.map_type {
+            // the dropped part will release the frames
+            mem_frames.split_off(&new_end);
+            self.vp_range.end = new_end;
+        } else {
+            panic!("map_type is not Framed when resizing");
+        }
+    }
+
+    /// ### split the area at `at`
+    /// `self` keeps [start, at), the returned area takes [at, end) with its frames
+    pub fn split_off(&mut self, at: Page) -> Self {
+        assert!(
+            self.vp_range.start < at && at < self.vp_range.end,
+            "split point out of the map area"
+        );
+        let map_type = match self.map_type {
+            MapType::Identical => MapType::Identical,
+            MapType::Framed(ref mut mem_frames) => MapType::Framed(mem_frames.split_off(&at)),
+            MapType::Target(_) => panic!("map_type is Target when splitting"),
+        };
+        let mut right = Self::new_bare(
+            VPRange {
+                start: at,
+                end: self.vp_range.end,
+            },
+            map_type,
+            self.map_perm,
+        );
+        right.file = self.file.as_ref().map(|file| MappedFile {
+            inode: file.inode.clone(),
+            offset: file.offset + (at.0 - self.vp_range.start.0) * PAGE_BYTES,
+        });
+        self.vp_range.end = at;
+        right
+    }
+
+    /// ### a new framed area sharing all the frames with `self`
+    /// each frame's reference count increases, no data copied
+    pub fn share_frames(&self) -> Self {
+        if let MapType::Framed(ref mem_frames) = self.map_type {
+            let mut ret = Self::new_bare(
+                self.vp_range,
+                MapType::Framed(mem_frames.clone()),
+                self.map_perm,
+            );
+            ret.file = self.file.clone();
+            ret
+        } else {
+            panic!("map_type is not Framed when sharing frames");
+        }
+    }
+
+    /// ### give `vp` a private frame before writing to it
+    /// - the frame is only referenced by this area : take it over
+    /// - otherwise : copy the data into a newly allocated frame
+    /// - None : no frame left
+    pub fn copy_on_write(&mut self, vp: Page) -> Option<Frame> {
+        if let MapType::Framed(ref mut mem_frames) = self.map_type {
+            let shared = mem_frames.get(&vp).expect("frame not found");
+            if is_exclusive(shared) {
+                return Some(shared.0);
+            }
+            let frame = frame_alloc()?;
+            frame
+                .0
+                .get_bytes_array_mut()
+                .copy_from_slice(shared.0.get_bytes_array_mut());
+            let ret = frame.0;
+            // the old frame's reference count decreases here
+            mem_frames.insert(vp, Arc::new(frame));
+            Some(ret)
+        } else {
+            panic!("map_type is not Framed when copying on write");
+        }
+    }
+}
+
+/// only referenced by one area, the page cache keeps `Weak`s of the frames it holds
+fn is_exclusive(frame: &Arc<FrameTracker>) -> bool {
+    Arc::strong_count(frame) == 1 && Arc::weak_count(frame) == 0
 }
 
 pub struct FillData<'a> {
@@ -1,181 +1,705 @@
-use core::cmp::max;
+use core::{cmp::max, mem::size_of};
 
-use alloc::{collections::BTreeMap, vec::Vec};
+use alloc::{string::String, vec::Vec};
 
 use crate::{
-    config::{TRAMPOLINE_VIRT_ADDR, TRAP_CTX_VIRT_ADDR, USER_STACK_SIZE},
+    config::{
+        ARG_MAX, INTERP_BASE_VIRT_ADDR, MMAP_BASE_VIRT_ADDR, PAGE_BYTES, PIE_BASE_VIRT_ADDR,
+        TRAMPOLINE_VIRT_ADDR, TRAP_CTX_VIRT_ADDR, USER_SPACE_END_VIRT_ADDR, USER_STACK_SIZE,
+    },
+    kfc_util::random::rand_u64,
     mm::map_area::FillData,
+    syscall_impl::errno::{E2BIG, ENOEXEC, ENOMEM},
 };
 
-use super::{MapArea, MapPerm, MapType, PTEFlags, PageTable, VPRange, VirtAddr};
+use super::{
+    Frame, MapArea, MapPerm, MapType, MappedFile, PTEFlags, Page, PageTable, VPRange, VirtAddr,
+};
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const EM_RISCV: u16 = 243;
+const ELF64_HEADER_SIZE: usize = 64;
+const ELF64_PHDR_SIZE: usize = 56;
+const ELF64_DYN_SIZE: usize = 16;
+const ELF64_RELA_SIZE: usize = 24;
+
+// the tags of the dynamic section
+const DT_NULL: u64 = 0;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_RELAENT: u64 = 9;
+
+// the types of relocations
+const R_RISCV_NONE: u32 = 0;
+const R_RISCV_RELATIVE: u32 = 3;
+
+// the keys of auxv
+pub const AT_NULL: usize = 0;
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_BASE: usize = 7;
+pub const AT_ENTRY: usize = 9;
+pub const AT_RANDOM: usize = 25;
+
+/// an ELF file mapped into a memory set
+struct ElfImage {
+    entry_point: usize,
+    /// 0 for ET_EXEC
+    load_bias: usize,
+    /// None : the program headers are not in a loadable segment
+    phdr_va: Option<usize>,
+    /// (start, end) of the program headers in the file
+    ph_range: (usize, usize),
+    ph_count: usize,
+    end_va: VirtAddr,
+}
+
+/// the reasons a page fault is not resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageFaultError {
+    /// the access is not allowed, or no map_area contains the page
+    Denied,
+    /// no frame left for the page or the page table
+    NoMemory,
+}
+
+/// ### the pages of [addr, addr + len) in user space
+/// None : the range wraps around or ends above the user space
+fn user_range(addr: VirtAddr, len: usize) -> Option<VPRange> {
+    let end = addr.0.checked_add(len)?;
+    if end > USER_SPACE_END_VIRT_ADDR.0 {
+        return None;
+    }
+    Some(VPRange::new(addr, VirtAddr(end)))
+}
 
 pub struct MemorySet {
     pub map_areas: Vec<MapArea>,
     pub page_table: PageTable,
+    /// user heap : [heap_bottom, brk)
+    pub heap_bottom: VirtAddr,
+    pub brk: VirtAddr,
 }
 
 impl MemorySet {
     /// #### only page table root is set
-    pub fn new_bare() -> Self {
-        MemorySet {
+    /// None : no frame left for the root
+    pub fn new_bare() -> Option<Self> {
+        Some(MemorySet {
             map_areas: Vec::new(),
-            page_table: PageTable::new(),
-        }
+            page_table: PageTable::new()?,
+            heap_bottom: VirtAddr(0),
+            brk: VirtAddr(0),
+        })
     }
 
     /// build realations in **page_table**
     pub fn insert_new_map_area(&mut self, map_area: MapArea) {
-        let vp_range = &map_area.vp_range;
-        let pte_flags = PTEFlags::from_bits(map_area.map_perm.bits()).unwrap();
+        let res = self.try_insert_map_area(map_area);
+        assert!(res.is_ok(), "virtual page mapping to physical page failed");
+    }
+
+    /// ### build realations in **page_table**
+    /// Err : no frame left for the page table, the pages mapped are unmapped and the area is dropped
+    pub fn try_insert_map_area(&mut self, map_area: MapArea) -> Result<(), ()> {
+        let vp_range = map_area.vp_range;
+        let pte_flags = map_area.pte_flags();
         // TODO : PTE flags may have other flags to be set
 
         // trace!("insert new map area : {:#X?}", map_area);
+        if !map_area.is_accessible() {
+            self.map_areas.push(map_area);
+            return Ok(());
+        }
         for it in vp_range.iter() {
             let vp = it.value();
-            let pp = map_area.mapped_to(vp);
-            // trace!("vp={:#X?}, pp={:#X?}", vp, pp);
-            let res = self.page_table.map_one(vp, pp, pte_flags);
-            assert!(res.is_ok(), "virtual page mapping to physical page failed");
+            // unbacked pages of lazy areas are mapped when page fault
+            if let Some(pp) = map_area.try_mapped_to(vp) {
+                // trace!("vp={:#X?}, pp={:#X?}", vp, pp);
+                if self.page_table.map_one(vp, pp, pte_flags).is_err() {
+                    let mapped = VPRange {
+                        start: vp_range.start,
+                        end: vp,
+                    };
+                    for it in mapped.iter() {
+                        if map_area.try_mapped_to(it.value()).is_some() {
+                            let _ = self.page_table.unmap_one(it.value());
+                        }
+                    }
+                    return Err(());
+                }
+            }
         }
 
         self.map_areas.push(map_area);
+        Ok(())
+    }
+
+    /// ### split the area containing `at` into two areas
+    /// nothing happens if `at` is the boundary of areas or not in any area
+    fn split_area_at(&mut self, at: Page) {
+        if let Some(map_area) = self
+            .map_areas
+            .iter_mut()
+            .find(|ma| ma.vp_range.start < at && at < ma.vp_range.end)
+        {
+            let right = map_area.split_off(at);
+            self.map_areas.push(right);
+        }
     }
 
     /// release the relations in **page_table**
+    /// areas partially in the `vp_range` are split, and the parts out of it are kept
     pub fn relase_area(&mut self, vp_range: &VPRange) {
-        let index = self
-            .map_areas
-            .iter()
-            .position(|ma| ma.vp_range == *vp_range)
-            .expect("no such map area fitting the vp_range");
+        self.split_area_at(vp_range.start);
+        self.split_area_at(vp_range.end);
 
-        // move the value out
-        let map_area = self.map_areas.remove(index);
+        // move the values out
+        let mut index = 0;
+        while index < self.map_areas.len() {
+            let cur_range = self.map_areas[index].vp_range;
+            // empty areas (e.g. the heap before growing) only keep their places
+            if cur_range.start == cur_range.end
+                || cur_range.start < vp_range.start
+                || vp_range.end < cur_range.end
+            {
+                index += 1;
+                continue;
+            }
+            let map_area = self.map_areas.remove(index);
 
-        // release the relations in page_table
-        for it in map_area.vp_range.iter() {
-            let vp = it.value();
-            if let Err(_) = self.page_table.unmap_one(vp) {
-                panic!("unmap a page failed")
+            // release the relations in page_table
+            for it in map_area.vp_range.iter() {
+                let vp = it.value();
+                if map_area.try_mapped_to(vp).is_none() || !map_area.is_accessible() {
+                    continue;
+                }
+                if let Err(_) = self.page_table.unmap_one(vp) {
+                    panic!("unmap a page failed")
+                }
             }
-        }
 
-        // the map_area will be dropped here
+            // the map_area will be dropped here
+        }
     }
 
     pub fn free_resources(&mut self) {
         self.map_areas.clear();
         self.page_table.pt_frames.clear();
     }
+
+    /// ### map the resources of a thread other than the main one
+    /// - the trap context page at `trap_ctx_va`, kernel-only
+    /// - an anonymous user stack, placed as mmap
+    /// - return (trap context frame, user stack bottom)
+    /// - Err : no space for the user stack, or no frame left
+    pub fn alloc_thread_res(&mut self, trap_ctx_va: VirtAddr) -> Result<(Frame, VirtAddr), ()> {
+        let ustack_bottom = self.mmap(
+            VirtAddr(0),
+            USER_STACK_SIZE,
+            MapPerm::U | MapPerm::R | MapPerm::W,
+            false,
+            None,
+        )?;
+        let inserted = match MapArea::new_framed(
+            VPRange::new(trap_ctx_va, trap_ctx_va.step_offset(PAGE_BYTES)),
+            MapPerm::R | MapPerm::W,
+        ) {
+            Some(ctx_area) => self.try_insert_map_area(ctx_area),
+            None => Err(()),
+        };
+        if inserted.is_err() {
+            self.relase_area(&VPRange::new(
+                ustack_bottom,
+                ustack_bottom.step_offset(USER_STACK_SIZE),
+            ));
+            return Err(());
+        }
+        let trap_ctx_frame = self
+            .page_table
+            .translate_vp(trap_ctx_va.floor_page())
+            .unwrap();
+        Ok((trap_ctx_frame, ustack_bottom))
+    }
+
+    /// release the resources mapped by `alloc_thread_res`
+    pub fn dealloc_thread_res(&mut self, trap_ctx_va: VirtAddr, ustack_bottom: VirtAddr) {
+        self.relase_area(&VPRange::new(
+            trap_ctx_va,
+            trap_ctx_va.step_offset(PAGE_BYTES),
+        ));
+        self.relase_area(&VPRange::new(
+            ustack_bottom,
+            ustack_bottom.step_offset(USER_STACK_SIZE),
+        ));
+    }
 }
 
 impl MemorySet {
-    /// return (`memory_set`, `entry_point`, `user_stack_top`)
-    pub fn new_from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
-        let mut memory_set = MemorySet::new_bare();
+    /// ### check the ELF header, the file should be a RISC-V 64 executable
+    /// little endian, ET_EXEC or ET_DYN
+    /// - return e_type
+    fn check_elf_header(elf_data: &[u8]) -> Result<u16, ()> {
+        if elf_data.len() < ELF64_HEADER_SIZE || elf_data[..4] != ELF_MAGIC {
+            return Err(());
+        }
+        let e_type = u16::from_le_bytes([elf_data[16], elf_data[17]]);
+        let e_machine = u16::from_le_bytes([elf_data[18], elf_data[19]]);
+        if elf_data[4] != ELFCLASS64
+            || elf_data[5] != ELFDATA2LSB
+            || e_machine != EM_RISCV
+            || (e_type != ET_EXEC && e_type != ET_DYN)
+        {
+            return Err(());
+        }
+        Ok(e_type)
+    }
 
-        // insert trampoline
-        memory_set.insert_new_map_area(MapArea::new_trampoline());
+    /// read a little endian u64 from the file, Err : out of the file
+    fn read_u64(elf_data: &[u8], offset: usize) -> Result<u64, ()> {
+        let bytes = elf_data
+            .get(offset..offset.checked_add(8).ok_or(())?)
+            .ok_or(())?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
 
-        // insert trap context
-        let ctx_area = MapArea::new(
-            VPRange::new(TRAP_CTX_VIRT_ADDR, TRAMPOLINE_VIRT_ADDR),
-            MapType::Framed(BTreeMap::new()),
-            MapPerm::R | MapPerm::W,
-            None,
-        );
-        memory_set.insert_new_map_area(ctx_area);
+    /// ### apply the relocations in `.rela.dyn` of a PIE
+    /// - `dynamic` : (offset, size) of the dynamic segment in the file
+    /// - `loads` : (vaddr, offset, file_size) of the loadable segments, not biased
+    /// - only R_RISCV_RELATIVE is supported : *(bias + offset) = bias + addend
+    /// - Err : malformed tables, unsupported types, or targets out of the segments
+    fn apply_relocations(
+        &mut self,
+        elf_data: &[u8],
+        load_bias: usize,
+        dynamic: (usize, usize),
+        loads: &[(usize, usize, usize)],
+    ) -> Result<(), ()> {
+        let (mut rela, mut rela_size, mut rela_ent) = (None, 0, ELF64_RELA_SIZE);
+        let (dyn_offset, dyn_size) = dynamic;
+        for i in 0..dyn_size / ELF64_DYN_SIZE {
+            let entry = dyn_offset + i * ELF64_DYN_SIZE;
+            let (tag, value) = (
+                Self::read_u64(elf_data, entry)?,
+                Self::read_u64(elf_data, entry + 8)? as usize,
+            );
+            match tag {
+                DT_NULL => break,
+                DT_RELA => rela = Some(value),
+                DT_RELASZ => rela_size = value,
+                DT_RELAENT => rela_ent = value,
+                _ => {}
+            }
+        }
+        let rela = match rela {
+            Some(rela) => rela,
+            None => return Ok(()),
+        };
+        if rela_ent != ELF64_RELA_SIZE {
+            return Err(());
+        }
 
-        // parse elf file by xmas_elf
-        let elf = xmas_elf::ElfFile::new(elf_data).expect("failed to parse elf");
+        // the table is found in the file through the segment containing it
+        let (vaddr, offset, _) = loads
+            .iter()
+            .find(|(vaddr, _, file_size)| {
+                *vaddr <= rela && rela.saturating_add(rela_size) <= vaddr + file_size
+            })
+            .ok_or(())?;
+        let table = offset + (rela - vaddr);
 
-        // get elf header
-        let elf_headr = elf.header;
+        for i in 0..rela_size / ELF64_RELA_SIZE {
+            let entry = table + i * ELF64_RELA_SIZE;
+            let r_offset = Self::read_u64(elf_data, entry)? as usize;
+            let r_info = Self::read_u64(elf_data, entry + 8)?;
+            let r_addend = Self::read_u64(elf_data, entry + 16)? as usize;
+            match r_info as u32 {
+                R_RISCV_NONE => {}
+                R_RISCV_RELATIVE => {
+                    let target = load_bias.checked_add(r_offset).ok_or(())?;
+                    let value = load_bias.wrapping_add(r_addend);
+                    self.write_segment(target, &value.to_le_bytes())?;
+                }
+                _ => return Err(()),
+            }
+        }
+        Ok(())
+    }
 
-        // check if the magic is "0x7F E L F"
-        let magic = elf_headr.pt1.magic;
-        assert_eq!(
-            magic,
-            [0x7F, 'E' as u8, 'L' as u8, 'F' as u8],
-            "elf magic error"
-        );
+    /// ### write `data` into the loadable segments before the program runs
+    /// read-only pages are written too, the relocations may target them
+    /// - Err : the range is not in a loadable segment of the user
+    fn write_segment(&mut self, va: usize, data: &[u8]) -> Result<(), ()> {
+        let end = va.checked_add(data.len()).ok_or(())?;
+        if end > MMAP_BASE_VIRT_ADDR.0 {
+            return Err(());
+        }
+        let vp_range = VPRange::new(VirtAddr(va), VirtAddr(end));
+        for it in vp_range.iter() {
+            // the pages already bounded return Err, which is fine
+            let _ = self.handle_page_fault(it.value().start_address(), false);
+        }
+        let bufs = self
+            .page_table
+            .translate_byte_buffer_mut(va, data.len())
+            .ok_or(())?;
+        let mut copied = 0;
+        for slice in bufs {
+            slice.copy_from_slice(&data[copied..copied + slice.len()]);
+            copied += slice.len();
+        }
+        Ok(())
+    }
+
+    /// ### the interpreter requested by `PT_INTERP`
+    /// - Ok(None) : a static program
+    /// - Err : not a valid ELF file, or a malformed path
+    pub fn elf_interp(elf_data: &[u8]) -> Result<Option<String>, ()> {
+        Self::check_elf_header(elf_data)?;
+        let elf = xmas_elf::ElfFile::new(elf_data).map_err(|_| ())?;
+        for i in 0..elf.header.pt2.ph_count() {
+            let ph = elf.program_header(i).map_err(|_| ())?;
+            if ph.get_type().map_err(|_| ())? != xmas_elf::program::Type::Interp {
+                continue;
+            }
+            let offset = ph.offset() as usize;
+            let file_end = offset.checked_add(ph.file_size() as usize).ok_or(())?;
+            let path = elf_data.get(offset..file_end).ok_or(())?;
+            let path = path.split(|&b| b == 0).next().unwrap();
+            let path = core::str::from_utf8(path).map_err(|_| ())?;
+            if path.is_empty() {
+                return Err(());
+            }
+            return Ok(Some(path.into()));
+        }
+        Ok(None)
+    }
 
-        // get program header table
+    /// ### map the loadable segments of an ELF file
+    /// - ET_DYN : loaded at `dyn_base`, ET_EXEC : as linked
+    /// - `relocate` : apply the relocations in `.rela.dyn`, which is left to the
+    ///   interpreter for a dynamically linked program
+    /// - Err : errno (positive), ENOMEM : no frame left,
+    ///   ENOEXEC : malformed segments, segments out of the user's range
+    ///   (from 0 to `MMAP_BASE_VIRT_ADDR`, far below the trap context and trampoline)
+    ///   or overlapping with the mapped areas
+    fn map_elf(
+        &mut self,
+        elf_data: &[u8],
+        dyn_base: usize,
+        relocate: bool,
+    ) -> Result<ElfImage, isize> {
+        let e_type = Self::check_elf_header(elf_data).map_err(|_| ENOEXEC)?;
+        let load_bias = if e_type == ET_DYN { dyn_base } else { 0 };
+        // parse elf file by xmas_elf
+        let elf = xmas_elf::ElfFile::new(elf_data).map_err(|_| ENOEXEC)?;
+        let entry_point = (elf.header.pt2.entry_point() as usize)
+            .checked_add(load_bias)
+            .ok_or(ENOEXEC)?;
+        let ph_offset = elf.header.pt2.ph_offset() as usize;
         let ph_count = elf.header.pt2.ph_count();
-        let mut max_end_va = VirtAddr(0);
+        let ph_end = ph_offset
+            .checked_add(ph_count as usize * ELF64_PHDR_SIZE)
+            .ok_or(ENOEXEC)?;
+        if elf.header.pt2.ph_entry_size() as usize != ELF64_PHDR_SIZE || ph_end > elf_data.len() {
+            return Err(ENOEXEC);
+        }
+
+        let mut end_va = VirtAddr(0);
+        let mut loads = Vec::new();
+        let mut dynamic = None;
+        let mut phdr_va = None;
+        let mut entry_found = false;
 
         // map all loadable segments
         for i in 0..ph_count {
-            let ph = elf.program_header(i).expect("failed to get program header");
-            // If this header is a loadable segment, map it into memory.
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                let start_va = VirtAddr(ph.virtual_addr() as usize);
-                let end_va = start_va.step_offset(ph.mem_size() as usize);
-                max_end_va = max(max_end_va, end_va);
-
-                // map it with U permission and R/W/X according to the flags
-                let mut map_perm = MapPerm::U;
-                let ph_flag = ph.flags();
-                if ph_flag.is_read() {
-                    map_perm |= MapPerm::R;
-                }
-                if ph_flag.is_write() {
-                    map_perm |= MapPerm::W;
-                }
-                if ph_flag.is_execute() {
-                    map_perm |= MapPerm::X;
+            let ph = elf.program_header(i).map_err(|_| ENOEXEC)?;
+            let (offset, file_size) = (ph.offset() as usize, ph.file_size() as usize);
+            let file_end = offset.checked_add(file_size).ok_or(ENOEXEC)?;
+            match ph.get_type().map_err(|_| ENOEXEC)? {
+                xmas_elf::program::Type::Load => {}
+                xmas_elf::program::Type::Dynamic => {
+                    if file_end > elf_data.len() {
+                        return Err(ENOEXEC);
+                    }
+                    dynamic = Some((offset, file_size));
+                    continue;
                 }
+                _ => continue,
+            }
+            let (vaddr, mem_size) = (ph.virtual_addr() as usize, ph.mem_size() as usize);
+            loads.push((vaddr, offset, file_size));
+            let start = vaddr.checked_add(load_bias).ok_or(ENOEXEC)?;
+            let end = start.checked_add(mem_size).ok_or(ENOEXEC)?;
+            if file_size > mem_size || file_end > elf_data.len() || end > MMAP_BASE_VIRT_ADDR.0 {
+                return Err(ENOEXEC);
+            }
+            let start_va = VirtAddr(start);
+            let vp_range = VPRange::new(start_va, VirtAddr(end));
+            if self
+                .map_areas
+                .iter()
+                .any(|ma| ma.vp_range.is_overlap(&vp_range))
+            {
+                return Err(ENOEXEC);
+            }
+            end_va = max(end_va, VirtAddr(end));
 
-                // build a map_area and bound frames
-                let fill_data = FillData::new(
-                    start_va,
-                    start_va.step_offset(ph.file_size() as usize),
-                    &elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize],
-                );
+            // the program headers are loaded with this segment
+            if offset <= ph_offset && ph_end <= file_end {
+                phdr_va = Some(start + ph_offset - offset);
+            }
 
-                let map_area = MapArea::new(
-                    VPRange::new(start_va, end_va),
-                    MapType::Framed(BTreeMap::new()),
-                    map_perm,
-                    Some(fill_data),
-                );
+            // map it with U permission and R/W/X according to the flags
+            let mut map_perm = MapPerm::U;
+            let ph_flag = ph.flags();
+            if ph_flag.is_read() {
+                map_perm |= MapPerm::R;
+            }
+            if ph_flag.is_write() {
+                map_perm |= MapPerm::W;
+            }
+            if ph_flag.is_execute() {
+                map_perm |= MapPerm::X;
+                entry_found |= start <= entry_point && entry_point < end;
+            }
+
+            // build a map_area and bound frames
+            let fill_data = FillData::new(
+                start_va,
+                start_va.step_offset(file_size),
+                &elf_data[offset..file_end],
+            );
+
+            // pages only in .bss are bounded when first accessed
+            let map_area = MapArea::new_filled(vp_range, map_perm, fill_data).ok_or(ENOMEM)?;
+
+            // insert the map_area into memory_set
+            self.try_insert_map_area(map_area).map_err(|_| ENOMEM)?;
+        }
+        if !entry_found {
+            return Err(ENOEXEC);
+        }
 
-                // insert the map_area into memory_set
-                memory_set.insert_new_map_area(map_area);
+        if let (Some(dynamic), true) = (dynamic, relocate) {
+            self.apply_relocations(elf_data, load_bias, dynamic, &loads)
+                .map_err(|_| ENOEXEC)?;
+        }
+
+        Ok(ElfImage {
+            entry_point,
+            load_bias,
+            phdr_va,
+            ph_range: (ph_offset, ph_end),
+            ph_count: ph_count as usize,
+            end_va,
+        })
+    }
+
+    /// ### build the user space of an ELF program
+    /// - ET_DYN (PIE) : loaded at `PIE_BASE_VIRT_ADDR`
+    /// - `interp_data` : the interpreter named by `PT_INTERP`, loaded at `INTERP_BASE_VIRT_ADDR`,
+    ///   the program starts from the interpreter, which finds the program by AT_PHDR and AT_ENTRY
+    /// - return (`memory_set`, `entry_point`, `user_stack_top`, `auxv`)
+    /// - Err : errno (positive), ENOEXEC : malformed program or interpreter,
+    ///   ENOMEM : no frame left
+    pub fn new_from_elf(
+        elf_data: &[u8],
+        interp_data: Option<&[u8]>,
+    ) -> Result<(Self, usize, usize, Vec<(usize, usize)>), isize> {
+        let mut memory_set = MemorySet::new_bare().ok_or(ENOMEM)?;
+
+        // insert trampoline
+        memory_set
+            .try_insert_map_area(MapArea::new_trampoline())
+            .map_err(|_| ENOMEM)?;
+
+        // insert trap context
+        let ctx_area = MapArea::new_framed(
+            VPRange::new(TRAP_CTX_VIRT_ADDR, TRAMPOLINE_VIRT_ADDR),
+            MapPerm::R | MapPerm::W,
+        )
+        .ok_or(ENOMEM)?;
+        memory_set
+            .try_insert_map_area(ctx_area)
+            .map_err(|_| ENOMEM)?;
+
+        let program = memory_set.map_elf(elf_data, PIE_BASE_VIRT_ADDR.0, interp_data.is_none())?;
+        let mut max_end_va = program.end_va;
+
+        // the program headers are not loaded : copy them after the segments, read-only
+        let phdr_va = match program.phdr_va {
+            Some(phdr_va) => phdr_va,
+            None => {
+                let (ph_offset, ph_end) = program.ph_range;
+                let phdr_start = max_end_va.ceil_page().start_address();
+                let phdr_end = phdr_start.step_offset(ph_end - ph_offset);
+                let fill_data = FillData::new(phdr_start, phdr_end, &elf_data[ph_offset..ph_end]);
+                let phdr_area = MapArea::new_filled(
+                    VPRange::new(phdr_start, phdr_end),
+                    MapPerm::U | MapPerm::R,
+                    fill_data,
+                )
+                .ok_or(ENOMEM)?;
+                memory_set
+                    .try_insert_map_area(phdr_area)
+                    .map_err(|_| ENOMEM)?;
+                max_end_va = phdr_end;
+                phdr_start.0
             }
+        };
+
+        let mut auxv = Vec::from([
+            (AT_PHDR, phdr_va),
+            (AT_PHENT, ELF64_PHDR_SIZE),
+            (AT_PHNUM, program.ph_count),
+            (AT_PAGESZ, PAGE_BYTES),
+            (AT_ENTRY, program.entry_point),
+        ]);
+        let mut entry_point = program.entry_point;
+
+        // the interpreter is a static program, relocated by the kernel
+        if let Some(interp_data) = interp_data {
+            let interp = memory_set.map_elf(interp_data, INTERP_BASE_VIRT_ADDR.0, true)?;
+            max_end_va = max(max_end_va, interp.end_va);
+            auxv.push((AT_BASE, interp.load_bias));
+            entry_point = interp.entry_point;
         }
 
         // build the user stack : next_page() actually build a guard page...
         let user_stack_bottom = max_end_va.ceil_page().next_page().start_address();
         let user_stack_top = user_stack_bottom.step_offset(USER_STACK_SIZE);
-        let user_stack = MapArea::new(
+        let user_stack = MapArea::new_lazy(
             VPRange::new(user_stack_bottom, user_stack_top),
-            MapType::Framed(BTreeMap::new()),
             MapPerm::U | MapPerm::R | MapPerm::W,
-            None,
         );
         memory_set.insert_new_map_area(user_stack);
 
-        (
-            memory_set,
-            elf_headr.pt2.entry_point() as usize,
-            user_stack_top.0,
-        )
+        // build the user heap above the user stack with a guard page, empty at first
+        let heap_bottom = user_stack_top.ceil_page().next_page().start_address();
+        let user_heap = MapArea::new_lazy(
+            VPRange::new(heap_bottom, heap_bottom),
+            MapPerm::U | MapPerm::R | MapPerm::W,
+        );
+        memory_set.insert_new_map_area(user_heap);
+        memory_set.heap_bottom = heap_bottom;
+        memory_set.brk = heap_bottom;
+
+        // the stack and heap should not reach the mmap area
+        if heap_bottom.0 >= MMAP_BASE_VIRT_ADDR.0 {
+            return Err(ENOEXEC);
+        }
+
+        Ok((memory_set, entry_point, user_stack_top.0, auxv))
+    }
+
+    /// ### build the initial user stack in the System V layout
+    /// from the top : the strings of argv and envp, 16 random bytes for AT_RANDOM,
+    /// then (aligned to 16) auxv, envp, argv and argc
+    /// - return (sp, argv) : sp points to argc
+    /// - Err : errno (positive), E2BIG : the arguments take more than `ARG_MAX`,
+    ///   ENOMEM : no frame left for the stack
+    pub fn init_user_stack(
+        &mut self,
+        user_sp: usize,
+        argv: &[String],
+        envp: &[String],
+        auxv: &[(usize, usize)],
+    ) -> Result<(usize, usize), isize> {
+        let strings_len: usize = argv.iter().chain(envp).map(|s| s.len() + 1).sum();
+        let random_ptr = ((user_sp - strings_len) & !0xf) - 16;
+        // argc, argv with NULL, envp with NULL, auxv with AT_RANDOM and AT_NULL
+        let words_len =
+            (1 + argv.len() + 1 + envp.len() + 1 + 2 * (auxv.len() + 2)) * size_of::<usize>();
+        let sp = (random_ptr - words_len) & !0xf;
+        if user_sp - sp > ARG_MAX {
+            return Err(E2BIG);
+        }
+        // all the pages are bounded here, the writes below can't fail
+        self.fault_in_range(VirtAddr(sp), user_sp - sp)
+            .map_err(|_| ENOMEM)?;
+
+        let mut str_ptr = user_sp;
+        let mut push_str = |s: &String| {
+            str_ptr -= s.len() + 1;
+            self.write_bytes(str_ptr, s.as_bytes());
+            self.write_bytes(str_ptr + s.len(), &[0]);
+            str_ptr
+        };
+        let argv_ptrs: Vec<usize> = argv.iter().map(&mut push_str).collect();
+        let envp_ptrs: Vec<usize> = envp.iter().map(&mut push_str).collect();
+        self.write_bytes(random_ptr, &rand_u64().to_ne_bytes());
+        self.write_bytes(random_ptr + 8, &rand_u64().to_ne_bytes());
+
+        let mut words = Vec::from([argv.len()]);
+        words.extend(argv_ptrs);
+        words.push(0);
+        words.extend(envp_ptrs);
+        words.push(0);
+        for (key, value) in auxv {
+            words.extend([*key, *value]);
+        }
+        words.extend([AT_RANDOM, random_ptr, AT_NULL, 0]);
+        for (i, word) in words.iter().enumerate() {
+            self.write_bytes(sp + i * size_of::<usize>(), &word.to_ne_bytes());
+        }
+        Ok((sp, sp + size_of::<usize>()))
     }
 
-    // fork all the areas except for trampoline : Target
-    pub fn fork_memory_set(&self) -> Self {
-        let mut memory_set = MemorySet::new_bare();
+    /// copy `data` into user space, the pages should have been faulted in
+    fn write_bytes(&self, va: usize, data: &[u8]) {
+        let bufs = self
+            .page_table
+            .translate_byte_buffer_mut(va, data.len())
+            .expect("writing to unmapped user memory");
+        let mut copied = 0;
+        for slice in bufs {
+            slice.copy_from_slice(&data[copied..copied + slice.len()]);
+            copied += slice.len();
+        }
+    }
+
+    /// fork all the areas except for trampoline : Target
+    /// - user framed areas : share frames with parent, writable pages marked as COW on both sides
+    /// - kernel-only framed areas (trap context) : copy data
+    /// - None : no frame left
+    pub fn fork_memory_set(&mut self) -> Option<Self> {
+        let mut memory_set = MemorySet::new_bare()?;
+        memory_set.heap_bottom = self.heap_bottom;
+        memory_set.brk = self.brk;
 
         for area in self.map_areas.iter() {
-            let map_type = match area.map_type {
-                MapType::Identical => MapType::Identical,
-                MapType::Target(frame) => MapType::Target(frame),
-                MapType::Framed(_) => MapType::Framed(BTreeMap::new()),
-            };
+            if let MapType::Framed(ref mem_frames) = area.map_type {
+                if area.map_perm.contains(MapPerm::U) {
+                    memory_set.try_insert_map_area(area.share_frames()).ok()?;
+                    if area.map_perm.contains(MapPerm::W) {
+                        // only the bounded pages are shared
+                        for &vp in mem_frames.keys() {
+                            let res = self.page_table.mark_cow(vp);
+                            assert!(res.is_ok(), "marking parent's page as COW failed");
+                            let res = memory_set.page_table.mark_cow(vp);
+                            assert!(res.is_ok(), "marking child's page as COW failed");
+                        }
+                    }
+                    continue;
+                }
+            }
 
-            let new_area =
-                MapArea::new(area.vp_range.clone(), map_type, area.map_perm.clone(), None);
+            let new_area = match area.map_type {
+                MapType::Identical => {
+                    MapArea::new(area.vp_range, MapType::Identical, area.map_perm, None)
+                }
+                MapType::Target(frame) => {
+                    MapArea::new(area.vp_range, MapType::Target(frame), area.map_perm, None)
+                }
+                MapType::Framed(_) => MapArea::new_framed(area.vp_range, area.map_perm)?,
+            };
 
             // when framed, copy data
             if let MapType::Framed(_) = new_area.map_type {
@@ -189,9 +713,355 @@ impl MemorySet {
                 }
             }
 
-            memory_set.insert_new_map_area(new_area);
+            memory_set.try_insert_map_area(new_area).ok()?;
         }
 
-        memory_set
+        Some(memory_set)
+    }
+
+    /// handle a store page fault on a COW page
+    /// Err : not a COW page, it's a real fault, or no frame left for the copy
+    pub fn copy_on_write(&mut self, vp: Page) -> Result<(), PageFaultError> {
+        let pte = self.page_table.find_pte(vp).ok_or(PageFaultError::Denied)?;
+        if !pte.is_valid() || !pte.is_cow() {
+            return Err(PageFaultError::Denied);
+        }
+        let flags = (pte.get_flags() | PTEFlags::W) - PTEFlags::COW;
+
+        let map_area = self
+            .map_areas
+            .iter_mut()
+            .find(|ma| ma.vp_range.contains(vp))
+            .ok_or(PageFaultError::Denied)?;
+        // COW pages may have been protected as read-only by mprotect
+        if !map_area.map_perm.contains(MapPerm::W) {
+            return Err(PageFaultError::Denied);
+        }
+        let frame = map_area.copy_on_write(vp).ok_or(PageFaultError::NoMemory)?;
+
+        // the pte is valid, checked above
+        self.page_table
+            .remap_one(vp, frame, flags)
+            .map_err(|_| PageFaultError::Denied)
+    }
+
+    /// ### handle a page fault in user space
+    /// - mapped page : copy on write when writing
+    /// - unbacked page in a lazy area : bound a zeroed frame, or the page of the mapped file
+    /// - Err : the access is not allowed, no map_area contains it, or no frame left
+    pub fn handle_page_fault(
+        &mut self,
+        va: VirtAddr,
+        is_write: bool,
+    ) -> Result<(), PageFaultError> {
+        let vp = va.floor_page();
+        if let Some(pte) = self.page_table.find_pte(vp) {
+            if pte.is_valid() {
+                // another thread of the process has handled the fault on this page,
+                // the TLB is flushed when back to user space
+                let needed = PTEFlags::U | if is_write { PTEFlags::W } else { PTEFlags::R };
+                if pte.get_flags().contains(needed) {
+                    return Ok(());
+                }
+                return if is_write {
+                    self.copy_on_write(vp)
+                } else {
+                    Err(PageFaultError::Denied)
+                };
+            }
+        }
+
+        let map_area = self
+            .map_areas
+            .iter_mut()
+            .find(|ma| ma.vp_range.contains(vp))
+            .ok_or(PageFaultError::Denied)?;
+        if !map_area.map_perm.contains(MapPerm::U)
+            || (is_write && !map_area.map_perm.contains(MapPerm::W))
+            || (!is_write && !map_area.map_perm.contains(MapPerm::R))
+        {
+            return Err(PageFaultError::Denied);
+        }
+        let pte_flags = map_area.pte_flags();
+        let frame = map_area.lazy_alloc(vp)?;
+        // the page cache's frame is copied when first written
+        let cow = pte_flags.contains(PTEFlags::W) && map_area.is_shared(vp);
+        let map_flags = if cow {
+            (pte_flags - PTEFlags::W) | PTEFlags::COW
+        } else {
+            pte_flags
+        };
+        // the pte is invalid, only the page table may fail to grow
+        self.page_table
+            .map_one(vp, frame, map_flags)
+            .map_err(|_| PageFaultError::NoMemory)?;
+        if cow && is_write {
+            self.copy_on_write(vp)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// ### the kernel accesses user's memory by physical address
+    /// resolve the page faults (lazy frames, COW) user would meet before writing to it,
+    /// or before reading it for the read-only pages
+    /// - Err : no frame left
+    pub fn fault_in_range(&mut self, start: VirtAddr, len: usize) -> Result<(), ()> {
+        let vp_range = VPRange::new(start, start.step_offset(len));
+        for it in vp_range.iter() {
+            let va = it.value().start_address();
+            // invalid pages are left to the translation, which will fail
+            let res = match self.handle_page_fault(va, true) {
+                Err(PageFaultError::Denied) => self.handle_page_fault(va, false),
+                res => res,
+            };
+            if res == Err(PageFaultError::NoMemory) {
+                return Err(());
+            }
+        }
+        Ok(())
+    }
+
+    /// ### the user pages in [start, start + len) are all mapped writable
+    /// the kernel should not write to the read-only ones, which may be in the page cache
+    pub fn is_user_writable(&self, start: VirtAddr, len: usize) -> bool {
+        VPRange::new(start, start.step_offset(len))
+            .iter()
+            .all(|it| match self.page_table.find_pte(it.value()) {
+                Some(pte) => pte.is_valid() && pte.get_flags().contains(PTEFlags::U | PTEFlags::W),
+                None => false,
+            })
+    }
+
+    /// (resident pages, pages shared with other address spaces) of the user
+    pub fn resident_pages(&self) -> (usize, usize) {
+        self.map_areas
+            .iter()
+            .filter(|ma| ma.map_perm.contains(MapPerm::U))
+            .map(|ma| ma.resident_pages())
+            .fold((0, 0), |(rss, shared), (area_rss, area_shared)| {
+                (rss + area_rss, shared + area_shared)
+            })
+    }
+
+    /// ### move the program break to `new_brk`
+    /// the heap area grows or shrinks by pages, frames are bounded lazily
+    /// - Err : below the heap bottom, or overlapping with other areas
+    pub fn set_brk(&mut self, new_brk: VirtAddr) -> Result<(), ()> {
+        if new_brk < self.heap_bottom {
+            return Err(());
+        }
+        let heap_start = self.heap_bottom.floor_page();
+        let old_end = self.brk.ceil_page();
+        let new_end = new_brk.ceil_page();
+
+        // the heap area may have been unmapped by munmap
+        let heap_index = self
+            .map_areas
+            .iter()
+            .position(|ma| ma.vp_range.start == heap_start && ma.vp_range.end == old_end)
+            .ok_or(())?;
+
+        if new_end > old_end {
+            let grown = VPRange {
+                start: old_end,
+                end: new_end,
+            };
+            if self
+                .map_areas
+                .iter()
+                .any(|ma| ma.vp_range.is_overlap(&grown))
+            {
+                return Err(());
+            }
+        } else {
+            for it in (VPRange {
+                start: new_end,
+                end: old_end,
+            })
+            .iter()
+            {
+                let vp = it.value();
+                if self.page_table.translate_vp(vp).is_some() {
+                    let res = self.page_table.unmap_one(vp);
+                    assert!(res.is_ok(), "unmap a heap page failed");
+                }
+            }
+        }
+
+        self.map_areas[heap_index].resize(new_end);
+        self.brk = new_brk;
+        Ok(())
+    }
+
+    /// ### find a free range of `page_num` pages for mmap
+    /// - try the hint first, then first-fit from `MMAP_BASE_VIRT_ADDR`
+    /// - the range should be in the user half of Sv39
+    fn find_free_range(&self, hint: Page, page_num: usize) -> Option<VPRange> {
+        let limit = USER_SPACE_END_VIRT_ADDR.floor_page();
+        let is_free = |range: &VPRange| {
+            range.end.0 <= limit.0
+                && !self
+                    .map_areas
+                    .iter()
+                    .any(|ma| ma.vp_range.is_overlap(range))
+        };
+
+        if let Some(end) = hint.0.checked_add(page_num) {
+            let hinted = VPRange {
+                start: hint,
+                end: Page(end),
+            };
+            if hint.0 != 0 && is_free(&hinted) {
+                return Some(hinted);
+            }
+        }
+
+        let mut start = MMAP_BASE_VIRT_ADDR.floor_page();
+        loop {
+            let range = VPRange {
+                start,
+                end: Page(start.0.checked_add(page_num)?),
+            };
+            if range.end.0 > limit.0 {
+                return None;
+            }
+            // jump over the first area overlapping with the range
+            match self
+                .map_areas
+                .iter()
+                .filter(|ma| ma.vp_range.is_overlap(&range))
+                .map(|ma| ma.vp_range.end)
+                .max()
+            {
+                Some(end) => start = end,
+                None => return Some(range),
+            }
+        }
+    }
+
+    /// ### map an area of `len` bytes, frames are bounded lazily
+    /// - `fixed` : the area must be at `addr`, old mappings there are released
+    /// - otherwise `addr` is only a hint
+    /// - `file` : a private mapping of the file, None : anonymous
+    /// - return the start address of the area
+    pub fn mmap(
+        &mut self,
+        addr: VirtAddr,
+        len: usize,
+        map_perm: MapPerm,
+        fixed: bool,
+        file: Option<MappedFile>,
+    ) -> Result<VirtAddr, ()> {
+        if len == 0 || addr.get_offset() != 0 {
+            return Err(());
+        }
+        let vp_range = if fixed {
+            if addr.0 == 0 {
+                return Err(());
+            }
+            let vp_range = user_range(addr, len).ok_or(())?;
+            self.relase_area(&vp_range);
+            vp_range
+        } else {
+            if len > USER_SPACE_END_VIRT_ADDR.0 {
+                return Err(());
+            }
+            let page_num = VirtAddr(len).ceil_page().0;
+            self.find_free_range(addr.floor_page(), page_num)
+                .ok_or(())?
+        };
+
+        let map_perm = map_perm | MapPerm::U;
+        self.insert_new_map_area(match file {
+            Some(file) => MapArea::new_file(vp_range, map_perm, file),
+            None => MapArea::new_lazy(vp_range, map_perm),
+        });
+        Ok(vp_range.start.start_address())
+    }
+
+    /// ### check if `vp_range` is fully covered by user areas
+    fn is_user_range(&self, vp_range: &VPRange) -> bool {
+        let mut areas: Vec<&VPRange> = self
+            .map_areas
+            .iter()
+            .filter(|ma| ma.map_perm.contains(MapPerm::U) && ma.vp_range.is_overlap(vp_range))
+            .map(|ma| &ma.vp_range)
+            .collect();
+        areas.sort();
+
+        let mut cur = vp_range.start;
+        for range in areas {
+            if range.start > cur {
+                return false;
+            }
+            cur = max(cur, range.end);
+        }
+        cur >= vp_range.end
+    }
+
+    /// ### unmap the user pages in [addr, addr + len)
+    /// areas are split when partially unmapped
+    pub fn munmap(&mut self, addr: VirtAddr, len: usize) -> Result<(), ()> {
+        if len == 0 || addr.get_offset() != 0 {
+            return Err(());
+        }
+        let vp_range = user_range(addr, len).ok_or(())?;
+        self.relase_area(&vp_range);
+        Ok(())
+    }
+
+    /// ### change the permission of the user pages in [addr, addr + len)
+    /// - the whole range should be mapped, areas are split at the boundaries
+    /// - the frames shared with other address spaces get W only after copying (COW)
+    /// - PROT_NONE pages are unmapped in the page table, with their frames kept
+    pub fn mprotect(&mut self, addr: VirtAddr, len: usize, map_perm: MapPerm) -> Result<(), ()> {
+        if addr.get_offset() != 0 {
+            return Err(());
+        }
+        let vp_range = user_range(addr, len).ok_or(())?;
+        if !self.is_user_range(&vp_range) {
+            return Err(());
+        }
+        self.split_area_at(vp_range.start);
+        self.split_area_at(vp_range.end);
+
+        let map_perm = map_perm | MapPerm::U;
+        for map_area in self.map_areas.iter_mut() {
+            let cur_range = map_area.vp_range;
+            if cur_range.start < vp_range.start || vp_range.end < cur_range.end {
+                continue;
+            }
+            map_area.map_perm = map_perm;
+            let pte_flags = map_area.pte_flags();
+
+            for it in cur_range.iter() {
+                let vp = it.value();
+                let frame = match map_area.try_mapped_to(vp) {
+                    Some(frame) => frame,
+                    None => continue,
+                };
+                let mapped = self.page_table.translate_vp(vp).is_some();
+                if !map_area.is_accessible() {
+                    if mapped {
+                        let res = self.page_table.unmap_one(vp);
+                        assert!(res.is_ok(), "unmap a page failed");
+                    }
+                    continue;
+                }
+                let flags = if pte_flags.contains(PTEFlags::W) && map_area.is_shared(vp) {
+                    (pte_flags - PTEFlags::W) | PTEFlags::COW
+                } else {
+                    pte_flags
+                };
+                let res = if mapped {
+                    self.page_table.remap_one(vp, frame, flags)
+                } else {
+                    self.page_table.map_one(vp, frame, flags)
+                };
+                assert!(res.is_ok(), "remap a page failed");
+            }
+        }
+        Ok(())
     }
 }
@@ -6,14 +6,17 @@ pub mod map_area;
 pub mod memory_set;
 pub mod mm_test;
 pub mod page;
+pub mod page_cache;
 pub mod page_table;
 
 pub use address::{PhysAddr, VARange, VirtAddr};
-pub use frame_allocator::{frame_alloc, frame_allocator_init, frame_dealloc, FrameTracker};
-pub use heap_allocator::{heap_init, heap_test::heap_test};
+pub use frame_allocator::{
+    frame_alloc, frame_allocator_init, frame_dealloc, frame_stats, FrameTracker,
+};
+pub use heap_allocator::{heap_init, heap_stats, heap_test::heap_test};
 pub use kernel_space::KERNEL_SPACE;
-pub use map_area::{MapArea, MapPerm, MapType};
-pub use memory_set::MemorySet;
+pub use map_area::{MapArea, MapPerm, MapType, MappedFile};
+pub use memory_set::{MemorySet, PageFaultError};
 pub use page::{Frame, Page, VPRange};
 pub use page_table::{PTEFlags, PageTable, PTE};
 
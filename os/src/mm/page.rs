@@ -87,6 +87,14 @@ impl VPRange {
         }
     }
 
+    pub fn contains(&self, vp: Page) -> bool {
+        self.start <= vp && vp < self.end
+    }
+
+    pub fn is_overlap(&self, other: &VPRange) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
     pub fn iter(&self) -> Iter {
         Iter {
             cur: self.start,
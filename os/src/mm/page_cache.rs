@@ -0,0 +1,52 @@
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+};
+use lazy_static::lazy_static;
+
+use crate::{config::PAGE_BYTES, fs::vfs::VfsInode, kfc_util::spin_lock::SpinLock};
+
+use super::{frame_alloc, FrameTracker};
+
+lazy_static! {
+    /// ### the pages of the files mapped by mmap, keyed by (inode, page index)
+    /// - the mappings hold the frames, a frame is freed once no mapping uses it
+    /// - an inode is keyed by its address, it can't be reused while its pages are mapped,
+    ///   as the mapping areas hold the inode too
+    static ref PAGE_CACHE: SpinLock<BTreeMap<(usize, usize), Weak<FrameTracker>>> =
+        SpinLock::new("PAGE_CACHE", BTreeMap::new());
+}
+
+fn inode_key(inode: &Arc<dyn VfsInode>) -> usize {
+    Arc::as_ptr(inode) as *const u8 as usize
+}
+
+/// ### the frame holding the `index`th page of a file
+/// - read from the file if no mapping is using it, the bytes beyond the end are zero
+/// - the frame is shared by all the mappings of the page, it should not be written
+/// - None : no frame left
+pub fn get_page(inode: &Arc<dyn VfsInode>, index: usize) -> Option<Arc<FrameTracker>> {
+    let key = (inode_key(inode), index);
+    if let Some(frame) = PAGE_CACHE.lock().get(&key).and_then(Weak::upgrade) {
+        return Some(frame);
+    }
+
+    // the file is read without the lock, another mapping may read the same page meanwhile
+    let frame = Arc::new(frame_alloc()?);
+    inode.read_at(index * PAGE_BYTES, frame.0.get_bytes_array_mut());
+    let mut cache = PAGE_CACHE.lock();
+    if let Some(cached) = cache.get(&key).and_then(Weak::upgrade) {
+        return Some(cached);
+    }
+    // the pages no longer mapped are cleaned up here
+    cache.retain(|_, frame| frame.strong_count() > 0);
+    cache.insert(key, Arc::downgrade(&frame));
+    Some(frame)
+}
+
+/// ### drop the cached pages of a file after it's written
+/// the mappings made later read the new content, the existing ones keep the old pages
+pub fn invalidate_pages(inode: &Arc<dyn VfsInode>) {
+    let key = inode_key(inode);
+    PAGE_CACHE.lock().retain(|&(inode, _), _| inode != key);
+}
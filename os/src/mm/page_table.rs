@@ -18,6 +18,7 @@ bitflags! {
         const G = 1 << 5; // global
         const A = 1 << 6; // accessed
         const D = 1 << 7; // dirty
+        const COW = 1 << 8; // copy on write (RSW, reserved for software)
     }
 }
 
@@ -74,6 +75,10 @@ impl PTE {
     pub fn is_user(&self) -> bool {
         (self.get_flags() & PTEFlags::U) != PTEFlags::empty()
     }
+
+    pub fn is_cow(&self) -> bool {
+        (self.get_flags() & PTEFlags::COW) != PTEFlags::empty()
+    }
 }
 
 pub struct PageTable {
@@ -83,17 +88,19 @@ pub struct PageTable {
 }
 
 impl PageTable {
-    pub fn new() -> Self {
-        let rt_ft = frame_alloc().unwrap();
+    /// None : no frame left for the root
+    pub fn new() -> Option<Self> {
+        let rt_ft = frame_alloc()?;
         let rt_frame = rt_ft.0;
         let mut srcs = Vec::new();
         srcs.push(rt_ft);
-        PageTable {
+        Some(PageTable {
             entry: rt_frame,
             pt_frames: srcs,
-        }
+        })
     }
 
+    /// None : no frame left for the page table
     pub fn find_create_pte_mut(&mut self, vp: Page) -> Option<&'static mut PTE> {
         let mut cur_frame = self.entry.clone();
         let indices = vp.get_indices();
@@ -104,7 +111,7 @@ impl PageTable {
             // not valid, create a new page table
             // debug!("cur frame: {:X?}", cur_frame);
             if !pte.is_valid() {
-                let new_frame = frame_alloc().unwrap();
+                let new_frame = frame_alloc()?;
                 // debug!("new frame: {:X?}", new_frame.0);
                 pte.map_frame(new_frame.0, PTEFlags::V);
                 // debug!("valid after map : {:?}", pte.is_valid());
@@ -137,7 +144,7 @@ impl PageTable {
 
     pub fn map_one(&mut self, vp: Page, pp: Frame, flags: PTEFlags) -> Result<(), ()> {
         // debug!("map_one: {:x?} {:x?} {:x?}", vp, pp, flags);
-        let pte = self.find_create_pte_mut(vp).unwrap();
+        let pte = self.find_create_pte_mut(vp).ok_or(())?;
         if pte.is_valid() {
             Err(())
         } else {
@@ -149,7 +156,32 @@ impl PageTable {
     pub fn unmap_one(&mut self, vp: Page) -> Result<(), ()> {
         let pte = self.find_pte_mut(vp).unwrap();
         if pte.is_valid() {
-            pte.clear_flags(PTEFlags::V);
+            // no stale flags left for the next mapping
+            pte.clear_flags(PTEFlags::all());
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    /// replace both the frame and the flags of a valid pte
+    pub fn remap_one(&mut self, vp: Page, pp: Frame, flags: PTEFlags) -> Result<(), ()> {
+        let pte = self.find_pte_mut(vp).ok_or(())?;
+        if pte.is_valid() {
+            pte.clear_flags(PTEFlags::all());
+            pte.map_frame(pp, flags | PTEFlags::V);
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    /// the page becomes read-only, and the first write to it will be trapped
+    pub fn mark_cow(&mut self, vp: Page) -> Result<(), ()> {
+        let pte = self.find_pte_mut(vp).ok_or(())?;
+        if pte.is_valid() {
+            pte.clear_flags(PTEFlags::W);
+            pte.set_flags(PTEFlags::COW);
             Ok(())
         } else {
             Err(())
@@ -171,18 +203,25 @@ impl PageTable {
         }
     }
 
+    /// ### a string ending with '\0' in user space
+    /// None : not mapped, or not UTF-8
     pub fn translate_str(&self, start: *const u8) -> Option<String> {
-        let mut ret = String::new();
-        let mut ptr = start;
-        loop {
-            let va = VirtAddr(ptr as usize);
+        String::from_utf8(self.translate_c_bytes(start, usize::MAX)?).ok()
+    }
+
+    /// ### the bytes of a string ending with '\0' in user space, without the '\0'
+    /// - at most `max_len` bytes are read, a longer string is cut there
+    /// - None : not mapped
+    pub fn translate_c_bytes(&self, start: *const u8, max_len: usize) -> Option<Vec<u8>> {
+        let mut ret = Vec::new();
+        let mut va = VirtAddr(start as usize);
+        while ret.len() < max_len {
             let pp = self.translate_vp(va.floor_page())?;
-            let c = pp.get_bytes_array_mut()[va.get_offset()];
-            match c {
+            match pp.get_bytes_array_mut()[va.get_offset()] {
                 0 => break,
-                _ => ret.push(c as char),
+                c => ret.push(c),
             }
-            ptr = unsafe { ptr.add(1) };
+            va.0 += 1;
         }
         Some(ret)
     }
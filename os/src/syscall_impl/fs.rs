@@ -1,40 +1,249 @@
-use crate::{console::console_getc, task::PROCESSOR};
+use core::mem::size_of;
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    fs::{
+        copy_to_bufs, find_inode, make_dir, make_pipe, mount, open_file, path_string, remove_inode,
+        resolve_path, umount, OpenFlags,
+    },
+    mm::{PageTable, VirtAddr},
+    task::PROCESSOR,
+};
+
+use super::errno::{EBADF, EFAULT, EINVAL, ENAMETOOLONG, ENOTDIR, ERANGE};
 
 // buf pointer is an address in user space
 // but now satp is kernel satp
 // translate the user addr into kernel addr...
 pub fn sys_write_impl(fd: usize, buf: *const u8, len: usize) -> isize {
-    if fd != 1 {
-        panic!("Not support for other file descriptor!");
-    }
-    if let Some(bufs) = PROCESSOR.translate_cur_byte_buffer_mut(buf as usize, len) {
-        for slice in bufs {
-            print!("{}", core::str::from_utf8(slice).unwrap());
+    let current = PROCESSOR.current_process().expect("no current process!");
+    let file = match current.get_file(fd) {
+        Some(file) if file.writable() => file,
+        _ => return -EBADF,
+    };
+    if let Some(bufs) = PROCESSOR.translate_cur_byte_buffer(buf as usize, len) {
+        match file.write(bufs) {
+            Ok(len) => len as isize,
+            Err(errno) => -errno,
         }
-        len as isize
     } else {
-        panic!("Can not find the write buffer's physical address");
+        -EFAULT
     }
 }
 
 pub fn sys_read_impl(fd: usize, buf: *mut u8, len: usize) -> isize {
-    if fd != 0 {
-        panic!("Not support for other file descriptor!");
+    let current = PROCESSOR.current_process().expect("no current process!");
+    let file = match current.get_file(fd) {
+        Some(file) if file.readable() => file,
+        _ => return -EBADF,
+    };
+    // write to the current task's address space
+    if let Some(bufs) = PROCESSOR.translate_cur_byte_buffer_mut(buf as usize, len) {
+        file.read(bufs) as isize
+    } else {
+        -EFAULT
+    }
+}
+
+const AT_FDCWD: isize = -100;
+const AT_REMOVEDIR: u32 = 0x200;
+
+/// ### the path string in user space
+/// return (the directory a relative path starts from, path)
+/// - `AT_FDCWD` : the cwd
+/// - otherwise : the opened directory of `dirfd`
+fn translate_at_path(dirfd: isize, path: *const u8) -> Result<(String, String), isize> {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    let path = current.translate_str(path).ok_or(EFAULT)?;
+    // kfc-fs can't store a longer name, and no other filesystem needs one
+    if path
+        .split('/')
+        .any(|name| name.len() > kfc_fs::NAME_LENGTH_LIMIT)
+    {
+        return Err(ENAMETOOLONG);
     }
+    if path.starts_with('/') || dirfd == AT_FDCWD {
+        return Ok((current.get_cwd(), path));
+    }
+    let file = current.get_file(dirfd as usize).ok_or(EBADF)?;
+    match file.dentry() {
+        Some(dentry) if dentry.inode.is_dir() => Ok((dentry.path, path)),
+        _ => Err(ENOTDIR),
+    }
+}
+
+/// ### openat : a relative path starts from the cwd or `dirfd`
+/// - `mode` is not supported
+pub fn sys_openat_impl(dirfd: isize, path: *const u8, flags: u32, _mode: u32) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    let (base, path) = match translate_at_path(dirfd, path) {
+        Ok(at_path) => at_path,
+        Err(errno) => return -errno,
+    };
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return -EINVAL,
+    };
+    match open_file(&base, &path, flags) {
+        Ok(file) => current.alloc_fd(file) as isize,
+        Err(errno) => -errno,
+    }
+}
 
-    assert_eq!(len, 1, "Only support read one byte");
+/// ### mkdirat
+/// - `mode` is not supported
+pub fn sys_mkdirat_impl(dirfd: isize, path: *const u8, _mode: u32) -> isize {
+    let (base, path) = match translate_at_path(dirfd, path) {
+        Ok(at_path) => at_path,
+        Err(errno) => return -errno,
+    };
+    match make_dir(&base, &path) {
+        Ok(_) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// ### unlinkat : remove an empty directory with `AT_REMOVEDIR`
+/// the opened files keep working, the inode is freed after they are all closed
+pub fn sys_unlinkat_impl(dirfd: isize, path: *const u8, flags: u32) -> isize {
+    let (base, path) = match translate_at_path(dirfd, path) {
+        Ok(at_path) => at_path,
+        Err(errno) => return -errno,
+    };
+    if flags & !AT_REMOVEDIR != 0 {
+        return -EINVAL;
+    }
+    match remove_inode(&base, &path, flags & AT_REMOVEDIR != 0) {
+        Ok(_) => 0,
+        Err(errno) => -errno,
+    }
+}
 
-    let mut c: u8;
-    loop {
-        c = console_getc();
-        if c != 0 {
-            break;
+/// ### getcwd : the cwd ending with '\0' is copied into `buf`
+/// return the length including '\0'
+pub fn sys_getcwd_impl(buf: *mut u8, size: usize) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    let mut cwd = current.get_cwd().into_bytes();
+    cwd.push(0);
+    if cwd.len() > size {
+        return -ERANGE;
+    }
+    match PROCESSOR.translate_cur_byte_buffer_mut(buf as usize, cwd.len()) {
+        Some(bufs) => copy_to_bufs(bufs, &cwd) as isize,
+        None => -EFAULT,
+    }
+}
+
+pub fn sys_chdir_impl(path: *const u8) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    let path = match current.translate_str(path) {
+        Some(path) => path,
+        None => return -EFAULT,
+    };
+    let cwd = current.get_cwd();
+    match find_inode(&cwd, &path) {
+        Ok(inode) if inode.is_dir() => {
+            current.set_cwd(path_string(&resolve_path(&cwd, &path)));
+            0
         }
-        // maybe we can yield here...
+        Ok(_) => -ENOTDIR,
+        Err(errno) => -errno,
     }
-    // write to the current task's address space
-    if let Some(mut bufs) = PROCESSOR.translate_cur_byte_buffer_mut(buf as usize, len) {
-        unsafe { bufs[0].as_mut_ptr().write_volatile(c) }
+}
+
+/// ### getdents64 : read `linux_dirent64`s from an opened directory
+/// - return 0 at the end of the directory
+/// - -EINVAL : the buffer is too small for the next entry
+pub fn sys_getdents64_impl(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    let file = match current.get_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    let bufs = match PROCESSOR.translate_cur_byte_buffer_mut(buf as usize, len) {
+        Some(bufs) => bufs,
+        None => return -EFAULT,
+    };
+    match file.getdents(bufs) {
+        Ok(n) => n as isize,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_close_impl(fd: usize) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    match current.close_fd(fd) {
+        Ok(_) => 0,
+        Err(_) => -EBADF,
+    }
+}
+
+/// the new fd is the lowest one available
+pub fn sys_dup_impl(fd: usize) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    match current.get_file(fd) {
+        Some(file) => current.alloc_fd(file) as isize,
+        None => -EBADF,
+    }
+}
+
+/// `pipe_ptr` points to `int[2]` : [read_end, write_end]
+pub fn sys_pipe_impl(pipe_ptr: usize) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    if !current.fault_in_writable(VirtAddr(pipe_ptr), 2 * size_of::<i32>()) {
+        return -EFAULT;
+    }
+    let light_pt = PageTable {
+        entry: current.pt_entry(),
+        pt_frames: Vec::new(),
+    };
+    let (read_ptr, write_ptr) = match (
+        light_pt.get_mut::<i32>(pipe_ptr),
+        light_pt.get_mut::<i32>(pipe_ptr + size_of::<i32>()),
+    ) {
+        (Some(read_ptr), Some(write_ptr)) => (read_ptr, write_ptr),
+        _ => return -EFAULT,
+    };
+
+    let (read_end, write_end) = make_pipe();
+    *read_ptr = current.alloc_fd(read_end) as i32;
+    *write_ptr = current.alloc_fd(write_end) as i32;
+    0
+}
+
+/// ### mount : `source`, `flags` and `data` are ignored
+/// `fs_type` : kfcfs, tmpfs, devfs or procfs
+pub fn sys_mount_impl(
+    _source: *const u8,
+    target: *const u8,
+    fs_type: *const u8,
+    _flags: usize,
+    _data: *const u8,
+) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    let (target, fs_type) = match (
+        current.translate_str(target),
+        current.translate_str(fs_type),
+    ) {
+        (Some(target), Some(fs_type)) => (target, fs_type),
+        _ => return -EFAULT,
+    };
+    match mount(&current.get_cwd(), &target, &fs_type) {
+        Ok(_) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// ### umount2 : `flags` are ignored
+pub fn sys_umount2_impl(target: *const u8, _flags: usize) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    let target = match current.translate_str(target) {
+        Some(target) => target,
+        None => return -EFAULT,
+    };
+    match umount(&current.get_cwd(), &target) {
+        Ok(_) => 0,
+        Err(errno) => -errno,
     }
-    1
 }
@@ -0,0 +1,122 @@
+use crate::{
+    config::PAGE_BYTES,
+    fs::InodeType,
+    mm::{MapPerm, MappedFile, VirtAddr},
+    syscall_impl::errno::{EACCES, EBADF, EINVAL, ENODEV, ENOMEM},
+    task::PROCESSOR,
+};
+
+const PROT_READ: usize = 1 << 0;
+const PROT_WRITE: usize = 1 << 1;
+const PROT_EXEC: usize = 1 << 2;
+
+const MAP_SHARED: usize = 1 << 0;
+const MAP_PRIVATE: usize = 1 << 1;
+const MAP_FIXED: usize = 1 << 4;
+const MAP_ANONYMOUS: usize = 1 << 5;
+
+/// PROT_* --> MapPerm, None if unknown bits are set
+fn prot_to_map_perm(prot: usize) -> Option<MapPerm> {
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+        return None;
+    }
+    let mut map_perm = MapPerm::U;
+    if prot & PROT_READ != 0 {
+        map_perm |= MapPerm::R;
+    }
+    // W without R is reserved in the pte, writable pages are also readable as on Linux
+    if prot & PROT_WRITE != 0 {
+        map_perm |= MapPerm::R | MapPerm::W;
+    }
+    if prot & PROT_EXEC != 0 {
+        map_perm |= MapPerm::X;
+    }
+    Some(map_perm)
+}
+
+/// brk(0) or an invalid break : just get the current program break
+pub fn sys_brk_impl(addr: usize) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    current.set_brk(VirtAddr(addr)).0 as isize
+}
+
+/// ### only private mappings are supported, anonymous or of a file
+/// - a file mapping shares the frames in the page cache, and copies a page when writing it
+/// - EINVAL : bad `prot` or `flags`, `len` is 0, `addr` or `offset` is not aligned
+/// - EBADF : `fd` is not opened, EACCES : not opened for reading
+/// - ENODEV : not a regular file
+/// - ENOMEM : no space for the mapping
+pub fn sys_mmap_impl(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    let map_perm = match prot_to_map_perm(prot) {
+        Some(map_perm) => map_perm,
+        None => return -EINVAL,
+    };
+    if flags & MAP_SHARED != 0 || flags & MAP_PRIVATE == 0 {
+        return -EINVAL;
+    }
+    if len == 0 || addr % PAGE_BYTES != 0 {
+        return -EINVAL;
+    }
+
+    let current = PROCESSOR.current_process().expect("no current process!");
+    let file = if flags & MAP_ANONYMOUS != 0 {
+        None
+    } else {
+        if offset % PAGE_BYTES != 0 {
+            return -EINVAL;
+        }
+        let file = match current.get_file(fd) {
+            Some(file) => file,
+            None => return -EBADF,
+        };
+        if !file.readable() {
+            return -EACCES;
+        }
+        match file.dentry() {
+            Some(dentry) if dentry.inode.inode_type() == InodeType::File => Some(MappedFile {
+                inode: dentry.inode,
+                offset,
+            }),
+            _ => return -ENODEV,
+        }
+    };
+    match current.mmap(VirtAddr(addr), len, map_perm, flags & MAP_FIXED != 0, file) {
+        Ok(start) => start.0 as isize,
+        Err(_) => -ENOMEM,
+    }
+}
+
+/// EINVAL : `len` is 0, `addr` is not aligned, or the range is not in user space
+pub fn sys_munmap_impl(addr: usize, len: usize) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    match current.munmap(VirtAddr(addr), len) {
+        Ok(_) => 0,
+        Err(_) => -EINVAL,
+    }
+}
+
+/// ### change the permission of the pages in [addr, addr + len)
+/// - EINVAL : bad `prot` or `addr` is not aligned
+/// - ENOMEM : some pages in the range are not mapped
+pub fn sys_mprotect_impl(addr: usize, len: usize, prot: usize) -> isize {
+    let map_perm = match prot_to_map_perm(prot) {
+        Some(map_perm) => map_perm,
+        None => return -EINVAL,
+    };
+    if addr % PAGE_BYTES != 0 {
+        return -EINVAL;
+    }
+
+    let current = PROCESSOR.current_process().expect("no current process!");
+    match current.mprotect(VirtAddr(addr), len, map_perm) {
+        Ok(_) => 0,
+        Err(_) => -ENOMEM,
+    }
+}
@@ -1,35 +1,106 @@
 use self::{
-    fs::{sys_read_impl, sys_write_impl},
+    fs::{
+        sys_chdir_impl, sys_close_impl, sys_dup_impl, sys_getcwd_impl, sys_getdents64_impl,
+        sys_mkdirat_impl, sys_mount_impl, sys_openat_impl, sys_pipe_impl, sys_read_impl,
+        sys_umount2_impl, sys_unlinkat_impl, sys_write_impl,
+    },
+    mm::{sys_brk_impl, sys_mmap_impl, sys_mprotect_impl, sys_munmap_impl},
     process::{
-        sys_exec_impl, sys_exit_impl, sys_fork_impl, sys_getpid_impl, sys_times_impl,
-        sys_waitpid_impl, sys_yield_impl,
+        sys_execve_impl, sys_exit_impl, sys_fork_impl, sys_get_priority_impl, sys_getpid_impl,
+        sys_gettid_impl, sys_nanosleep_impl, sys_set_priority_impl, sys_thread_create_impl,
+        sys_times_impl, sys_waitpid_impl, sys_waittid_impl, sys_yield_impl,
     },
+    sync::sys_futex_impl,
 };
 
+pub mod errno;
 mod fs;
+mod mm;
 mod process;
+mod sync;
 
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_MKDIRAT: usize = 34;
+const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_UMOUNT2: usize = 39;
+const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_OPENAT: usize = 56;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS64: usize = 61;
 const SYSCALL_WRITE: usize = 64;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_NANOSLEEP: usize = 101;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_SETPRIORITY: usize = 140;
+const SYSCALL_GETPRIORITY: usize = 141;
 const SYSCALL_TIMES: usize = 153;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_FORK: usize = 220;
-const SYSCALL_EXEC: usize = 221;
+const SYSCALL_EXECVE: usize = 221;
 const SYSCALL_WAITPID: usize = 260;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETTID: usize = 178;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_THREAD_CREATE: usize = 1000;
+const SYSCALL_WAITTID: usize = 1002;
 
-pub fn syscall_dispathcer(id: usize, args: [usize; 3]) -> isize {
+pub fn syscall_dispathcer(id: usize, args: [usize; 6]) -> isize {
     match id {
+        SYSCALL_GETCWD => sys_getcwd_impl(args[0] as *mut u8, args[1]),
+        SYSCALL_DUP => sys_dup_impl(args[0]),
+        SYSCALL_MKDIRAT => sys_mkdirat_impl(args[0] as isize, args[1] as *const u8, args[2] as u32),
+        SYSCALL_UNLINKAT => {
+            sys_unlinkat_impl(args[0] as isize, args[1] as *const u8, args[2] as u32)
+        }
+        SYSCALL_UMOUNT2 => sys_umount2_impl(args[0] as *const u8, args[1]),
+        SYSCALL_MOUNT => sys_mount_impl(
+            args[0] as *const u8,
+            args[1] as *const u8,
+            args[2] as *const u8,
+            args[3],
+            args[4] as *const u8,
+        ),
+        SYSCALL_CHDIR => sys_chdir_impl(args[0] as *const u8),
+        SYSCALL_OPENAT => sys_openat_impl(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as u32,
+            args[3] as u32,
+        ),
+        SYSCALL_CLOSE => sys_close_impl(args[0]),
+        SYSCALL_PIPE => sys_pipe_impl(args[0]),
+        SYSCALL_GETDENTS64 => sys_getdents64_impl(args[0], args[1] as *mut u8, args[2]),
         SYSCALL_WRITE => sys_write_impl(args[0], args[1] as *const u8, args[2]),
         SYSCALL_EXIT => sys_exit_impl(args[0] as i32),
+        SYSCALL_FUTEX => sys_futex_impl(args[0], args[1], args[2], args[3]),
+        SYSCALL_NANOSLEEP => sys_nanosleep_impl(args[0]),
         SYSCALL_YIELD => sys_yield_impl(),
+        SYSCALL_SETPRIORITY => sys_set_priority_impl(args[0] as isize),
+        SYSCALL_GETPRIORITY => sys_get_priority_impl(),
         SYSCALL_TIMES => sys_times_impl(),
         SYSCALL_READ => sys_read_impl(args[0], args[1] as *mut u8, args[2]),
         SYSCALL_FORK => sys_fork_impl(),
-        SYSCALL_EXEC => sys_exec_impl(args[0] as *const u8),
+        SYSCALL_EXECVE => sys_execve_impl(
+            args[0] as *const u8,
+            args[1] as *const usize,
+            args[2] as *const usize,
+        ),
         SYSCALL_WAITPID => sys_waitpid_impl(args[0] as isize, args[1]),
         SYSCALL_GETPID => sys_getpid_impl(),
+        SYSCALL_GETTID => sys_gettid_impl(),
+        SYSCALL_BRK => sys_brk_impl(args[0]),
+        SYSCALL_MUNMAP => sys_munmap_impl(args[0], args[1]),
+        SYSCALL_MMAP => sys_mmap_impl(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_MPROTECT => sys_mprotect_impl(args[0], args[1], args[2]),
+        SYSCALL_THREAD_CREATE => sys_thread_create_impl(args[0], args[1]),
+        SYSCALL_WAITTID => sys_waittid_impl(args[0]),
         _ => panic!("unsupported syscall id: {}", id),
     }
 }
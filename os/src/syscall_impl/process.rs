@@ -1,9 +1,19 @@
-use alloc::{sync::Arc, vec::Vec};
+use core::mem::size_of;
+
+use alloc::vec::Vec;
 
 use crate::{
-    kfc_sbi::timer::{get_time, CLOCK_FREQ, MSEC_PER_SEC},
-    mm::PageTable,
-    task::{exit_cur_run_next, suspend_cur_run_next, PROCESSOR, TASK_MANAGER},
+    kfc_sbi::timer::{get_time, CLOCK_FREQ, MSEC_PER_SEC, NSEC_PER_SEC},
+    mm::{PageTable, VirtAddr},
+    syscall_impl::errno::{EFAULT, EINTR, EINVAL},
+    task::{
+        exit_cur_run_next,
+        scheduler::{MAX_PRIORITY, MIN_PRIORITY},
+        suspend_cur_run_next,
+        task_struct::TaskStruct,
+        wait_queue::sleep_until,
+        PROCESSOR, TASK_MANAGER,
+    },
 };
 
 pub fn sys_exit_impl(exit_code: i32) -> ! {
@@ -12,9 +22,10 @@ pub fn sys_exit_impl(exit_code: i32) -> ! {
             .current_arc()
             .expect("exit implementation : no current task!");
         info!(
-            "In process \"{}\", pid = {}, exit with code {}",
-            cur_task.get_name(),
-            *cur_task.pid,
+            "In process \"{}\", pid = {}, tid = {}, exit with code {}",
+            cur_task.process.get_name(),
+            *cur_task.process.pid,
+            cur_task.tid,
             exit_code
         );
         // --------cur task drop here--------
@@ -35,45 +46,227 @@ pub fn sys_yield_impl() -> isize {
     0
 }
 
+/// ### set the priority of the current task, kept over exec
+/// - return the priority
+/// - EINVAL : less than `MIN_PRIORITY` or greater than `MAX_PRIORITY`
+pub fn sys_set_priority_impl(priority: isize) -> isize {
+    if priority < MIN_PRIORITY as isize || priority > MAX_PRIORITY as isize {
+        return -EINVAL;
+    }
+    let current = PROCESSOR.current_arc().expect("no current task!");
+    let mut sched_entity = current.sched_entity();
+    sched_entity.priority = priority as usize;
+    current.set_sched_entity(sched_entity);
+    priority
+}
+
+pub fn sys_get_priority_impl() -> isize {
+    let current = PROCESSOR.current_arc().expect("no current task!");
+    current.sched_entity().priority as isize
+}
+
+/// ### the calling thread is copied as the main thread of the child
+/// ENOMEM : no memory for the child
 pub fn sys_fork_impl() -> isize {
     let current = PROCESSOR.current_arc().expect("no current task!");
-    let forked = Arc::new(current.fork_task_struct());
-    forked.set_parent(Arc::downgrade(&current));
-    current.add_child(forked.clone());
+    let forked = match current.process.fork_process(&current) {
+        Ok(forked) => forked,
+        Err(errno) => return -errno,
+    };
 
     let pid = *forked.pid as isize;
 
     // the forked process will no get system call value, it's return value is stored in a0
-    let trap_ctx = forked.trap_ctx_mut();
+    let main_thread = forked.main_thread().expect("no main thread");
+    let trap_ctx = main_thread.trap_ctx_mut();
     trap_ctx.x[10] = 0;
 
-    TASK_MANAGER.add_ready_task(forked);
+    TASK_MANAGER.add_ready_task(main_thread);
     pid
 }
 
 pub fn sys_getpid_impl() -> isize {
-    *PROCESSOR.current_arc().expect("no current task!").pid as isize
+    *PROCESSOR
+        .current_process()
+        .expect("no current process!")
+        .pid as isize
 }
 
-// the pointer is in user's address space
-pub fn sys_exec_impl(path: *const u8) -> isize {
+/// ### a new thread running `entry(arg)` on its own user stack
+/// - return the tid
+/// - EAGAIN : too many threads, ENOMEM : no space for the user stack
+pub fn sys_thread_create_impl(entry: usize, arg: usize) -> isize {
     let current = PROCESSOR.current_arc().expect("no current task!");
-    if let Some(res) = current.exec_from_elf(path) {
-        res
-    } else {
-        -1
+    match TaskStruct::new_user_thread(&current.process, current.sched_entity(), entry, arg) {
+        Ok(thread) => {
+            let tid = thread.tid as isize;
+            TASK_MANAGER.add_ready_task(thread);
+            tid
+        }
+        Err(errno) => -errno,
     }
 }
 
-/// NO child process has the given pid -> -1
-/// The required pid is still running -> -2
-pub fn sys_waitpid_impl(pid: isize, exit_code_ptr: usize) -> isize {
+pub fn sys_gettid_impl() -> isize {
+    PROCESSOR.current_arc().expect("no current task!").tid as isize
+}
+
+/// ### wait for a thread in the same process to exit, blocking until it exits
+/// - return its exit code
+/// - -1 : no such thread, the main thread, itself, or the process is exiting
+pub fn sys_waittid_impl(tid: usize) -> isize {
     let current = PROCESSOR.current_arc().expect("no current task!");
+    let process = &current.process;
+    loop {
+        let mut ret = -2;
+        // woken up when a thread exits
+        let alive = process.wait_thread.wait_killable_if(|| {
+            ret = process.waittid(tid, current.tid);
+            ret == -2
+        });
+        if ret != -2 {
+            return ret;
+        }
+        // killed before back to user space
+        if !alive {
+            return -1;
+        }
+    }
+}
+
+/// ### execve : the pointers are in user's address space
+/// - `argv` and `envp` are NULL-terminated arrays, a NULL array is empty
+/// - return argc to the new program, with argv in a1
+/// - EFAULT : a bad pointer, or a string not in UTF-8
+/// - E2BIG : the strings and pointers of argv and envp take more than `ARG_MAX` bytes
+pub fn sys_execve_impl(path: *const u8, argv: *const usize, envp: *const usize) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    let path = match current.translate_str(path) {
+        Some(path) => path,
+        None => return -EFAULT,
+    };
+    let mut arg_len = 0;
+    let argv = match current.translate_str_array(argv, &mut arg_len) {
+        Ok(argv) => argv,
+        Err(errno) => return -errno,
+    };
+    let envp = match current.translate_str_array(envp, &mut arg_len) {
+        Ok(envp) => envp,
+        Err(errno) => return -errno,
+    };
+    match current.exec_from_elf(&path, argv, envp) {
+        Ok(argc) => argc as isize,
+        Err(errno) => -errno,
+    }
+}
+
+/// ### wait for a child to exit, blocking until one of the required children exits
+/// - NO child process has the given pid -> -1
+/// - -EFAULT : `exit_code_ptr` is not writable
+pub fn sys_waitpid_impl(pid: isize, exit_code_ptr: usize) -> isize {
+    let current = PROCESSOR.current_process().expect("no current process!");
+    if !current.fault_in_writable(VirtAddr(exit_code_ptr), size_of::<i32>()) {
+        return -EFAULT;
+    }
+
+    let mut exit_code = 0;
+    let ret = loop {
+        let mut ret = -2;
+        // woken up when a child exits
+        let alive = current.wait_child.wait_killable_if(|| {
+            ret = current.wait_process(pid, &mut exit_code);
+            ret == -2
+        });
+        if ret != -2 {
+            break ret;
+        }
+        // killed before back to user space
+        if !alive {
+            return -EINTR;
+        }
+    };
+    if ret < 0 {
+        return ret;
+    }
+
+    // translated again : another thread may have unmapped the page while blocked
+    if !current.fault_in_writable(VirtAddr(exit_code_ptr), size_of::<i32>()) {
+        return -EFAULT;
+    }
     let light_pt = PageTable {
         entry: current.pt_entry(),
         pt_frames: Vec::new(),
     };
+    let bufs = match light_pt.translate_byte_buffer_mut(exit_code_ptr, size_of::<i32>()) {
+        Some(bufs) => bufs,
+        None => return -EFAULT,
+    };
+    let mut bytes = exit_code.to_ne_bytes().into_iter();
+    for slice in bufs {
+        for byte in slice.iter_mut() {
+            *byte = bytes.next().unwrap();
+        }
+    }
+    ret
+}
+
+/// the time for nanosleep, the same layout as in libc
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TimeSpec {
+    pub tv_sec: usize,
+    pub tv_nsec: usize,
+}
+
+/// ### read the TimeSpec at `req` as clock ticks
+/// - a time too long to count saturates to `usize::MAX`, never reached
+/// - EFAULT : `req` is not mapped
+/// - EINVAL : tv_sec is negative, or tv_nsec is out of [0, 999999999]
+pub(super) fn read_time_spec_ticks(req: usize) -> Result<usize, isize> {
+    let mut bytes = [0u8; size_of::<TimeSpec>()];
+    if req.checked_add(bytes.len()).is_none() {
+        return Err(EFAULT);
+    }
+    {
+        let current = PROCESSOR.current_process().expect("no current process!");
+        current.fault_in_range(VirtAddr(req), bytes.len());
+        let light_pt = PageTable {
+            entry: current.pt_entry(),
+            pt_frames: Vec::new(),
+        };
+        // copied by bytes : `req` may be misaligned, or cross a page boundary
+        let bufs = light_pt
+            .translate_byte_buffer_mut(req, bytes.len())
+            .ok_or(EFAULT)?;
+        let mut copied = 0;
+        for slice in bufs {
+            bytes[copied..copied + slice.len()].copy_from_slice(slice);
+            copied += slice.len();
+        }
+    }
+    let time_spec = unsafe { (bytes.as_ptr() as *const TimeSpec).read_unaligned() };
+    if (time_spec.tv_sec as isize) < 0 || time_spec.tv_nsec >= NSEC_PER_SEC {
+        return Err(EINVAL);
+    }
+    Ok(time_spec
+        .tv_sec
+        .checked_mul(CLOCK_FREQ)
+        .and_then(|ticks| ticks.checked_add(time_spec.tv_nsec / (NSEC_PER_SEC / CLOCK_FREQ)))
+        .unwrap_or(usize::MAX))
+}
 
-    let exit_code_mut: &mut i32 = light_pt.get_mut(exit_code_ptr).expect("invalid pointer!");
-    current.wait_task(pid, exit_code_mut)
+/// ### block for the time in `req`, the remaining time is not reported
+/// - EFAULT : `req` is not mapped
+/// - EINVAL : tv_sec is negative, or tv_nsec is out of [0, 999999999]
+pub fn sys_nanosleep_impl(req: usize) -> isize {
+    match read_time_spec_ticks(req) {
+        Ok(ticks) => {
+            if sleep_until(get_time().saturating_add(ticks)) {
+                0
+            } else {
+                -EINTR
+            }
+        }
+        Err(errno) => -errno,
+    }
 }
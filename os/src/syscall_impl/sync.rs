@@ -0,0 +1,80 @@
+use core::{
+    mem::size_of,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use alloc::vec::Vec;
+
+use crate::{
+    kfc_sbi::timer::get_time,
+    mm::{PageTable, VirtAddr},
+    syscall_impl::errno::{EAGAIN, EFAULT, EINTR, EINVAL, ETIMEDOUT},
+    task::{
+        futex::{futex_wait, futex_wake, FutexWaitError},
+        PROCESSOR,
+    },
+};
+
+use super::process::read_time_spec_ticks;
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+/// the futex is not shared with other processes, accepted and ignored
+const FUTEX_PRIVATE_FLAG: usize = 128;
+
+/// ### the futex word at `uaddr` in the kernel space
+/// the page is faulted in for write, so a COW page gets its own frame first
+/// - EINVAL : not 4-byte aligned
+/// - EFAULT : not mapped
+fn translate_futex_word(uaddr: usize) -> Result<&'static AtomicU32, isize> {
+    if uaddr % size_of::<u32>() != 0 {
+        return Err(EINVAL);
+    }
+    let current = PROCESSOR.current_process().expect("no current process!");
+    current.fault_in_range(VirtAddr(uaddr), size_of::<u32>());
+    let light_pt = PageTable {
+        entry: current.pt_entry(),
+        pt_frames: Vec::new(),
+    };
+    match light_pt.get_mut::<AtomicU32>(uaddr) {
+        Some(word) => Ok(word),
+        None => Err(EFAULT),
+    }
+}
+
+/// ### futex : wait on or wake up the threads waiting on the word at `uaddr`
+/// - FUTEX_WAIT : block if the word is still `val`, until woken up or `timeout`
+///   (a relative TimeSpec, NULL : no timeout), return 0;
+///   EAGAIN : the word is not `val`, ETIMEDOUT : timed out
+/// - FUTEX_WAKE : wake up at most `val` waiters, return the number woken up
+/// - EINVAL : unknown op or a misaligned `uaddr`, EFAULT : a bad pointer
+pub fn sys_futex_impl(uaddr: usize, op: usize, val: usize, timeout: usize) -> isize {
+    let word = match translate_futex_word(uaddr) {
+        Ok(word) => word,
+        Err(errno) => return -errno,
+    };
+    // the physical address of the word, the same for every thread mapping it
+    let key = word as *const AtomicU32 as usize;
+    match op & !FUTEX_PRIVATE_FLAG {
+        FUTEX_WAIT => {
+            let deadline = if timeout == 0 {
+                None
+            } else {
+                match read_time_spec_ticks(timeout) {
+                    Ok(ticks) => Some(get_time().saturating_add(ticks)),
+                    Err(errno) => return -errno,
+                }
+            };
+            let expected = val as u32;
+            match futex_wait(key, || word.load(Ordering::SeqCst) == expected, deadline) {
+                Ok(()) => 0,
+                Err(FutexWaitError::ValueChanged) => -EAGAIN,
+                Err(FutexWaitError::TimedOut) => -ETIMEDOUT,
+                // never seen by the user, the thread exits before back to user space
+                Err(FutexWaitError::Killed) => -EINTR,
+            }
+        }
+        FUTEX_WAKE => futex_wake(key, val) as isize,
+        _ => -EINVAL,
+    }
+}
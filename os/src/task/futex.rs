@@ -0,0 +1,107 @@
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+use lazy_static::lazy_static;
+
+use crate::kfc_util::up_safe_cell::UPSafeCell;
+
+use super::{
+    block_cur_run_next,
+    task_struct::TaskStruct,
+    wait_queue::{add_sleeping, cancel_sleeping, wake_up},
+    PROCESSOR,
+};
+
+lazy_static! {
+    /// ### the threads waiting on the futex words
+    /// keyed by the physical address of the word, so the processes sharing the frame
+    /// meet at the same key, and the empty queues are removed
+    static ref FUTEX_QUEUES: UPSafeCell<BTreeMap<usize, VecDeque<Arc<TaskStruct>>>> =
+        UPSafeCell::new("FUTEX_QUEUES", BTreeMap::new());
+}
+
+pub enum FutexWaitError {
+    /// the word has changed before blocking
+    ValueChanged,
+    TimedOut,
+    /// the process has exited
+    Killed,
+}
+
+/// ### block the current thread on the futex `key`, if `cond` holds
+/// - `cond` is checked with the queues locked, so a waker changing the word
+///   before `futex_wake` is not missed
+/// - deadline : in clock ticks, None : no timeout
+pub fn futex_wait(
+    key: usize,
+    cond: impl FnOnce() -> bool,
+    deadline: Option<usize>,
+) -> Result<(), FutexWaitError> {
+    let mut queues = FUTEX_QUEUES.exclusive_access();
+    if !cond() {
+        return Err(FutexWaitError::ValueChanged);
+    }
+    let blocked = block_cur_run_next(true, move |task| {
+        if let Some(deadline) = deadline {
+            add_sleeping(deadline, task.clone());
+        }
+        queues.entry(key).or_default().push_back(task);
+    });
+    if !blocked {
+        return Err(FutexWaitError::Killed);
+    }
+
+    // woken up by `futex_wake`, the timer or `kill`, leave no stale entry behind
+    let task = PROCESSOR.current_arc().expect("no current task");
+    let mut queues = FUTEX_QUEUES.exclusive_access();
+    let still_queued = match queues.get_mut(&key) {
+        Some(queue) => match queue.iter().position(|other| Arc::ptr_eq(other, &task)) {
+            Some(index) => {
+                queue.remove(index);
+                if queue.is_empty() {
+                    queues.remove(&key);
+                }
+                true
+            }
+            None => false,
+        },
+        None => false,
+    };
+    drop(queues);
+    if deadline.is_some() {
+        cancel_sleeping(&task);
+    }
+    if task.is_killed() {
+        Err(FutexWaitError::Killed)
+    } else if still_queued {
+        Err(FutexWaitError::TimedOut)
+    } else {
+        Ok(())
+    }
+}
+
+/// wake up at most `count` threads on the futex `key`, return the number woken up
+pub fn futex_wake(key: usize, count: usize) -> usize {
+    let mut queues = FUTEX_QUEUES.exclusive_access();
+    let queue = match queues.get_mut(&key) {
+        Some(queue) => queue,
+        None => return 0,
+    };
+    let mut woken = 0;
+    while woken < count {
+        match queue.pop_front() {
+            // the timed out ones are skipped
+            Some(task) => {
+                if wake_up(task) {
+                    woken += 1;
+                }
+            }
+            None => break,
+        }
+    }
+    if queue.is_empty() {
+        queues.remove(&key);
+    }
+    woken
+}
@@ -0,0 +1,58 @@
+use alloc::vec::Vec;
+
+use crate::kfc_util::spin_lock::SpinLock;
+use core::ops::Deref;
+
+/// ### ids from `start`, the freed ones are reused first
+/// for pids, kernel stacks and the tids in a process
+pub struct RecycleAllocator {
+    x: usize,
+    y: Vec<usize>,
+}
+
+impl RecycleAllocator {
+    pub const fn new(start: usize) -> Self {
+        Self {
+            x: start,
+            y: Vec::new(),
+        }
+    }
+
+    pub fn alloc(&mut self) -> usize {
+        if self.y.is_empty() {
+            self.x += 1;
+            self.x - 1
+        } else {
+            self.y.pop().unwrap()
+        }
+    }
+
+    pub fn dealloc(&mut self, t: usize) {
+        assert!(t < self.x, "invalid id to dealloc");
+        self.y.push(t);
+    }
+}
+
+// init process has pid 1
+static PID_ALLOCATOR: SpinLock<RecycleAllocator> =
+    SpinLock::new("PID_ALLOCATOR", RecycleAllocator::new(1));
+
+pub struct PIDTracker(usize);
+
+pub fn pid_alloc() -> PIDTracker {
+    PIDTracker(PID_ALLOCATOR.lock().alloc())
+}
+
+impl Deref for PIDTracker {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for PIDTracker {
+    fn drop(&mut self) {
+        PID_ALLOCATOR.lock().dealloc(self.0);
+    }
+}
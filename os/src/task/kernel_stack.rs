@@ -1,46 +1,55 @@
-use alloc::collections::BTreeMap;
-
 use crate::{
     config::{KERNEL_STACK_SIZE, PAGE_BYTES, TRAMPOLINE_VIRT_ADDR},
+    kfc_util::spin_lock::SpinLock,
     mm::{MapArea, MapPerm, VPRange, VirtAddr, KERNEL_SPACE},
 };
 
+use super::id_allocator::RecycleAllocator;
+
+// the places of the kernel stacks, one for each thread
+static KSTACK_ALLOCATOR: SpinLock<RecycleAllocator> =
+    SpinLock::new("KSTACK_ALLOCATOR", RecycleAllocator::new(0));
+
 // a guard page between each task's kernel stack
-fn kernel_stack_range(pid: usize) -> (VirtAddr, VirtAddr) {
+fn kernel_stack_range(id: usize) -> (VirtAddr, VirtAddr) {
     let space_size = VirtAddr(KERNEL_STACK_SIZE + PAGE_BYTES).ceil_page().0;
     (
-        VirtAddr(TRAMPOLINE_VIRT_ADDR.0 - (pid + 1) * space_size + PAGE_BYTES),
-        VirtAddr(TRAMPOLINE_VIRT_ADDR.0 - (pid + 1) * space_size + PAGE_BYTES + KERNEL_STACK_SIZE),
+        VirtAddr(TRAMPOLINE_VIRT_ADDR.0 - (id + 1) * space_size + PAGE_BYTES),
+        VirtAddr(TRAMPOLINE_VIRT_ADDR.0 - (id + 1) * space_size + PAGE_BYTES + KERNEL_STACK_SIZE),
     )
 }
 
 // hold by task_struct, alloc and dealloc in kernel space
 pub struct KernelStack {
-    pid: usize,
+    id: usize,
 }
 
 impl KernelStack {
     pub fn top_sp(&self) -> usize {
-        (kernel_stack_range(self.pid).1).0
+        (kernel_stack_range(self.id).1).0
     }
-    pub fn new(pid: usize) -> Self {
-        let range = kernel_stack_range(pid);
+    /// None : no frame left for the stack
+    pub fn new() -> Option<Self> {
+        let id = KSTACK_ALLOCATOR.lock().alloc();
+        let range = kernel_stack_range(id);
         let vp_range = VPRange::new(range.0, range.1);
-        let kernel_stack = MapArea::new(
-            vp_range,
-            crate::mm::MapType::Framed(BTreeMap::new()),
-            MapPerm::R | MapPerm::W,
-            None,
-        );
-        KERNEL_SPACE.add_kernel_stack(kernel_stack);
-        KernelStack { pid }
+        let mapped = match MapArea::new_framed(vp_range, MapPerm::R | MapPerm::W) {
+            Some(kernel_stack) => KERNEL_SPACE.add_kernel_stack(kernel_stack).is_ok(),
+            None => false,
+        };
+        if !mapped {
+            KSTACK_ALLOCATOR.lock().dealloc(id);
+            return None;
+        }
+        Some(KernelStack { id })
     }
 }
 
 impl Drop for KernelStack {
     fn drop(&mut self) {
-        let range = kernel_stack_range(self.pid);
+        let range = kernel_stack_range(self.id);
         let vp_range = VPRange::new(range.0, range.1);
         KERNEL_SPACE.remove_map_area(&vp_range);
+        KSTACK_ALLOCATOR.lock().dealloc(self.id);
     }
 }
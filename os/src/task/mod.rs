@@ -1,31 +1,62 @@
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use lazy_static::lazy_static;
 
 use crate::app_loader::get_app_names;
 
 use self::{
+    preempt::{clear_need_resched, preempt_disable, preempt_enable},
+    process_struct::ProcessStruct,
     processor::switch_to_idle,
     task_struct::{TaskStatus, TaskStruct},
 };
 
+pub mod futex;
+pub mod id_allocator;
 pub mod kernel_stack;
-pub mod pid_allocator;
+pub mod preempt;
+pub mod process_struct;
 pub mod processor;
+pub mod scheduler;
 pub mod switch;
 pub mod task_context;
 pub mod task_manager;
 pub mod task_struct;
+pub mod wait_queue;
 
 pub use processor::PROCESSOR;
 pub use task_manager::TASK_MANAGER;
 
 lazy_static! {
-    pub static ref INIT_PROC: Arc<TaskStruct> = Arc::new(TaskStruct::new_from_elf("initproc"));
+    pub static ref INIT_PROC: Arc<ProcessStruct> = ProcessStruct::new_from_elf("initproc");
 }
 
+/// ### the processes not reaped yet
+/// every process is a descendant of INIT_PROC
+pub fn all_processes() -> Vec<Arc<ProcessStruct>> {
+    let mut ret = Vec::new();
+    let mut stack = Vec::from([INIT_PROC.clone()]);
+    while let Some(process) = stack.pop() {
+        stack.extend(process.get_children());
+        ret.push(process);
+    }
+    ret.sort_by_key(|process| *process.pid);
+    ret
+}
+
+pub fn find_process(pid: usize) -> Option<Arc<ProcessStruct>> {
+    all_processes()
+        .into_iter()
+        .find(|process| *process.pid == pid)
+}
+
+// the current task is taken out by the idle control flow after switched out,
+// so it is not run on another hart before its context is saved
+// the preemption is disabled on the way, see `switch_to_idle`
+
 pub fn suspend_cur_run_next() {
+    preempt_disable();
     // suspend current task
-    let cur_task = PROCESSOR.take_out_current().expect("no current task");
+    let cur_task = PROCESSOR.current_arc().expect("no current task");
     let cur_task_ctx_ptr = cur_task.task_ctx_ptr();
     cur_task.mark_task_status(TaskStatus::Ready);
     TASK_MANAGER.add_ready_task(cur_task);
@@ -34,14 +65,49 @@ pub fn suspend_cur_run_next() {
     switch_to_idle(cur_task_ctx_ptr);
 }
 
+/// ### block the current task, `park` puts it where it will be woken up
+/// - the task is not in the task manager until woken up
+/// - killable : also woken up when its process exits, the caller should take the task
+///   out of where it is parked after woken up
+/// - false : killed, not blocked
+pub fn block_cur_run_next(killable: bool, park: impl FnOnce(Arc<TaskStruct>)) -> bool {
+    preempt_disable();
+    let cur_task = PROCESSOR.current_arc().expect("no current task");
+    let cur_task_ctx_ptr = cur_task.task_ctx_ptr();
+    if !cur_task.block(killable) {
+        // the locks held by `park` are released first
+        drop(park);
+        drop(cur_task);
+        preempt_enable();
+        return false;
+    }
+    park(cur_task);
+
+    // switch to idle
+    switch_to_idle(cur_task_ctx_ptr);
+    true
+}
+
+/// the current task has used up its time slice
+pub fn preempt_cur_run_next() {
+    // the locks released on the way are not safe points any more
+    clear_need_resched();
+    let cur_task = PROCESSOR.current_arc().expect("no current task");
+    let mut sched_entity = cur_task.sched_entity();
+    sched_entity.preempted = true;
+    cur_task.set_sched_entity(sched_entity);
+    drop(cur_task);
+    suspend_cur_run_next();
+}
+
 // if normal exit, exit_code = 0
 // else exit_code = -1
 pub fn exit_cur_run_next(exit_code: i32) {
-    // take out current task, so PROCESSOR.current will be None
-    let cur_task = PROCESSOR.take_out_current().expect("no current task");
+    preempt_disable();
+    let cur_task = PROCESSOR.current_arc().expect("no current task");
     let cur_task_ctx_ptr = cur_task.task_ctx_ptr();
-    cur_task.exit_task(exit_code);
-    // should manually drop cur_task
+    cur_task.exit_thread(exit_code);
+    // should manually drop cur_task, the last one may be dropped by the idle control flow
     drop(cur_task);
     switch_to_idle(cur_task_ctx_ptr)
 }
@@ -49,10 +115,10 @@ pub fn exit_cur_run_next(exit_code: i32) {
 pub fn task_init() {
     let name_list = get_app_names();
     info!("====================The Supported Apps====================");
-    for &name in name_list.iter() {
+    for name in name_list.iter() {
         info!("{}", name);
     }
     info!("==========================================================");
 
-    TASK_MANAGER.add_ready_task(INIT_PROC.clone());
+    TASK_MANAGER.add_ready_task(INIT_PROC.main_thread().expect("no main thread"));
 }
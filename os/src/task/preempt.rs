@@ -0,0 +1,65 @@
+//! ### kernel preemption
+//! - a timer interrupt taken in the kernel only marks the hart, the task is switched out
+//!   at the next safe point : releasing the last lock, or `cond_resched`
+//! - a task holding a spin lock is not switched out, as another task may spin on it,
+//!   nor holding a `UPSafeCell`, which can't migrate to another hart
+//! - the locks of kfc-fs are `RawSpinLock`s, so are counted here as well;
+//!   a lock that doesn't disable preemption must never be spun on in the kernel
+//! - the idle control flow holds a count itself, and is never preempted
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::{config::MAX_HARTS, kfc_sbi::hart_id};
+
+use super::preempt_cur_run_next;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const COUNT_INIT: AtomicUsize = AtomicUsize::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const FLAG_INIT: AtomicBool = AtomicBool::new(false);
+
+/// the locks held on each hart, and the preemption disabled explicitly
+static PREEMPT_COUNT: [AtomicUsize; MAX_HARTS] = [COUNT_INIT; MAX_HARTS];
+/// the time slice of the task on each hart is used up
+static NEED_RESCHED: [AtomicBool; MAX_HARTS] = [FLAG_INIT; MAX_HARTS];
+
+pub fn preempt_count() -> usize {
+    PREEMPT_COUNT[hart_id()].load(Ordering::Relaxed)
+}
+
+pub fn preempt_disable() {
+    PREEMPT_COUNT[hart_id()].fetch_add(1, Ordering::Relaxed);
+}
+
+/// a safe point if the count drops to 0
+pub fn preempt_enable() {
+    preempt_enable_no_resched();
+    cond_resched();
+}
+
+/// for the scheduler, which is about to switch anyway
+pub fn preempt_enable_no_resched() {
+    let old = PREEMPT_COUNT[hart_id()].fetch_sub(1, Ordering::Relaxed);
+    assert!(old > 0, "preempt_enable without preempt_disable");
+}
+
+/// called by the timer interrupt handler
+pub fn set_need_resched() {
+    NEED_RESCHED[hart_id()].store(true, Ordering::Relaxed);
+}
+
+/// called by the hart switching to a new task, or switching out the current one
+pub fn clear_need_resched() {
+    NEED_RESCHED[hart_id()].store(false, Ordering::Relaxed);
+}
+
+/// ### a safe point for the long kernel paths
+/// switch out the current task if its time slice is used up and no lock is held
+pub fn cond_resched() {
+    let hart = hart_id();
+    if PREEMPT_COUNT[hart].load(Ordering::Relaxed) == 0
+        && NEED_RESCHED[hart].load(Ordering::Relaxed)
+    {
+        preempt_cur_run_next();
+    }
+}
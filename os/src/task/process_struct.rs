@@ -0,0 +1,686 @@
+use core::mem::size_of;
+
+use alloc::{
+    format,
+    string::String,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    app_loader::{load_app_by_name, parse_shebang, MAX_SHEBANG_DEPTH},
+    config::{ARG_MAX, MAX_THREADS, TRAP_CTX_BOTTOM_VIRT_ADDR, TRAP_CTX_VIRT_ADDR},
+    fs::{open_file, File, OpenFlags},
+    kfc_util::up_safe_cell::UPSafeCell,
+    mm::{
+        memory_set::MemorySet, Frame, MapPerm, MappedFile, PageFaultError, PageTable, VPRange,
+        VirtAddr, KERNEL_SPACE,
+    },
+    syscall_impl::errno::{E2BIG, EAGAIN, EBUSY, EFAULT, ELOOP, ENOENT, ENOEXEC, ENOMEM},
+    trap::{trap_context::TrapContext, trap_handler},
+};
+
+use super::{
+    id_allocator::{pid_alloc, PIDTracker, RecycleAllocator},
+    scheduler::SchedEntity,
+    task_struct::{trap_ctx_virt_addr, TaskStatus, TaskStruct},
+    wait_queue::WaitQueue,
+    INIT_PROC, TASK_MANAGER,
+};
+
+pub struct ProcessStructInner {
+    pub name: String,
+    pub user_space: MemorySet,
+    /// exited with its main thread, waiting to be reaped
+    pub is_zombie: bool,
+    pub exit_code: i32,
+    pub parent: Option<Weak<ProcessStruct>>,
+    pub children: Vec<Arc<ProcessStruct>>,
+    /// index is the file descriptor, None : closed
+    pub fd_table: Vec<Option<Arc<dyn File>>>,
+    /// the absolute path of the current working directory
+    pub cwd: String,
+    /// index is the tid, None : reaped
+    pub threads: Vec<Option<Arc<TaskStruct>>>,
+    pub tid_allocator: RecycleAllocator,
+    /// the threads not exited yet, the last one frees the user space
+    pub alive_threads: usize,
+}
+
+/// ### the resources shared by the threads
+/// the threads refer to their process, the process refers to its threads until they are
+/// reaped by `waittid`, or all of them have exited
+pub struct ProcessStruct {
+    // read only fields
+    pub pid: PIDTracker,
+    /// the threads waiting for the children to exit
+    pub wait_child: WaitQueue,
+    /// the threads waiting for the other threads to exit
+    pub wait_thread: WaitQueue,
+    inner: UPSafeCell<ProcessStructInner>,
+}
+
+impl ProcessStruct {
+    pub fn get_name(&self) -> String {
+        self.inner.exclusive_access().name.clone()
+    }
+
+    pub fn get_cwd(&self) -> String {
+        self.inner.exclusive_access().cwd.clone()
+    }
+
+    /// `cwd` should be an absolute path to a directory
+    pub fn set_cwd(&self, cwd: String) {
+        self.inner.exclusive_access().cwd = cwd;
+    }
+
+    pub fn get_exit_code(&self) -> i32 {
+        self.inner.exclusive_access().exit_code
+    }
+
+    pub fn is_zombie(&self) -> bool {
+        self.inner.exclusive_access().is_zombie
+    }
+
+    /// Zombie after exited, otherwise the status of the main thread
+    pub fn status(&self) -> TaskStatus {
+        let inner = self.inner.exclusive_access();
+        match inner.threads.first() {
+            Some(Some(main_thread)) if !inner.is_zombie => main_thread.task_status(),
+            // the main thread is exiting
+            _ => TaskStatus::Zombie,
+        }
+    }
+
+    /// the main thread, None : reaped
+    pub fn main_thread(&self) -> Option<Arc<TaskStruct>> {
+        self.inner
+            .exclusive_access()
+            .threads
+            .first()
+            .cloned()
+            .flatten()
+    }
+
+    /// None : the parent has exited (only for INIT_PROC)
+    pub fn get_parent(&self) -> Option<Arc<ProcessStruct>> {
+        self.inner
+            .exclusive_access()
+            .parent
+            .as_ref()
+            .and_then(|parent| parent.upgrade())
+    }
+
+    pub fn get_children(&self) -> Vec<Arc<ProcessStruct>> {
+        self.inner.exclusive_access().children.clone()
+    }
+
+    /// (range, permission, map type) of each area in the user space
+    pub fn map_areas_info(&self) -> Vec<(VPRange, MapPerm, String)> {
+        self.inner
+            .exclusive_access()
+            .user_space
+            .map_areas
+            .iter()
+            .map(|area| match area.file {
+                Some(_) => (area.vp_range, area.map_perm, String::from("File")),
+                None => (area.vp_range, area.map_perm, format!("{:?}", area.map_type)),
+            })
+            .collect()
+    }
+
+    /// (resident pages, pages shared with other address spaces) of the user
+    pub fn resident_pages(&self) -> (usize, usize) {
+        self.inner.exclusive_access().user_space.resident_pages()
+    }
+
+    pub fn pt_entry(&self) -> Frame {
+        self.inner.exclusive_access().user_space.page_table.entry
+    }
+
+    pub fn add_child(&self, child: Arc<ProcessStruct>) {
+        self.inner.exclusive_access().children.push(child);
+    }
+
+    pub fn set_parent(&self, parent: Weak<ProcessStruct>) {
+        self.inner.exclusive_access().parent = Some(parent);
+    }
+
+    pub fn handle_page_fault(&self, va: VirtAddr, is_write: bool) -> Result<(), PageFaultError> {
+        self.inner
+            .exclusive_access()
+            .user_space
+            .handle_page_fault(va, is_write)
+    }
+
+    /// Linux's brk : return the program break after moving
+    pub fn set_brk(&self, new_brk: VirtAddr) -> VirtAddr {
+        let mut inner = self.inner.exclusive_access();
+        // on failure, the program break stays the same
+        let _ = inner.user_space.set_brk(new_brk);
+        inner.user_space.brk
+    }
+
+    /// a string ending with '\0' in user space
+    pub fn translate_str(&self, ptr: *const u8) -> Option<String> {
+        self.inner
+            .exclusive_access()
+            .user_space
+            .page_table
+            .translate_str(ptr)
+    }
+
+    /// ### a NULL-terminated array of string pointers in user space
+    /// - a NULL array is empty
+    /// - `arg_len` : the bytes of the strings and pointers copied, added up over the arrays
+    /// - Err : EFAULT : not mapped, or not UTF-8, E2BIG : `arg_len` exceeds `ARG_MAX`
+    pub fn translate_str_array(
+        &self,
+        ptr: *const usize,
+        arg_len: &mut usize,
+    ) -> Result<Vec<String>, isize> {
+        let mut ret = Vec::new();
+        if ptr.is_null() {
+            return Ok(ret);
+        }
+        let mut inner = self.inner.exclusive_access();
+        loop {
+            let va = VirtAddr(ptr as usize + ret.len() * size_of::<usize>());
+            inner
+                .user_space
+                .fault_in_range(va, size_of::<usize>())
+                .map_err(|_| ENOMEM)?;
+            let str_ptr = *inner
+                .user_space
+                .page_table
+                .get_mut::<usize>(va.0)
+                .ok_or(EFAULT)?;
+            if str_ptr == 0 {
+                return Ok(ret);
+            }
+            // the string is cut just beyond the limit, no more is copied
+            *arg_len += size_of::<usize>();
+            let bytes = inner
+                .user_space
+                .page_table
+                .translate_c_bytes(str_ptr as *const u8, ARG_MAX.saturating_sub(*arg_len))
+                .ok_or(EFAULT)?;
+            *arg_len += bytes.len() + 1;
+            if *arg_len > ARG_MAX {
+                return Err(E2BIG);
+            }
+            ret.push(String::from_utf8(bytes).map_err(|_| EFAULT)?);
+        }
+    }
+
+    /// the pages not faulted in (no frame left) are left to the translation, which will fail
+    pub fn fault_in_range(&self, start: VirtAddr, len: usize) {
+        let _ = self
+            .inner
+            .exclusive_access()
+            .user_space
+            .fault_in_range(start, len);
+    }
+
+    /// ### fault in the user pages for the kernel to write
+    /// false : some pages are not mapped writable
+    pub fn fault_in_writable(&self, start: VirtAddr, len: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        inner.user_space.fault_in_range(start, len).is_ok()
+            && inner.user_space.is_user_writable(start, len)
+    }
+
+    pub fn mmap(
+        &self,
+        addr: VirtAddr,
+        len: usize,
+        map_perm: MapPerm,
+        fixed: bool,
+        file: Option<MappedFile>,
+    ) -> Result<VirtAddr, ()> {
+        self.inner
+            .exclusive_access()
+            .user_space
+            .mmap(addr, len, map_perm, fixed, file)
+    }
+
+    pub fn munmap(&self, addr: VirtAddr, len: usize) -> Result<(), ()> {
+        self.inner.exclusive_access().user_space.munmap(addr, len)
+    }
+
+    pub fn mprotect(&self, addr: VirtAddr, len: usize, map_perm: MapPerm) -> Result<(), ()> {
+        self.inner
+            .exclusive_access()
+            .user_space
+            .mprotect(addr, len, map_perm)
+    }
+}
+
+impl ProcessStruct {
+    fn new(pid: PIDTracker, inner: ProcessStructInner) -> Arc<Self> {
+        Arc::new(ProcessStruct {
+            pid,
+            wait_child: WaitQueue::new(),
+            wait_thread: WaitQueue::new(),
+            inner: UPSafeCell::new("process", inner),
+        })
+    }
+
+    pub fn new_from_elf(name: &str) -> Arc<Self> {
+        let pid = pid_alloc();
+        let elf_data = load_app_by_name("/", name);
+        let (mut user_space, entry_addr, user_sp, auxv) =
+            MemorySet::new_from_elf(&elf_data.expect("failed to load app"), None)
+                .expect("invalid elf file");
+        let argv = [String::from(name)];
+        let (user_sp, argv_ptr) = user_space
+            .init_user_stack(user_sp, &argv, &[], &auxv)
+            .expect("failed to init the user stack");
+
+        let console: Arc<dyn File> =
+            open_file("/", "/dev/console", OpenFlags::RDWR).expect("no console");
+
+        let process = Self::new(
+            pid,
+            ProcessStructInner {
+                name: name.into(),
+                user_space,
+                is_zombie: false,
+                exit_code: 0,
+                parent: None,
+                children: Vec::new(),
+                // stdin, stdout, stderr
+                fd_table: vec![Some(console); 3],
+                cwd: "/".into(),
+                threads: Vec::new(),
+                tid_allocator: RecycleAllocator::new(0),
+                alive_threads: 0,
+            },
+        );
+        let main_thread =
+            TaskStruct::new(&process, SchedEntity::new()).expect("failed to create main thread");
+
+        // initialize the trap context
+        let trap_ctx = main_thread.trap_ctx_mut();
+        *trap_ctx = TrapContext::init_trap_ctx(
+            entry_addr,
+            user_sp,
+            PageTable::satp_token(KERNEL_SPACE.pt_entry()),
+            main_thread.kernel_stack.top_sp(),
+            trap_handler as usize,
+        );
+        trap_ctx.x[10] = argv.len();
+        trap_ctx.x[11] = argv_ptr;
+
+        process
+    }
+
+    /// ### fork the process, `thread` is copied as the main thread of the child
+    /// - the trap context : as the same as the context "when `thread` traps in",
+    ///   but the kernel_sp should change
+    /// - the other threads are not copied, their user stacks are kept in the user space
+    /// - Err : errno (positive), ENOMEM : no memory for the child
+    pub fn fork_process(self: &Arc<Self>, thread: &TaskStruct) -> Result<Arc<Self>, isize> {
+        let mut user_space = self
+            .inner
+            .exclusive_access()
+            .user_space
+            .fork_memory_set()
+            .ok_or(ENOMEM)?;
+        let pid = pid_alloc();
+        user_space.relase_area(&VPRange::new(TRAP_CTX_BOTTOM_VIRT_ADDR, TRAP_CTX_VIRT_ADDR));
+
+        // the files are shared with the parent
+        let fd_table = self.inner.exclusive_access().fd_table.clone();
+
+        let child = Self::new(
+            pid,
+            ProcessStructInner {
+                name: self.get_name(),
+                user_space,
+                is_zombie: false,
+                exit_code: 0,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                fd_table,
+                cwd: self.get_cwd(),
+                threads: Vec::new(),
+                tid_allocator: RecycleAllocator::new(0),
+                alive_threads: 0,
+            },
+        );
+        let main_thread = TaskStruct::new(&child, thread.sched_entity())?;
+
+        // only kernel sp changes
+        let trap_ctx = main_thread.trap_ctx_mut();
+        *trap_ctx = *thread.trap_ctx_mut();
+        trap_ctx.kernel_sp = main_thread.kernel_stack.top_sp();
+
+        self.add_child(child.clone());
+        Ok(child)
+    }
+
+    /// ### replace the user space with the program at `path`
+    /// a script starting with "#!" is run by its interpreter
+    /// - return argc, for a0 of the new program
+    /// - Err : errno (positive), the process is not changed
+    /// - EBUSY : other threads are running, only a single-threaded process can exec
+    pub fn exec_from_elf(
+        &self,
+        path: &str,
+        mut argv: Vec<String>,
+        envp: Vec<String>,
+    ) -> Result<usize, isize> {
+        // the only thread alive is the main thread, as the process exits with it
+        if self.inner.exclusive_access().alive_threads > 1 {
+            return Err(EBUSY);
+        }
+
+        // pid : no change
+        let cwd = self.get_cwd();
+        let mut elf_data = load_app_by_name(&cwd, path).ok_or(ENOENT)?;
+
+        // a script : run its interpreter with argv [interpreter, argument, script, argv[1..]]
+        let mut script = String::from(path);
+        for depth in 0.. {
+            let (interp, arg) = match parse_shebang(&elf_data) {
+                Some(shebang) => shebang?,
+                None => break,
+            };
+            if depth == MAX_SHEBANG_DEPTH {
+                return Err(ELOOP);
+            }
+            let mut interp_argv = vec![interp.clone()];
+            interp_argv.extend(arg);
+            interp_argv.push(script);
+            interp_argv.extend(argv.into_iter().skip(1));
+            argv = interp_argv;
+            elf_data = load_app_by_name(&cwd, &interp).ok_or(ENOENT)?;
+            script = interp;
+        }
+
+        let interp_data = match MemorySet::elf_interp(&elf_data).map_err(|_| ENOEXEC)? {
+            Some(interp) => Some(load_app_by_name(&cwd, &interp).ok_or(ENOENT)?),
+            None => None,
+        };
+
+        // build the new user space before replacing the old one
+        let (mut user_space, entry_addr, user_sp, auxv) =
+            MemorySet::new_from_elf(&elf_data, interp_data.as_deref())?;
+        let (user_sp, argv_ptr) = user_space.init_user_stack(user_sp, &argv, &envp, &auxv)?;
+
+        // update name
+        self.inner.exclusive_access().name = path.into();
+
+        // the exited threads not reaped are gone with the old program
+        {
+            let mut inner = self.inner.exclusive_access();
+            let zombies: Vec<_> = inner.threads.drain(1..).flatten().collect();
+            for task in zombies.iter() {
+                inner.tid_allocator.dealloc(task.tid);
+            }
+            // the threads refer to this process, drop them without it locked
+            drop(inner);
+            drop(zombies);
+        }
+
+        // replace the old user_space
+        self.inner.exclusive_access().user_space = user_space;
+
+        // get new trap context frame
+        let trap_ctx_frame = self
+            .inner
+            .exclusive_access()
+            .user_space
+            .page_table
+            .translate_vp(TRAP_CTX_VIRT_ADDR.floor_page())
+            .unwrap();
+        // !!! update the trap context frame !!!
+        let main_thread = self.main_thread().expect("no main thread");
+        main_thread.set_trap_ctx_frame(trap_ctx_frame);
+
+        // kernel stack and task context don't need to be updated
+
+        // trap context : set to entry point of the new code
+        let trap_ctx = trap_ctx_frame.get_mut::<TrapContext>();
+        *trap_ctx = TrapContext::init_trap_ctx(
+            entry_addr,
+            user_sp,
+            PageTable::satp_token(KERNEL_SPACE.pt_entry()),
+            main_thread.kernel_stack.top_sp(),
+            trap_handler as usize,
+        );
+        // a0 is set to the return value of execve : argc
+        trap_ctx.x[11] = argv_ptr;
+
+        Ok(argv.len())
+    }
+}
+
+// thread related functions
+impl ProcessStruct {
+    /// ### a tid, with the trap context and user stack of the thread
+    /// - the main thread uses those built with the user space
+    /// - return (tid, trap context frame, user stack bottom)
+    /// - Err : EAGAIN : too many threads or exiting, ENOMEM : no space for the user stack
+    pub fn alloc_thread(&self) -> Result<(usize, Frame, Option<VirtAddr>), isize> {
+        let mut inner = self.inner.exclusive_access();
+        // exiting, a new thread would not be killed
+        if inner.is_zombie {
+            return Err(EAGAIN);
+        }
+        let tid = inner.tid_allocator.alloc();
+        if tid >= MAX_THREADS {
+            inner.tid_allocator.dealloc(tid);
+            return Err(EAGAIN);
+        }
+        let trap_ctx_va = trap_ctx_virt_addr(tid);
+        if tid == 0 {
+            let trap_ctx_frame = inner
+                .user_space
+                .page_table
+                .translate_vp(trap_ctx_va.floor_page())
+                .unwrap();
+            return Ok((tid, trap_ctx_frame, None));
+        }
+        match inner.user_space.alloc_thread_res(trap_ctx_va) {
+            Ok((trap_ctx_frame, ustack_bottom)) => Ok((tid, trap_ctx_frame, Some(ustack_bottom))),
+            Err(_) => {
+                inner.tid_allocator.dealloc(tid);
+                Err(ENOMEM)
+            }
+        }
+    }
+
+    /// the thread is running, at its tid
+    pub fn add_thread(&self, task: Arc<TaskStruct>) {
+        let mut inner = self.inner.exclusive_access();
+        let tid = task.tid;
+        if inner.threads.len() <= tid {
+            inner.threads.resize(tid + 1, None);
+        }
+        inner.threads[tid] = Some(task);
+        inner.alive_threads += 1;
+    }
+
+    /// ### release the user stack and trap context of an exited thread
+    /// the last thread frees the user space, and the threads of the process
+    pub fn thread_exited(&self, task: &TaskStruct) {
+        let mut inner = self.inner.exclusive_access();
+        if let Some(ustack_bottom) = task.ustack_bottom {
+            inner
+                .user_space
+                .dealloc_thread_res(task.trap_ctx_va(), ustack_bottom);
+        }
+        inner.alive_threads -= 1;
+        if inner.alive_threads == 0 {
+            inner.user_space.free_resources();
+            let threads = core::mem::take(&mut inner.threads);
+            // the parent has been woken up by `exit_process` before the last thread exits
+            let parent = match inner.is_zombie {
+                true => inner.parent.as_ref().and_then(|parent| parent.upgrade()),
+                false => None,
+            };
+            // the threads refer to this process, drop them without it locked
+            drop(inner);
+            drop(threads);
+            if let Some(parent) = parent {
+                parent.wait_child.wake_all();
+            }
+        }
+    }
+
+    /// ### kill the process, as by a fatal error in one of its threads
+    /// the main thread exits the process at its next trap, see `kill_threads`
+    pub fn kill(&self) {
+        self.kill_threads(usize::MAX);
+    }
+
+    /// ### the process has exited, end the other threads
+    /// they exit at their next trap, the blocked ones are woken up now
+    fn kill_threads(&self, cur_tid: usize) {
+        let threads: Vec<_> = self
+            .inner
+            .exclusive_access()
+            .threads
+            .iter()
+            .flatten()
+            .filter(|task| task.tid != cur_tid)
+            .cloned()
+            .collect();
+        for task in threads {
+            if task.kill() {
+                TASK_MANAGER.add_ready_task(task);
+            }
+        }
+    }
+
+    /// exited, and all the threads have exited, then the user space is freed
+    pub fn can_reap(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        inner.is_zombie && inner.alive_threads == 0
+    }
+
+    /// ### reap an exited thread
+    /// - return its exit code, -1 : no such thread, the main thread or itself,
+    ///   -2 : the thread is still running
+    pub fn waittid(&self, tid: usize, cur_tid: usize) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        let task = match inner.threads.get(tid) {
+            Some(Some(task)) if tid != 0 && tid != cur_tid => task.clone(),
+            _ => return -1,
+        };
+        if task.task_status() != TaskStatus::Zombie {
+            return -2;
+        }
+        inner.threads[tid] = None;
+        inner.tid_allocator.dealloc(tid);
+
+        // ----------------- task dropped here -----------------
+        // or by the idle control flow of the hart it exited on, after switched out
+        task.get_exit_code() as isize
+    }
+}
+
+// system call related functions
+impl ProcessStruct {
+    /// ### the process exits with its main thread
+    /// the user space is freed by the last thread exiting
+    pub fn exit_process(&self, exit_code: i32) {
+        let mut inner = self.inner.exclusive_access();
+
+        // store the exit code
+        inner.exit_code = exit_code;
+
+        // free the resources
+        inner.fd_table.clear();
+
+        let children = core::mem::take(&mut inner.children);
+        let parent = inner.parent.as_ref().and_then(|parent| parent.upgrade());
+        // no other process is locked with this one, the parent may be locking its children
+        drop(inner);
+
+        // move a the child process to INIT_PROC
+        for child in children.iter() {
+            child.set_parent(Arc::downgrade(&INIT_PROC));
+            INIT_PROC.add_child(child.clone());
+        }
+        if !children.is_empty() {
+            INIT_PROC.wait_child.wake_all();
+        }
+
+        // change the status, then the parent may be waiting for this process
+        self.inner.exclusive_access().is_zombie = true;
+        self.kill_threads(0);
+        self.wait_thread.wake_all();
+        if let Some(parent) = parent {
+            parent.wait_child.wake_all();
+        }
+    }
+
+    pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        let inner = self.inner.exclusive_access();
+        inner.fd_table.get(fd).cloned().flatten()
+    }
+
+    /// put the file at the lowest available fd
+    pub fn alloc_fd(&self, file: Arc<dyn File>) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        if let Some(fd) = inner.fd_table.iter().position(|f| f.is_none()) {
+            inner.fd_table[fd] = Some(file);
+            fd
+        } else {
+            inner.fd_table.push(Some(file));
+            inner.fd_table.len() - 1
+        }
+    }
+
+    /// Err : the fd is not opened
+    pub fn close_fd(&self, fd: usize) -> Result<(), ()> {
+        let mut inner = self.inner.exclusive_access();
+        // the file is dropped here if no one else refers to it
+        inner
+            .fd_table
+            .get_mut(fd)
+            .and_then(|f| f.take())
+            .map(|_| ())
+            .ok_or(())
+    }
+
+    /// ### reap a zombie child
+    /// - return its pid, -1 : no such child, -2 : the child is still running
+    pub fn wait_process(&self, pid: isize, exit_code_mut: &mut i32) -> isize {
+        let mut inner = self.inner.exclusive_access();
+
+        // no required pid found
+        if inner
+            .children
+            .iter()
+            .find(|&ch| pid == -1 || *ch.pid == pid as usize)
+            .is_none()
+        {
+            return -1;
+        }
+
+        let zombie = inner
+            .children
+            .iter()
+            .position(|ch| (pid == -1 || *ch.pid == pid as usize) && ch.can_reap());
+
+        if let Some(zom_idx) = zombie {
+            let ch = inner.children.remove(zom_idx);
+            let pid = *ch.pid;
+
+            *exit_code_mut = ch.get_exit_code();
+
+            // ----------------- ch dropped here -----------------
+            // or by the idle control flow its last thread exited on
+
+            pid as isize
+        } else {
+            // still running
+            return -2;
+        }
+    }
+}
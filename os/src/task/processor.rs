@@ -1,15 +1,29 @@
+use core::{arch::asm, hint::spin_loop, ops::Deref, sync::atomic::Ordering};
+
 use alloc::{sync::Arc, vec::Vec};
+use riscv::register::sstatus;
 
 use crate::{
-    kfc_sbi::sbi_shutdown, kfc_util::up_safe_cell::UPSafeCell, mm::PageTable,
+    config::MAX_HARTS,
+    console::console_poll,
+    fs::{diskfs::DISK_FS, vfs::FileSystem},
+    kfc_sbi::{hart_id, sbi_shutdown, timer::set_next_trigger},
+    kfc_util::up_safe_cell::UPSafeCell,
+    mm::{PageTable, VirtAddr},
     trap::trap_context::TrapContext,
 };
 
+#[cfg(feature = "lock_debug")]
+use crate::kfc_util::lock_debug;
+
 use super::{
+    preempt::{clear_need_resched, preempt_count, preempt_disable, preempt_enable_no_resched},
+    process_struct::ProcessStruct,
     switch::__switch,
     task_context::TaskContext,
     task_struct::{TaskStatus, TaskStruct},
-    TASK_MANAGER,
+    wait_queue::wake_expired,
+    INIT_PROC, TASK_MANAGER,
 };
 
 pub struct ProcessorInner {
@@ -28,12 +42,34 @@ pub struct Processor {
     inner: UPSafeCell<ProcessorInner>,
 }
 
-pub static PROCESSOR: Processor = Processor {
-    inner: UPSafeCell::new(ProcessorInner {
-        current: None,
-        idle_task_ctx: TaskContext::empty(),
-    }),
-};
+impl Processor {
+    const fn new() -> Self {
+        Self {
+            inner: UPSafeCell::new(
+                "processor",
+                ProcessorInner {
+                    current: None,
+                    idle_task_ctx: TaskContext::empty(),
+                },
+            ),
+        }
+    }
+}
+
+/// ### a `Processor` for each hart
+/// dereferenced to the one of the current hart
+pub struct Processors([Processor; MAX_HARTS]);
+
+impl Deref for Processors {
+    type Target = Processor;
+    fn deref(&self) -> &Self::Target {
+        &self.0[hart_id()]
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const PROCESSOR_INIT: Processor = Processor::new();
+pub static PROCESSOR: Processors = Processors([PROCESSOR_INIT; MAX_HARTS]);
 
 impl Processor {
     fn idle_task_ctx_ptr(&self) -> *mut TaskContext {
@@ -62,38 +98,108 @@ impl Processor {
         self.inner.exclusive_access().current.clone()
     }
 
+    /// the process of the current task
+    pub fn current_process(&self) -> Option<Arc<ProcessStruct>> {
+        self.inner
+            .exclusive_access()
+            .current
+            .as_ref()
+            .map(|task| task.process.clone())
+    }
+
     // virtual address may be continous, but physical address may not be
+    /// ### the user buffer for the kernel to write
+    /// None : not mapped, or read-only
     pub fn translate_cur_byte_buffer_mut(
         &self,
         buf: usize,
         len: usize,
     ) -> Option<Vec<&'static mut [u8]>> {
+        let current = self.current_process()?;
+        // the buffer may be not bounded yet, or shared copy-on-write
+        if !current.fault_in_writable(VirtAddr(buf), len) {
+            return None;
+        }
+        let light_pt = PageTable {
+            entry: current.pt_entry(),
+            pt_frames: Vec::new(),
+        };
+        light_pt.translate_byte_buffer_mut(buf, len)
+    }
+
+    /// ### the user buffer for the kernel to read, it should not be written
+    /// None : not mapped
+    pub fn translate_cur_byte_buffer(
+        &self,
+        buf: usize,
+        len: usize,
+    ) -> Option<Vec<&'static mut [u8]>> {
+        let current = self.current_process()?;
+        current.fault_in_range(VirtAddr(buf), len);
         let light_pt = PageTable {
-            entry: self.inner.exclusive_access().current.as_ref()?.pt_entry(),
+            entry: current.pt_entry(),
             pt_frames: Vec::new(),
         };
         light_pt.translate_byte_buffer_mut(buf, len)
     }
 }
 
+/// ### switch the current task out
+/// - the caller has disabled the preemption once, no lock can be held across the switch
+/// - the interrupts are disabled until switched back, the count then belongs to the hart
+///   the task is resumed on
 pub fn switch_to_idle(cur_task_ctx_ptr: *mut TaskContext) {
+    assert_eq!(preempt_count(), 1, "switching with a lock held");
+    let sie = sstatus::read().sie();
+    unsafe { sstatus::clear_sie() };
     let idle_ctx_ptr = PROCESSOR.idle_task_ctx_ptr();
+    // the sleeping locks held go with the task
+    #[cfg(feature = "lock_debug")]
+    let held = lock_debug::switch_out();
     __switch(cur_task_ctx_ptr, idle_ctx_ptr);
+    #[cfg(feature = "lock_debug")]
+    lock_debug::switch_in(held);
+    preempt_enable_no_resched();
+    if sie {
+        unsafe { sstatus::set_sie() };
+    }
 }
 
-// the idle control flow
+// the idle control flow, on each hart
 pub fn proc_schedule() {
+    // never preempted, the count is passed to the task switched to
+    preempt_disable();
     loop {
+        // the events the blocked tasks are waiting for
+        wake_expired();
+        console_poll();
+
         if let Some(next_task) = TASK_MANAGER.fetch_ready_task() {
+            // the hart switching it out may not have saved its context yet
+            while next_task.on_cpu.load(Ordering::Acquire) {
+                spin_loop();
+            }
+            next_task.on_cpu.store(true, Ordering::Relaxed);
+
             // only idle and next task, no current handled here
             let idle_ctx_ptr = PROCESSOR.idle_task_ctx_ptr();
             let next_ctx_ptr = next_task.task_ctx_ptr();
             next_task.mark_task_status(TaskStatus::Running);
+            set_next_trigger(TASK_MANAGER.time_slice(&next_task));
+            // the timer may have expired while idle, don't charge it to the next task
+            unsafe { asm!("csrw sip, {ssip}", ssip = in(reg) !2) };
+            clear_need_resched();
             PROCESSOR.set_current(next_task);
-            __switch(idle_ctx_ptr, next_ctx_ptr)
-        } else {
+            __switch(idle_ctx_ptr, next_ctx_ptr);
+
+            // the task has been put back to the task manager, a wait queue, or exited
+            // its context is saved now, so other harts can switch to it
+            let prev_task = PROCESSOR.take_out_current().expect("no task switched out");
+            prev_task.on_cpu.store(false, Ordering::Release);
+        } else if INIT_PROC.is_zombie() {
             info!("No process to schedule...");
             info!("Shutdown...");
+            DISK_FS.sync();
             sbi_shutdown(0);
         }
     }
@@ -0,0 +1,82 @@
+use alloc::{collections::VecDeque, sync::Arc};
+
+use crate::{
+    kfc_sbi::timer::{get_time, CLOCK_FREQ, INTERVAL},
+    task::task_struct::TaskStruct,
+};
+
+use super::Scheduler;
+
+const LEVEL_NUM: usize = 3;
+/// the time slice doubles for each level down : 10ms, 20ms, 40ms
+const BASE_TIME_SLICE: usize = INTERVAL;
+/// all the tasks go back to the highest level every second
+const BOOST_INTERVAL: usize = CLOCK_FREQ;
+
+/// ### multi-level feedback queue
+/// - the highest non-empty level runs first, round robin in each level
+/// - a task using up its time slice drops a level, one yielding or blocking stays
+/// - a periodic boost moves every task to the highest level, so CPU-bound tasks don't starve
+pub struct Mlfq {
+    queues: [VecDeque<Arc<TaskStruct>>; LEVEL_NUM],
+    /// the number of boosts
+    boost_epoch: usize,
+    last_boost: usize,
+}
+
+impl Mlfq {
+    pub fn new() -> Self {
+        Self {
+            queues: Default::default(),
+            boost_epoch: 0,
+            last_boost: get_time(),
+        }
+    }
+
+    fn level_time_slice(level: usize) -> usize {
+        BASE_TIME_SLICE << level
+    }
+
+    fn boost(&mut self) {
+        let now = get_time();
+        if now - self.last_boost < BOOST_INTERVAL {
+            return;
+        }
+        self.last_boost = now;
+        // the tasks not in the queues are reset when added
+        self.boost_epoch += 1;
+        for level in 1..LEVEL_NUM {
+            while let Some(task) = self.queues[level].pop_front() {
+                let mut sched_entity = task.sched_entity();
+                sched_entity.level = 0;
+                sched_entity.boost_epoch = self.boost_epoch;
+                task.set_sched_entity(sched_entity);
+                self.queues[0].push_back(task);
+            }
+        }
+    }
+}
+
+impl Scheduler for Mlfq {
+    fn add(&mut self, task: Arc<TaskStruct>) {
+        let mut sched_entity = task.sched_entity();
+        if sched_entity.boost_epoch != self.boost_epoch {
+            sched_entity.level = 0;
+            sched_entity.boost_epoch = self.boost_epoch;
+        } else if sched_entity.preempted {
+            sched_entity.level = (sched_entity.level + 1).min(LEVEL_NUM - 1);
+        }
+        sched_entity.preempted = false;
+        task.set_sched_entity(sched_entity);
+        self.queues[sched_entity.level].push_back(task);
+    }
+
+    fn fetch(&mut self) -> Option<Arc<TaskStruct>> {
+        self.boost();
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+
+    fn time_slice(&self, task: &TaskStruct) -> usize {
+        Self::level_time_slice(task.sched_entity().level)
+    }
+}
@@ -0,0 +1,81 @@
+//! ### the policies picking the next ready task
+//! selected by cargo features :
+//! - default : round robin
+//! - "stride" : stride scheduling, the share of CPU is proportional to the priority
+//! - "mlfq" : multi-level feedback queue, interactive tasks run first
+
+use alloc::{boxed::Box, sync::Arc};
+
+use crate::kfc_sbi::timer::INTERVAL;
+
+use super::task_struct::TaskStruct;
+
+#[cfg(all(feature = "stride", feature = "mlfq"))]
+compile_error!("features \"stride\" and \"mlfq\" are exclusive");
+
+#[cfg(feature = "mlfq")]
+mod mlfq;
+#[cfg(not(any(feature = "stride", feature = "mlfq")))]
+mod round_robin;
+#[cfg(feature = "stride")]
+mod stride;
+
+pub const DEFAULT_PRIORITY: usize = 16;
+pub const MIN_PRIORITY: usize = 2;
+/// bounds the share of CPU a task can take, against the others with `MIN_PRIORITY`
+pub const MAX_PRIORITY: usize = 1 << 10;
+
+/// the states of a task kept for the schedulers, inherited by fork and new threads
+#[derive(Debug, Clone, Copy)]
+pub struct SchedEntity {
+    /// in `MIN_PRIORITY..=MAX_PRIORITY`
+    pub priority: usize,
+    /// stride : the virtual time the task has run
+    pub pass: usize,
+    /// mlfq : the level of queue, 0 is the highest
+    pub level: usize,
+    /// mlfq : the boost the level belongs to, the level is reset by a new boost
+    pub boost_epoch: usize,
+    /// the task is switched out by the timer, having used up its time slice
+    pub preempted: bool,
+}
+
+impl SchedEntity {
+    pub fn new() -> Self {
+        Self {
+            priority: DEFAULT_PRIORITY,
+            pass: 0,
+            level: 0,
+            boost_epoch: 0,
+            preempted: false,
+        }
+    }
+}
+
+pub trait Scheduler: Send {
+    /// the task becomes ready to run
+    fn add(&mut self, task: Arc<TaskStruct>);
+
+    /// take out the next task to run
+    fn fetch(&mut self) -> Option<Arc<TaskStruct>>;
+
+    /// the time (in clock ticks) the task runs before the timer interrupt
+    fn time_slice(&self, _task: &TaskStruct) -> usize {
+        INTERVAL
+    }
+}
+
+#[cfg(not(any(feature = "stride", feature = "mlfq")))]
+pub fn new_scheduler() -> Box<dyn Scheduler> {
+    Box::new(round_robin::RoundRobin::new())
+}
+
+#[cfg(feature = "stride")]
+pub fn new_scheduler() -> Box<dyn Scheduler> {
+    Box::new(stride::Stride::new())
+}
+
+#[cfg(feature = "mlfq")]
+pub fn new_scheduler() -> Box<dyn Scheduler> {
+    Box::new(mlfq::Mlfq::new())
+}
@@ -0,0 +1,28 @@
+use alloc::{collections::VecDeque, sync::Arc};
+
+use crate::task::task_struct::TaskStruct;
+
+use super::Scheduler;
+
+/// first in first out, every task gets the same time slice
+pub struct RoundRobin {
+    ready_queue: VecDeque<Arc<TaskStruct>>,
+}
+
+impl RoundRobin {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for RoundRobin {
+    fn add(&mut self, task: Arc<TaskStruct>) {
+        self.ready_queue.push_back(task);
+    }
+
+    fn fetch(&mut self) -> Option<Arc<TaskStruct>> {
+        self.ready_queue.pop_front()
+    }
+}
@@ -0,0 +1,46 @@
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::task::task_struct::TaskStruct;
+
+use super::{Scheduler, MAX_PRIORITY};
+
+/// the pass grows by BIG_STRIDE / priority for each time slice
+/// - usize doesn't overflow in practice : 2^44 slices at least
+const BIG_STRIDE: usize = 1 << 20;
+
+// a task of `MAX_PRIORITY` still has its pass growing, or it would take the CPU forever
+const _: () = assert!(BIG_STRIDE / MAX_PRIORITY > 1);
+
+/// ### stride scheduling
+/// the task with the least pass runs next, so a task with priority p
+/// takes time slices in proportion to p
+pub struct Stride {
+    ready_tasks: Vec<Arc<TaskStruct>>,
+}
+
+impl Stride {
+    pub fn new() -> Self {
+        Self {
+            ready_tasks: Vec::new(),
+        }
+    }
+}
+
+impl Scheduler for Stride {
+    fn add(&mut self, task: Arc<TaskStruct>) {
+        self.ready_tasks.push(task);
+    }
+
+    fn fetch(&mut self) -> Option<Arc<TaskStruct>> {
+        let (index, _) = self
+            .ready_tasks
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, task)| task.sched_entity().pass)?;
+        let task = self.ready_tasks.swap_remove(index);
+        let mut sched_entity = task.sched_entity();
+        sched_entity.pass += BIG_STRIDE / sched_entity.priority;
+        task.set_sched_entity(sched_entity);
+        Some(task)
+    }
+}
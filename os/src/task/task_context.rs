@@ -7,7 +7,7 @@ pub struct TaskContext {
 }
 
 impl TaskContext {
-    /// back to `trap_return()` function as initial `ra`
+    /// back to `task_entry()` function as initial `ra`
     pub fn new(kernel_sp: usize, kernel_ra: usize) -> Self {
         Self {
             kernel_sp,
@@ -3,32 +3,41 @@
 // 1. TaskManagerInner's methods : do the real work
 //
 // 2. TaskManager's methods : call TaskManagerInner's methods, and do some synchronization
-// exclusive_access() can't be called outside TaskManager!
+// lock() can't be called outside TaskManager!
 //
 // 3. Normal functions : call TaskManager's methods
 // for outside use
 
-use alloc::{collections::VecDeque, sync::Arc};
+use alloc::{boxed::Box, sync::Arc};
 
-use crate::{app_loader::get_app_names, kfc_util::up_safe_cell::UPSafeCell};
+use crate::{app_loader::get_app_names, kfc_util::spin_lock::IrqSpinLock};
 use lazy_static::lazy_static;
 
-use super::task_struct::TaskStruct;
+use super::{
+    process_struct::ProcessStruct,
+    scheduler::{new_scheduler, Scheduler},
+    task_struct::TaskStruct,
+};
 
 lazy_static! {
     pub static ref TASK_MANAGER: TaskManager = TaskManager {
-        inner: UPSafeCell::new(TaskManagerInner {
-            task_structs: VecDeque::new(),
-        }),
+        inner: IrqSpinLock::new(
+            "TASK_MANAGER",
+            TaskManagerInner {
+                scheduler: new_scheduler(),
+            }
+        ),
     };
 }
 
+/// shared by all the harts, and safe to lock in the interrupt handlers
 pub struct TaskManager {
-    inner: UPSafeCell<TaskManagerInner>,
+    inner: IrqSpinLock<TaskManagerInner>,
 }
 
 pub struct TaskManagerInner {
-    pub task_structs: VecDeque<Arc<TaskStruct>>,
+    /// the ready tasks, picked by the policy selected at build time
+    pub scheduler: Box<dyn Scheduler>,
 }
 
 impl TaskManagerInner {
@@ -37,26 +46,32 @@ impl TaskManagerInner {
         let app_names = get_app_names();
         for name in app_names.iter() {
             info!("loading app {} into memory", name);
-            self.task_structs
-                .push_back(Arc::new(TaskStruct::new_from_elf(name)));
+            let process = ProcessStruct::new_from_elf(name);
+            self.scheduler
+                .add(process.main_thread().expect("no main thread"));
         }
     }
 
     pub fn fetch(&mut self) -> Option<Arc<TaskStruct>> {
-        self.task_structs.pop_front()
+        self.scheduler.fetch()
     }
 
     pub fn add(&mut self, task: Arc<TaskStruct>) {
-        self.task_structs.push_back(task);
+        self.scheduler.add(task);
     }
 }
 
 impl TaskManager {
     pub fn fetch_ready_task(&self) -> Option<Arc<TaskStruct>> {
-        self.inner.exclusive_access().fetch()
+        self.inner.lock().fetch()
     }
 
     pub fn add_ready_task(&self, task: Arc<TaskStruct>) {
-        self.inner.exclusive_access().add(task);
+        self.inner.lock().add(task);
+    }
+
+    /// the time slice of the task to be switched in
+    pub fn time_slice(&self, task: &TaskStruct) -> usize {
+        self.inner.lock().scheduler.time_slice(task)
     }
 }
@@ -1,54 +1,72 @@
-use alloc::{
-    string::String,
-    sync::{Arc, Weak},
-    vec::Vec,
-};
+use core::sync::atomic::AtomicBool;
+
+use alloc::sync::Arc;
 
 use crate::{
-    app_loader::load_app_by_name,
-    config::TRAP_CTX_VIRT_ADDR,
+    config::{PAGE_BYTES, TRAP_CTX_VIRT_ADDR, USER_STACK_SIZE},
     kfc_util::up_safe_cell::UPSafeCell,
-    mm::{memory_set::MemorySet, Frame, PageTable, KERNEL_SPACE},
-    task::pid_allocator::pid_alloc,
-    trap::{trap_context::TrapContext, trap_handler, trap_return},
+    mm::{Frame, PageTable, VirtAddr, KERNEL_SPACE},
+    syscall_impl::errno::ENOMEM,
+    trap::{task_entry, trap_context::TrapContext, trap_handler},
 };
 
 use super::{
-    kernel_stack::KernelStack, pid_allocator::PIDTracker, task_context::TaskContext, INIT_PROC,
+    kernel_stack::KernelStack, process_struct::ProcessStruct, scheduler::SchedEntity,
+    task_context::TaskContext,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TaskStatus {
     Ready,
     Running,
+    /// waiting in a `WaitQueue` or sleeping
+    Blocked,
     Zombie,
 }
 
+/// the trap context of thread `tid`, the main thread's is at `TRAP_CTX_VIRT_ADDR`
+pub fn trap_ctx_virt_addr(tid: usize) -> VirtAddr {
+    VirtAddr(TRAP_CTX_VIRT_ADDR.0 - tid * PAGE_BYTES)
+}
+
 pub struct TaskStructInner {
-    pub name: String,
     pub task_ctx: TaskContext,
     pub trap_ctx_frame: Frame,
     pub status: TaskStatus,
-    pub user_space: MemorySet,
     pub exit_code: i32,
-    pub parent: Option<Weak<TaskStruct>>,
-    pub children: Vec<Arc<TaskStruct>>,
+    pub sched_entity: SchedEntity,
+    /// the process has exited, the thread should exit at its next trap
+    pub killed: bool,
+    /// blocked in a wait ended by `kill`
+    pub killable: bool,
 }
 
+/// ### a thread, the unit scheduled on the harts
+/// the threads of a process share its address space and files
 pub struct TaskStruct {
     // read only fields
+    pub process: Arc<ProcessStruct>,
+    /// 0 : the main thread
+    pub tid: usize,
+    /// None : the main thread, whose user stack is built with the user space
+    pub ustack_bottom: Option<VirtAddr>,
     pub kernel_stack: KernelStack,
-    pub pid: PIDTracker,
+    /// its context is in use by a hart, until saved by `__switch`
+    pub on_cpu: AtomicBool,
     inner: UPSafeCell<TaskStructInner>,
 }
 
 impl TaskStruct {
-    pub fn get_name(&self) -> String {
-        self.inner.exclusive_access().name.clone()
+    pub fn sched_entity(&self) -> SchedEntity {
+        self.inner.exclusive_access().sched_entity
     }
 
-    pub fn pt_entry(&self) -> Frame {
-        self.inner.exclusive_access().user_space.page_table.entry
+    pub fn set_sched_entity(&self, sched_entity: SchedEntity) {
+        self.inner.exclusive_access().sched_entity = sched_entity;
+    }
+
+    pub fn get_exit_code(&self) -> i32 {
+        self.inner.exclusive_access().exit_code
     }
 
     pub fn task_status(&self) -> TaskStatus {
@@ -59,208 +77,137 @@ impl TaskStruct {
         self.inner.exclusive_access().status = status;
     }
 
-    pub fn add_child(&self, child: Arc<TaskStruct>) {
-        self.inner.exclusive_access().children.push(child);
+    /// ### Running -> Blocked
+    /// false : a killable wait is not entered by a killed thread
+    pub fn block(&self, killable: bool) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if killable && inner.killed {
+            return false;
+        }
+        inner.status = TaskStatus::Blocked;
+        inner.killable = killable;
+        true
+    }
+
+    /// ### mark the thread killed, and end its killable wait
+    /// return true if it is woken up, the caller should add it to the task manager
+    pub fn kill(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        inner.killed = true;
+        if inner.status == TaskStatus::Blocked && inner.killable {
+            inner.status = TaskStatus::Ready;
+            return true;
+        }
+        false
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.inner.exclusive_access().killed
+    }
+
+    /// Blocked -> Ready, false : not blocked
+    pub fn unblock(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.status != TaskStatus::Blocked {
+            return false;
+        }
+        inner.status = TaskStatus::Ready;
+        true
     }
 
-    pub fn set_parent(&self, parent: Weak<TaskStruct>) {
-        self.inner.exclusive_access().parent = Some(parent);
+    pub fn trap_ctx_va(&self) -> VirtAddr {
+        trap_ctx_virt_addr(self.tid)
     }
 
     pub fn trap_ctx_mut(&self) -> &'static mut TrapContext {
         self.inner.exclusive_access().trap_ctx_frame.get_mut()
     }
 
+    /// the main thread gets a new trap context page after exec
+    pub fn set_trap_ctx_frame(&self, trap_ctx_frame: Frame) {
+        self.inner.exclusive_access().trap_ctx_frame = trap_ctx_frame;
+    }
+
     pub fn task_ctx_ptr(&self) -> *mut TaskContext {
         &self.inner.exclusive_access().task_ctx as *const _ as *mut _
     }
 }
 
 impl TaskStruct {
-    pub fn new_from_elf(name: &'static str) -> Self {
-        let pid = pid_alloc();
-        let elf_data = load_app_by_name(name);
-        let (user_space, entry_addr, user_sp) =
-            MemorySet::new_from_elf(elf_data.expect("failed to load app"));
-        let kernel_stack = KernelStack::new(*pid);
-
-        // initialize the task context
-        let task_ctx = TaskContext::new(kernel_stack.top_sp(), trap_return as usize);
-
-        // initialize the trap context
-        let trap_ctx_frame = user_space
-            .page_table
-            .translate_vp(TRAP_CTX_VIRT_ADDR.floor_page())
-            .unwrap();
-
-        let trap_ctx = trap_ctx_frame.get_mut::<TrapContext>();
-        *trap_ctx = TrapContext::init_trap_ctx(
-            entry_addr,
-            user_sp,
-            PageTable::satp_token(KERNEL_SPACE.pt_entry()),
-            kernel_stack.top_sp(),
-            trap_handler as usize,
-        );
-
-        TaskStruct {
-            kernel_stack,
-            pid,
-            inner: UPSafeCell::new(TaskStructInner {
-                name: name.into(),
-                task_ctx,
-                trap_ctx_frame,
-                status: TaskStatus::Ready,
-                user_space,
-                exit_code: 0,
-                parent: None,
-                children: Vec::new(),
-            }),
-        }
-    }
-
-    // trap context : as the same as the context "when task traps in"
-    // but the kernel_sp should change
-    // task context : initial task context, return to trap_return
-    pub fn fork_task_struct(&self) -> Self {
-        let pid = pid_alloc();
-
-        let user_space = self.inner.exclusive_access().user_space.fork_memory_set();
-
-        let kernel_stack = KernelStack::new(*pid);
-
-        let task_ctx = TaskContext::new(kernel_stack.top_sp(), trap_return as usize);
-
-        let trap_ctx_frame = user_space
-            .page_table
-            .translate_vp(TRAP_CTX_VIRT_ADDR.floor_page())
-            .unwrap();
-
-        let trap_ctx = trap_ctx_frame.get_mut::<TrapContext>();
-        // only kernel sp changes
-        trap_ctx.kernel_sp = kernel_stack.top_sp();
-
-        let inner = TaskStructInner {
-            task_ctx,
-            trap_ctx_frame,
-            status: TaskStatus::Ready,
-            name: self.get_name().clone(),
-            user_space,
-            exit_code: 0,
-            parent: None,
-            children: Vec::new(),
-        };
-
-        TaskStruct {
+    /// ### a new thread in `process`, not added to the task manager
+    /// - the main thread (tid 0) uses the trap context and user stack in the user space,
+    ///   the others get new ones
+    /// - the trap context is left for the caller to initialize
+    /// - Err : errno (positive), EAGAIN : too many threads, ENOMEM : no memory for the stacks
+    pub fn new(
+        process: &Arc<ProcessStruct>,
+        sched_entity: SchedEntity,
+    ) -> Result<Arc<Self>, isize> {
+        let kernel_stack = KernelStack::new().ok_or(ENOMEM)?;
+        let (tid, trap_ctx_frame, ustack_bottom) = process.alloc_thread()?;
+
+        // back to user space for the first run
+        let task_ctx = TaskContext::new(kernel_stack.top_sp(), task_entry as usize);
+
+        let task = Arc::new(TaskStruct {
+            process: process.clone(),
+            tid,
+            ustack_bottom,
             kernel_stack,
-            pid,
-            inner: UPSafeCell::new(inner),
-        }
+            on_cpu: AtomicBool::new(false),
+            inner: UPSafeCell::new(
+                "task",
+                TaskStructInner {
+                    task_ctx,
+                    trap_ctx_frame,
+                    status: TaskStatus::Ready,
+                    exit_code: 0,
+                    sched_entity,
+                    killed: false,
+                    killable: false,
+                },
+            ),
+        });
+        process.add_thread(task.clone());
+        Ok(task)
     }
 
-    pub fn exec_from_elf(&self, name_ptr: *const u8) -> Option<isize> {
-        // update name
-        let name = self
-            .inner
-            .exclusive_access()
-            .user_space
-            .page_table
-            .translate_str(name_ptr)?;
-
-        self.inner.exclusive_access().name = name.clone();
-
-        // pid : no change
-        let elf_data = load_app_by_name(&name)?;
-
-        // kernel stack doesn't need to be updated
-
-        // task context doesn't need to be updated
-
-        // alloc new user_space and replace the old one
-        let (user_space, entry_addr, user_sp) = MemorySet::new_from_elf(elf_data);
-        self.inner.exclusive_access().user_space = user_space;
-
-        // get new trap context frame
-        let trap_ctx_frame = self
-            .inner
-            .exclusive_access()
-            .user_space
-            .page_table
-            .translate_vp(TRAP_CTX_VIRT_ADDR.floor_page())
-            .unwrap();
-        // !!! update the trap context frame !!!
-        self.inner.exclusive_access().trap_ctx_frame = trap_ctx_frame;
-
-        // trap context : set to entry point of the new code
-        let trap_ctx = trap_ctx_frame.get_mut::<TrapContext>();
+    /// ### a thread other than the main one, running `entry(arg)` on its own user stack
+    /// Err : errno (positive)
+    pub fn new_user_thread(
+        process: &Arc<ProcessStruct>,
+        sched_entity: SchedEntity,
+        entry: usize,
+        arg: usize,
+    ) -> Result<Arc<Self>, isize> {
+        let task = Self::new(process, sched_entity)?;
+        let ustack_bottom = task.ustack_bottom.expect("no user stack for a new thread");
+        let ustack_top = ustack_bottom.0 + USER_STACK_SIZE;
+        let trap_ctx = task.trap_ctx_mut();
         *trap_ctx = TrapContext::init_trap_ctx(
-            entry_addr,
-            user_sp,
+            entry,
+            ustack_top,
             PageTable::satp_token(KERNEL_SPACE.pt_entry()),
-            self.kernel_stack.top_sp(),
+            task.kernel_stack.top_sp(),
             trap_handler as usize,
         );
-
-        Some(0)
+        trap_ctx.x[10] = arg;
+        Ok(task)
     }
-}
 
-// system call related functions
-impl TaskStruct {
-    // exit the task
-    pub fn exit_task(&self, exit_code: i32) {
-        let mut inner = self.inner.exclusive_access();
-
-        // change the status
-        inner.status = TaskStatus::Zombie;
-
-        // store the exit code
-        inner.exit_code = exit_code;
-
-        // free the resources
-        inner.user_space.free_resources();
-
-        // move a the child process to INIT_PROC
-        for child in inner.children.iter() {
-            child.set_parent(Arc::downgrade(&INIT_PROC));
-            INIT_PROC.add_child(child.clone());
+    /// ### the thread exits
+    /// - its user stack and trap context are released, the last thread frees the user space
+    /// - the main thread exiting : the whole process exits,
+    ///   the other threads are killed at their next trap
+    pub fn exit_thread(&self, exit_code: i32) {
+        self.inner.exclusive_access().exit_code = exit_code;
+        self.process.thread_exited(self);
+        if self.tid == 0 {
+            self.process.exit_process(exit_code);
         }
 
-        inner.children.clear();
-    }
-
-    pub fn wait_task(&self, pid: isize, exit_code_mut: &mut i32) -> isize {
-        let mut inner = self.inner.exclusive_access();
-
-        // no required pid found
-        if inner
-            .children
-            .iter()
-            .find(|&ch| pid == -1 || *ch.pid == pid as usize)
-            .is_none()
-        {
-            return -1;
-        }
-
-        let zombie = inner.children.iter().position(|ch| {
-            (pid == -1 || *ch.pid == pid as usize) && ch.task_status() == TaskStatus::Zombie
-        });
-
-        if let Some(zom_idx) = zombie {
-            let ch = inner.children.remove(zom_idx);
-            let pid = *ch.pid;
-
-            assert!(
-                Arc::strong_count(&ch) == 1,
-                "zombie process has more than one reference"
-            );
-            *exit_code_mut = ch.inner.exclusive_access().exit_code;
-
-            // ----------------- ch dropped here -----------------
-
-            pid as isize
-        } else {
-            // still running
-            return -2;
-        }
+        // change the status, then the other threads may be waiting for this one
+        self.mark_task_status(TaskStatus::Zombie);
+        self.process.wait_thread.wake_all();
     }
 }
@@ -0,0 +1,133 @@
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+use lazy_static::lazy_static;
+
+use crate::{kfc_sbi::timer::get_time, kfc_util::up_safe_cell::UPSafeCell};
+
+use super::{block_cur_run_next, task_struct::TaskStruct, PROCESSOR, TASK_MANAGER};
+
+/// ### the tasks blocked until an event
+/// the waiter should check the condition again after woken up
+pub struct WaitQueue {
+    tasks: UPSafeCell<VecDeque<Arc<TaskStruct>>>,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self {
+            tasks: UPSafeCell::new("wait_queue", VecDeque::new()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.exclusive_access().is_empty()
+    }
+
+    /// ### block the current task until woken up, if `cond` holds
+    /// `cond` is checked with the queue locked, so a waker on another hart
+    /// changing the condition before waking up the queue is not missed
+    pub fn wait_if(&self, cond: impl FnOnce() -> bool) {
+        let mut tasks = self.tasks.exclusive_access();
+        if cond() {
+            block_cur_run_next(false, move |task| tasks.push_back(task));
+        }
+    }
+
+    /// ### `wait_if`, also ended when the process exits
+    /// false : the thread is killed, it should return to user space to exit
+    pub fn wait_killable_if(&self, cond: impl FnOnce() -> bool) -> bool {
+        let mut tasks = self.tasks.exclusive_access();
+        if !cond() {
+            return true;
+        }
+        if !block_cur_run_next(true, move |task| tasks.push_back(task)) {
+            return false;
+        }
+        // woken up by `kill`, the task is still in the queue
+        let cur_task = PROCESSOR.current_arc().expect("no current task");
+        let mut tasks = self.tasks.exclusive_access();
+        if let Some(index) = tasks.iter().position(|task| Arc::ptr_eq(task, &cur_task)) {
+            tasks.remove(index);
+        }
+        drop(tasks);
+        !cur_task.is_killed()
+    }
+
+    /// wake up the earliest waiter, false : no waiter
+    pub fn wake_one(&self) -> bool {
+        let mut tasks = self.tasks.exclusive_access();
+        while let Some(task) = tasks.pop_front() {
+            // a killed waiter may be woken up already
+            if wake_up(task) {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn wake_all(&self) {
+        while self.wake_one() {}
+    }
+}
+
+/// ### make a blocked task ready
+/// false : woken up already, a task waiting with a timeout or killed can be found
+/// by two wakers
+pub fn wake_up(task: Arc<TaskStruct>) -> bool {
+    if !task.unblock() {
+        return false;
+    }
+    TASK_MANAGER.add_ready_task(task);
+    true
+}
+
+lazy_static! {
+    /// (deadline, task) sorted by the deadline
+    static ref SLEEPING_TASKS: UPSafeCell<Vec<(usize, Arc<TaskStruct>)>> =
+        UPSafeCell::new("SLEEPING_TASKS", Vec::new());
+}
+
+/// ### block the current task until the time (in clock ticks)
+/// false : the thread is killed before the time
+pub fn sleep_until(deadline: usize) -> bool {
+    if !block_cur_run_next(true, |task| add_sleeping(deadline, task)) {
+        return false;
+    }
+    let cur_task = PROCESSOR.current_arc().expect("no current task");
+    cancel_sleeping(&cur_task);
+    !cur_task.is_killed()
+}
+
+/// ### wake up `task` at the time (in clock ticks), if it is blocked then
+/// the caller parking `task` elsewhere too should `cancel_sleeping` after it is woken up
+pub fn add_sleeping(deadline: usize, task: Arc<TaskStruct>) {
+    let mut sleeping = SLEEPING_TASKS.exclusive_access();
+    let index = sleeping.partition_point(|(other, _)| *other <= deadline);
+    sleeping.insert(index, (deadline, task));
+}
+
+/// remove `task` from the sleeping tasks, false : not sleeping (expired)
+pub fn cancel_sleeping(task: &Arc<TaskStruct>) -> bool {
+    let mut sleeping = SLEEPING_TASKS.exclusive_access();
+    match sleeping
+        .iter()
+        .position(|(_, other)| Arc::ptr_eq(other, task))
+    {
+        Some(index) => {
+            sleeping.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// wake up the sleeping tasks whose deadline has passed
+pub fn wake_expired() {
+    let now = get_time();
+    // woken up with the list locked, so a task woken up by an event before
+    // can not block elsewhere until it has called `cancel_sleeping`
+    let mut sleeping = SLEEPING_TASKS.exclusive_access();
+    let count = sleeping.partition_point(|(deadline, _)| *deadline <= now);
+    for (_, task) in sleeping.drain(..count) {
+        wake_up(task);
+    }
+}
@@ -2,6 +2,8 @@ use core::arch::asm;
 
 use riscv::register::{scause, stval};
 
+use crate::task::preempt::set_need_resched;
+
 #[naked]
 #[no_mangle]
 pub extern "C" fn kernelvec() {
@@ -43,9 +45,18 @@ pub extern "C" fn kernelvec() {
     }
 }
 
+/// ### the traps taken in S-mode
+/// - timer : only marks the hart, the task is switched out at the next safe point,
+///   as the interrupted code may hold a lock
+/// - others : kernel bug
 pub fn kernel_trap_handler() {
     let s_cause = scause::read();
     let s_val = stval::read();
+    if let scause::Trap::Interrupt(scause::Interrupt::SupervisorSoft) = s_cause.cause() {
+        unsafe { asm!("csrw sip, {ssip}", ssip = in(reg) !2) };
+        set_need_resched();
+        return;
+    }
     panic!(
         "Trap when in S-mode! [{:?}] , at address : {:#X}",
         s_cause.cause(),
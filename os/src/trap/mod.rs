@@ -2,13 +2,16 @@ pub mod kernel_trap;
 pub mod trap_context;
 
 use crate::{
-    config::TRAP_CTX_VIRT_ADDR,
-    mm::PageTable,
-    task::{exit_cur_run_next, suspend_cur_run_next, PROCESSOR},
+    kfc_sbi::hart_id,
+    mm::{PageFaultError, PageTable, VirtAddr},
+    task::{
+        exit_cur_run_next, preempt::cond_resched, preempt::preempt_enable_no_resched,
+        preempt_cur_run_next, PROCESSOR,
+    },
 };
 use core::arch::{asm, global_asm};
 
-use riscv::register::{scause, stval, stvec};
+use riscv::register::{scause, sstatus, stval, stvec};
 
 use crate::{config::TRAMPOLINE_VIRT_ADDR, mm::Frame, syscall_impl::syscall_dispathcer};
 
@@ -24,12 +27,14 @@ pub fn trampoline_frame() -> Frame {
 }
 
 pub fn trap_handler() -> ! {
-    // when in S-mode, disable the exception
+    // the traps in S-mode go to kernelvec
     // the interrupt has been disabled by hardware (sstatus.sie = 0)
     unsafe { stvec::write(kernelvec as usize, stvec::TrapMode::Direct) };
     let mut trap_ctx = PROCESSOR.cur_trap_ctx_mut();
     let s_cause = scause::read();
     let s_tval = stval::read();
+    // scause and stval are read, the timer can interrupt the kernel now
+    unsafe { sstatus::set_sie() };
     match s_cause.cause() {
         scause::Trap::Exception(e) => {
             match e {
@@ -40,59 +45,115 @@ pub fn trap_handler() -> ! {
                     trap_ctx.s_epc += 4;
                     let result = syscall_dispathcer(
                         trap_ctx.x[17],
-                        [trap_ctx.x[10], trap_ctx.x[11], trap_ctx.x[12]],
+                        [
+                            trap_ctx.x[10],
+                            trap_ctx.x[11],
+                            trap_ctx.x[12],
+                            trap_ctx.x[13],
+                            trap_ctx.x[14],
+                            trap_ctx.x[15],
+                        ],
                     ) as usize;
                     // exec will change the trap_ctx
                     trap_ctx = PROCESSOR.cur_trap_ctx_mut();
                     trap_ctx.x[10] = result;
                 }
-                _ => {
-                    {
-                        let cur_task = PROCESSOR
-                            .current_arc()
-                            .expect("exception handler : no current task");
-                        info!(
-                            "In process \"{}\", pid = {}, exception \x1b[31m[{:?}]\x1b[34m happen at address : {:#X}, s_val : {:#X}",
-                            cur_task.get_name(),
-                            *cur_task.pid,
-                            e,
-                            trap_ctx.s_epc,
-                            s_tval
+                scause::Exception::LoadPageFault | scause::Exception::StorePageFault => {
+                    let res = PROCESSOR
+                        .current_process()
+                        .expect("page fault handler : no current process")
+                        .handle_page_fault(
+                            VirtAddr(s_tval),
+                            e == scause::Exception::StorePageFault,
                         );
-                        // --------cur task drop here--------
+                    if let Err(err) = res {
+                        if err == PageFaultError::NoMemory {
+                            // not the fault of the thread alone, the whole process is killed
+                            info!("no frame left for the page fault");
+                            PROCESSOR
+                                .current_process()
+                                .expect("page fault handler : no current process")
+                                .kill();
+                        }
+                        exception_exit(e, trap_ctx.s_epc, s_tval);
                     }
-                    exit_cur_run_next(-1);
-                    // trap_ctx.s_epc += 4;
                 }
+                _ => exception_exit(e, trap_ctx.s_epc, s_tval),
             }
         }
         scause::Trap::Interrupt(i) => match i {
             scause::Interrupt::SupervisorSoft => {
                 unsafe { asm!("csrw sip, {ssip}", ssip = in(reg) !2) };
-                suspend_cur_run_next();
+                preempt_cur_run_next();
             }
             _ => panic!("{:?} is not supported!", i),
         },
     };
+    // the time slice may be used up in the kernel
+    cond_resched();
+    // the process has exited with its main thread, or is killed, kill the thread
+    let killed = {
+        let cur_task = PROCESSOR.current_arc().expect("no current task");
+        cur_task.process.is_zombie() || cur_task.is_killed()
+    };
+    if killed {
+        exit_cur_run_next(-1);
+    }
+    trap_return()
+}
+
+// the thread can not handle the exception, kill it (and the process if it is the main thread)
+fn exception_exit(e: scause::Exception, s_epc: usize, s_tval: usize) {
+    {
+        let cur_task = PROCESSOR
+            .current_arc()
+            .expect("exception handler : no current task");
+        info!(
+            "In process \"{}\", pid = {}, tid = {}, exception \x1b[31m[{:?}]\x1b[34m happen at address : {:#X}, s_val : {:#X}",
+            cur_task.process.get_name(),
+            *cur_task.process.pid,
+            cur_task.tid,
+            e,
+            s_epc,
+            s_tval
+        );
+        // --------cur task drop here--------
+    }
+    exit_cur_run_next(-1);
+}
+
+/// ### the first run of a task, switched to by the idle control flow
+/// release the preemption disabled for the switch, see `switch_to_idle`
+pub fn task_entry() -> ! {
+    preempt_enable_no_resched();
     trap_return()
 }
 
 /// `trap_return()` should pass the `user_satp` and `trap_ctx` to `__restore_ctx`
 pub fn trap_return() -> ! {
+    // no interrupt until back to user space, stvec and the trap context are changing
+    unsafe { sstatus::clear_sie() };
     extern "C" {
         fn __save_trap_ctx();
         fn __restore_trap_ctx();
     }
     let restore_va =
         TRAMPOLINE_VIRT_ADDR.0 + __restore_trap_ctx as usize - __save_trap_ctx as usize;
-    let user_satp: usize = PageTable::satp_token(PROCESSOR.current_arc().unwrap().pt_entry());
+    let cur_task = PROCESSOR.current_arc().expect("no current task");
+    let user_satp: usize = PageTable::satp_token(cur_task.process.pt_entry());
+    // each thread has its own trap context
+    let trap_ctx_va = cur_task.trap_ctx_va();
+    let trap_ctx = cur_task.trap_ctx_mut();
+    drop(cur_task);
+    // releasing the locks is the last safe point, read the hart id after it
+    trap_ctx.kernel_hart_id = hart_id();
     unsafe {
         // when jump back to user space, set stvec to trampoline again
         stvec::write(TRAMPOLINE_VIRT_ADDR.0, stvec::TrapMode::Direct);
         asm!("jr {addr}", 
             addr = in(reg) restore_va,
             in("a0") user_satp,
-            in("a1") TRAP_CTX_VIRT_ADDR.0,
+            in("a1") trap_ctx_va.0,
             options(noreturn));
     }
 }
@@ -2,6 +2,7 @@ use riscv::register::sstatus;
 
 /// Aligned in C style
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct TrapContext {
     pub x: [usize; 32],
     pub s_status: usize,
@@ -10,6 +11,8 @@ pub struct TrapContext {
     pub kernel_satp: usize,
     pub kernel_sp: usize,
     pub trap_handler: usize,
+    /// the hart to trap into, set by `trap_return` as the task may run on any hart
+    pub kernel_hart_id: usize,
 }
 
 impl TrapContext {
@@ -31,6 +34,7 @@ impl TrapContext {
             kernel_satp,
             kernel_sp,
             trap_handler,
+            kernel_hart_id: 0,
         }
     }
 }
@@ -1,9 +1,127 @@
 #![allow(unused)]
 use crate::syscall::{
-    sys_exec, sys_exit, sys_fork, sys_getpid, sys_read, sys_times, sys_waitpid, sys_write,
-    sys_yield,
+    sys_brk, sys_chdir, sys_close, sys_dup, sys_execve, sys_exit, sys_fork, sys_futex,
+    sys_get_priority, sys_getcwd, sys_getdents64, sys_getpid, sys_gettid, sys_mkdirat, sys_mmap,
+    sys_mount, sys_mprotect, sys_munmap, sys_nanosleep, sys_openat, sys_pipe, sys_read,
+    sys_set_priority, sys_thread_create, sys_times, sys_umount2, sys_unlinkat, sys_waitpid,
+    sys_waittid, sys_write, sys_yield,
 };
+use core::sync::atomic::AtomicU32;
 
+pub const AT_FDCWD: isize = -100;
+pub const AT_REMOVEDIR: u32 = 0x200;
+
+// d_type of linux_dirent64
+pub const DT_CHR: u8 = 2;
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+
+pub const O_RDONLY: u32 = 0;
+pub const O_WRONLY: u32 = 1 << 0;
+pub const O_RDWR: u32 = 1 << 1;
+pub const O_CREAT: u32 = 1 << 6;
+pub const O_TRUNC: u32 = 1 << 9;
+
+pub const PROT_NONE: usize = 0;
+pub const PROT_READ: usize = 1 << 0;
+pub const PROT_WRITE: usize = 1 << 1;
+pub const PROT_EXEC: usize = 1 << 2;
+
+pub const MAP_PRIVATE: usize = 1 << 1;
+pub const MAP_FIXED: usize = 1 << 4;
+pub const MAP_ANONYMOUS: usize = 1 << 5;
+
+/// the path should end with '\0'
+pub fn open(path: &str, flags: u32) -> isize {
+    sys_openat(AT_FDCWD, path, flags, 0)
+}
+
+/// return the length of the cwd including '\0'
+pub fn getcwd(buf: &mut [u8]) -> isize {
+    sys_getcwd(buf)
+}
+
+/// the path should end with '\0'
+pub fn chdir(path: &str) -> isize {
+    sys_chdir(path)
+}
+
+/// the path should end with '\0'
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdirat(AT_FDCWD, path, 0)
+}
+
+/// remove a file, the path should end with '\0'
+pub fn unlink(path: &str) -> isize {
+    sys_unlinkat(AT_FDCWD, path, 0)
+}
+
+/// remove an empty directory, the path should end with '\0'
+pub fn rmdir(path: &str) -> isize {
+    sys_unlinkat(AT_FDCWD, path, AT_REMOVEDIR)
+}
+
+/// ### mount a filesystem of `fs_type` on the directory
+/// `fs_type` : kfcfs, tmpfs, devfs or procfs, the strings should end with '\0'
+pub fn mount(source: &str, target: &str, fs_type: &str) -> isize {
+    sys_mount(source, target, fs_type, 0)
+}
+
+/// the path should end with '\0'
+pub fn umount(target: &str) -> isize {
+    sys_umount2(target, 0)
+}
+
+/// ### fill `buf` with `linux_dirent64`s of the opened directory
+/// return the number of bytes filled, 0 : no more entries
+pub fn getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    sys_getdents64(fd, buf)
+}
+
+/// ### an entry parsed from the buffer filled by `getdents64`
+pub struct DirEntry<'a> {
+    pub inode_id: u64,
+    pub d_type: u8,
+    pub name: &'a str,
+}
+
+/// iterate over the `linux_dirent64`s in `buf[..len]`
+pub fn dir_entries(buf: &[u8]) -> impl Iterator<Item = DirEntry<'_>> {
+    let mut pos = 0;
+    core::iter::from_fn(move || {
+        if pos + 19 > buf.len() {
+            return None;
+        }
+        let record = &buf[pos..];
+        let inode_id = u64::from_le_bytes(record[0..8].try_into().unwrap());
+        let reclen = u16::from_le_bytes(record[16..18].try_into().unwrap()) as usize;
+        let d_type = record[18];
+        let name = &record[19..reclen];
+        let name_len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        pos += reclen;
+        Some(DirEntry {
+            inode_id,
+            d_type,
+            name: core::str::from_utf8(&name[..name_len]).unwrap(),
+        })
+    })
+}
+
+pub fn dup(fd: usize) -> isize {
+    sys_dup(fd)
+}
+
+pub fn close(fd: usize) -> isize {
+    sys_close(fd)
+}
+
+/// pipe_fd : [read_end, write_end]
+pub fn pipe(pipe_fd: &mut [i32; 2]) -> isize {
+    sys_pipe(pipe_fd)
+}
+
+/// ### return the number of bytes written, or errno (negative)
+/// - EPIPE : the fd is a pipe whose read ends are all closed
 pub fn write(fd: usize, buffer: &[u8]) -> isize {
     sys_write(fd, buffer)
 }
@@ -16,6 +134,16 @@ pub fn yield_() -> isize {
     sys_yield()
 }
 
+/// ### the share of CPU is proportional to the priority with the stride scheduler
+/// return the priority, or -EINVAL if it is not in 2..=1024
+pub fn set_priority(priority: isize) -> isize {
+    sys_set_priority(priority)
+}
+
+pub fn get_priority() -> isize {
+    sys_get_priority()
+}
+
 pub fn get_time() -> isize {
     sys_times()
 }
@@ -32,35 +160,133 @@ pub fn fork() -> isize {
     sys_fork()
 }
 
+/// ### run `entry(arg)` in a new thread of the process, on its own user stack
+/// - `entry` should end with `exit`, which only exits the thread
+/// - return the tid, or errno (negative)
+pub fn thread_create(entry: usize, arg: usize) -> isize {
+    sys_thread_create(entry, arg)
+}
+
+/// the main thread is 0, `exit` in the main thread exits the whole process
+pub fn gettid() -> isize {
+    sys_gettid()
+}
+
+/// ### block until the thread exits, return its exit code
+/// -1 : no such thread, the main thread or the caller itself
+pub fn waittid(tid: usize) -> isize {
+    sys_waittid(tid)
+}
+
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+
+// the errors of futex_wait
+pub const EAGAIN: isize = 11;
+pub const ETIMEDOUT: isize = 110;
+
+/// ### block while `word` is `expected`, until woken up by `futex_wake`
+/// - timeout : in ms, None : no timeout
+/// - return 0 when woken up, -EAGAIN : `word` is not `expected`, -ETIMEDOUT : timed out
+/// - the caller should check `word` again, a waiter may return without a change
+pub fn futex_wait(word: &AtomicU32, expected: u32, timeout: Option<usize>) -> isize {
+    let timeout = timeout.map(|time| [time / 1000, time % 1000 * 1_000_000]);
+    sys_futex(word, FUTEX_WAIT, expected as usize, timeout.as_ref())
+}
+
+/// wake up at most `count` threads waiting on `word`, return the number woken up
+pub fn futex_wake(word: &AtomicU32, count: usize) -> isize {
+    sys_futex(word, FUTEX_WAKE, count, None)
+}
+
+/// ### run the program with only argv[0], the path should end with '\0'
+/// return errno (negative) on failure
 pub fn exec(path: &str) -> isize {
-    sys_exec(path)
+    sys_execve(
+        path,
+        &[path.as_ptr(), core::ptr::null()],
+        &[core::ptr::null()],
+    )
 }
 
+/// ### run the program with arguments and environment variables
+/// - `argv` and `envp` are NULL-terminated arrays of pointers to strings ending with '\0'
+/// - return errno (negative) on failure, E2BIG : argv and envp take more than half of
+///   the user stack, EFAULT : a bad pointer or a string not in UTF-8
+pub fn execve(path: &str, argv: &[*const u8], envp: &[*const u8]) -> isize {
+    assert_eq!(
+        argv.last(),
+        Some(&core::ptr::null()),
+        "argv should end with NULL"
+    );
+    assert_eq!(
+        envp.last(),
+        Some(&core::ptr::null()),
+        "envp should end with NULL"
+    );
+    sys_execve(path, argv, envp)
+}
+
+/// block until the child exits, -1 : no such child
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(pid as isize, exit_code) {
-            -2 => {
-                yield_();
-            }
-            exit_pid => return exit_pid,
-        }
-    }
+    sys_waitpid(pid as isize, exit_code)
 }
 
+/// block until any child exits, -1 : no child
 pub fn wait(exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(-1, exit_code) {
-            -2 => {
-                yield_();
-            }
-            exit_pid => return exit_pid,
-        }
-    }
+    sys_waitpid(-1, exit_code)
 }
 
+/// block for `tv_sec` s and `tv_nsec` ns, -EINVAL : tv_nsec is not less than 1s
+pub fn nanosleep(tv_sec: usize, tv_nsec: usize) -> isize {
+    sys_nanosleep(&[tv_sec, tv_nsec])
+}
+
+/// block for `time` ms
 pub fn sleep(time: usize) {
-    let start_time = get_time();
-    while get_time() - start_time < time as isize {
-        yield_();
+    nanosleep(time / 1000, time % 1000 * 1_000_000);
+}
+
+/// set the program break, return the new one (the old one if failed)
+pub fn brk(addr: usize) -> isize {
+    sys_brk(addr)
+}
+
+/// move the program break by `increment`, return the old one or -1
+pub fn sbrk(increment: isize) -> isize {
+    let old_brk = brk(0);
+    if increment == 0 {
+        return old_brk;
+    }
+    let new_brk = old_brk + increment;
+    if brk(new_brk as usize) != new_brk {
+        return -1;
     }
+    old_brk
+}
+
+/// anonymous private mapping, return the start address or errno (negative)
+pub fn mmap(addr: usize, len: usize, prot: usize, flags: usize) -> isize {
+    sys_mmap(addr, len, prot, flags, usize::MAX, 0)
+}
+
+/// ### private mapping of the file `fd` from `offset`, aligned to pages
+/// the unchanged pages are shared with the other mappings of the file
+pub fn mmap_file(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    sys_mmap(addr, len, prot, flags & !MAP_ANONYMOUS, fd, offset)
+}
+
+pub fn munmap(addr: usize, len: usize) -> isize {
+    sys_munmap(addr, len)
+}
+
+pub fn mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(addr, len, prot)
 }
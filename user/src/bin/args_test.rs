@@ -0,0 +1,92 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+use user_lib::{
+    api::{execve, exit, fork, waitpid},
+    args,
+    env::{var, vars},
+};
+
+const E2BIG: isize = 7;
+const ENOENT: isize = 2;
+const EFAULT: isize = 14;
+
+/// run by itself with "env" : check the environment variables
+fn check_env() -> i32 {
+    assert_eq!(args().count(), 2);
+    assert_eq!(var("KFC_KEY"), Some("value"));
+    assert_eq!(var("EMPTY"), Some(""));
+    assert_eq!(var("NOT_EXIST"), None);
+    assert_eq!(vars().count(), 2);
+    println!("args_test : environment variables passed");
+    0
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let argv: Vec<&str> = args().collect();
+    if argv.get(1) == Some(&"env") {
+        return check_env();
+    }
+
+    println!("\nargs_test APP running...\n");
+    for (i, arg) in argv.iter().enumerate() {
+        println!("argv[{}] = {}", i, arg);
+    }
+    assert_eq!(argv, ["args_test", "arg1", "arg2"]);
+    assert_eq!(vars().count(), 0);
+
+    let pid = fork();
+    if pid == 0 {
+        let null = core::ptr::null::<u8>();
+        assert_eq!(execve("args_test_not_exist\0", &[null], &[null]), -ENOENT);
+
+        // the arguments can't take more than half of the user stack
+        let mut long_arg = String::from("x").repeat(0x1000);
+        long_arg.push('\0');
+        assert_eq!(
+            execve("args_test\0", &[long_arg.as_ptr(), null], &[null]),
+            -E2BIG
+        );
+        // so can't the environment variables, the limit is on argv and envp together
+        let mut long_var = String::from("KEY=").repeat(0x200);
+        long_var.push('\0');
+        assert_eq!(
+            execve(
+                "args_test\0",
+                &["args_test\0".as_ptr(), long_var.as_ptr(), null],
+                &[long_var.as_ptr(), null]
+            ),
+            -E2BIG
+        );
+        // nor lots of short arguments
+        let mut many_args = Vec::from(["a\0".as_ptr(); 0x200]);
+        many_args.push(null);
+        assert_eq!(execve("args_test\0", &many_args, &[null]), -E2BIG);
+
+        // the strings should be in UTF-8
+        let invalid = b"\xff\xfe\0";
+        assert_eq!(
+            execve("args_test\0", &[invalid.as_ptr(), null], &[null]),
+            -EFAULT
+        );
+
+        execve(
+            "args_test\0",
+            &["args_test\0".as_ptr(), "env\0".as_ptr(), null],
+            &["KFC_KEY=value\0".as_ptr(), "EMPTY=\0".as_ptr(), null],
+        );
+        exit(-1);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    println!("args_test passed!");
+    0
+}
@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::api::{
+    exit, fork, get_time, getpid, mmap, munmap, nanosleep, sleep, thread_create, wait, waitpid,
+    waittid, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE,
+};
+
+const EFAULT: isize = 14;
+const EINVAL: isize = 22;
+const PAGE_SIZE: usize = 0x1000;
+
+/// fork a child sleeping for `time` ms
+fn sleepy_child(time: usize) -> isize {
+    let pid = fork();
+    if pid == 0 {
+        sleep(time);
+        exit(getpid() as i32);
+    }
+    pid
+}
+
+/// unmap the page at `addr` while the main thread is blocked in waitpid
+fn unmapper(addr: usize) -> ! {
+    sleep(50);
+    assert_eq!(munmap(addr, PAGE_SIZE), 0);
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nblock_test APP running...\n");
+
+    let start = get_time();
+    sleep(50);
+    let elapsed = get_time() - start;
+    println!("sleep 50 msecs, use {} msecs", elapsed);
+    assert!(elapsed >= 50);
+    assert_eq!(nanosleep(0, 1_000_000_000), -EINVAL);
+    assert_eq!(nanosleep(0, 0), 0);
+    // tv_sec is signed in the TimeSpec
+    assert_eq!(nanosleep(usize::MAX, 0), -EINVAL);
+
+    // the children sleep at the same time, woken up by the deadline
+    let start = get_time();
+    let late = sleepy_child(200);
+    let early = sleepy_child(100);
+    let mut exit_code = 0;
+    assert_eq!(wait(&mut exit_code), early);
+    assert_eq!(exit_code, early as i32);
+    assert_eq!(wait(&mut exit_code), late);
+    assert_eq!(exit_code, late as i32);
+    let elapsed = get_time() - start;
+    println!(
+        "two children sleep 100 and 200 msecs, use {} msecs",
+        elapsed
+    );
+    assert!(elapsed >= 200);
+
+    // the exit code is written after waking up, the page is gone by then
+    let page = mmap(
+        0,
+        PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+    );
+    assert!(page > 0);
+    let child = sleepy_child(100);
+    let tid = thread_create(unmapper as usize, page as usize);
+    assert!(tid > 0);
+    let exit_code_mut = unsafe { &mut *(page as *mut i32) };
+    assert_eq!(waitpid(child as usize, exit_code_mut), -EFAULT);
+    assert_eq!(waittid(tid as usize), 0);
+    // reaped anyway
+    assert_eq!(waitpid(child as usize, &mut exit_code), -1);
+
+    // no child left
+    assert_eq!(wait(&mut exit_code), -1);
+    assert_eq!(waitpid(getpid() as usize, &mut exit_code), -1);
+
+    println!("block_test passed!");
+    0
+}
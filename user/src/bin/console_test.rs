@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::api::{exit, getpid, read, sleep, thread_create};
+
+/// block on stdin, the console has no input during the tests
+fn reader(_arg: usize) -> ! {
+    let mut buf = [0u8; 1];
+    read(0, &mut buf);
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nconsole_test APP running...\n");
+    assert!(thread_create(reader as usize, 0) > 0);
+    // stdout is the same file as stdin, it is not locked by the blocked reader
+    for i in 0..10 {
+        sleep(1);
+        println!("written while a reader is blocked : {}", i);
+    }
+    println!("console_test passed! pid = {}", getpid());
+    // the reader is killed with the process
+    0
+}
@@ -0,0 +1,136 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::api::{
+    chdir, close, dir_entries, exit, fork, getcwd, getdents64, mkdir, open, read, rmdir, unlink,
+    waitpid, write, DT_DIR, DT_REG, O_CREAT, O_RDONLY, O_WRONLY,
+};
+
+const ENOENT: isize = 2;
+const EEXIST: isize = 17;
+const ENOTDIR: isize = 20;
+const EISDIR: isize = 21;
+const EINVAL: isize = 22;
+const ERANGE: isize = 34;
+const ENAMETOOLONG: isize = 36;
+const ENOTEMPTY: isize = 39;
+
+fn assert_cwd(expected: &str) {
+    let mut buf = [0u8; 64];
+    let len = getcwd(&mut buf);
+    assert_eq!(len, expected.len() as isize + 1);
+    assert_eq!(&buf[..expected.len()], expected.as_bytes());
+    assert_eq!(buf[expected.len()], 0);
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\ndir_test APP running...\n");
+
+    assert_cwd("/");
+    assert_eq!(getcwd(&mut [0u8; 1]), -ERANGE);
+
+    // build /dir_test/sub/file
+    assert_eq!(mkdir("dir_test\0"), 0);
+    assert_eq!(mkdir("/dir_test\0"), -EEXIST);
+    assert_eq!(mkdir("dir_test/sub\0"), 0);
+    assert_eq!(mkdir("dir_test/not_exist/sub\0"), -ENOENT);
+    // a name of kfc-fs has at most 27 bytes
+    assert_eq!(
+        mkdir("dir_test/a_name_longer_than_27_bytes\0"),
+        -ENAMETOOLONG
+    );
+    assert_eq!(
+        open("a_name_longer_than_27_bytes/file\0", O_CREAT | O_WRONLY),
+        -ENAMETOOLONG
+    );
+    assert_eq!(chdir("dir_test/sub\0"), 0);
+    assert_cwd("/dir_test/sub");
+
+    let msg = b"nested file";
+    let fd = open("file\0", O_CREAT | O_WRONLY) as usize;
+    assert_eq!(write(fd, msg), msg.len() as isize);
+    close(fd);
+
+    // "." and ".." in the path
+    let mut buf = [0u8; 64];
+    let fd = open("../sub/./file\0", O_RDONLY) as usize;
+    assert_eq!(read(fd, &mut buf), msg.len() as isize);
+    assert_eq!(&buf[..msg.len()], msg);
+    close(fd);
+    assert_eq!(open("file/x\0", O_RDONLY), -ENOTDIR);
+    assert_eq!(chdir("file\0"), -ENOTDIR);
+    assert_eq!(open("..\0", O_WRONLY), -EISDIR);
+
+    // list the parent
+    assert_eq!(chdir("..\0"), 0);
+    assert_cwd("/dir_test");
+    assert_eq!(mkdir("sub2\0"), 0);
+    let fd = open("/dir_test/sub/file\0", O_RDONLY) as usize;
+    assert_eq!(getdents64(fd, &mut buf), -ENOTDIR);
+    close(fd);
+
+    let fd = open(".\0", O_RDONLY) as usize;
+    // too small for any entry, but it's not the end
+    assert_eq!(getdents64(fd, &mut [0u8; 8]), -EINVAL);
+    let mut dents = [0u8; 256];
+    let len = getdents64(fd, &mut dents);
+    assert!(len > 0);
+    let mut count = 0;
+    for entry in dir_entries(&dents[..len as usize]) {
+        println!("{} : inode {}", entry.name, entry.inode_id);
+        assert!(entry.name == "sub" || entry.name == "sub2");
+        assert_eq!(entry.d_type, DT_DIR);
+        count += 1;
+    }
+    assert_eq!(count, 2);
+    assert_eq!(getdents64(fd, &mut dents), 0);
+    close(fd);
+
+    // the child inherits the cwd
+    let pid = fork();
+    if pid == 0 {
+        assert_cwd("/dir_test");
+        assert_eq!(chdir("sub\0"), 0);
+        let fd = open("file\0", O_RDONLY) as usize;
+        let len = getdents64(fd, &mut dents);
+        close(fd);
+        exit(if len == -ENOTDIR { 0 } else { -1 });
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    // the parent's cwd doesn't change
+    assert_cwd("/dir_test");
+
+    // the file is a regular file in its directory
+    let fd = open("sub\0", O_RDONLY) as usize;
+    let len = getdents64(fd, &mut dents);
+    let entry = dir_entries(&dents[..len as usize]).next().unwrap();
+    assert_eq!(entry.name, "file");
+    assert_eq!(entry.d_type, DT_REG);
+    close(fd);
+
+    // clean up
+    assert_eq!(rmdir("sub\0"), -ENOTEMPTY);
+    assert_eq!(unlink("sub\0"), -EISDIR);
+    assert_eq!(rmdir("sub/file\0"), -ENOTDIR);
+    // the opened file is still readable after unlinked
+    let fd = open("sub/file\0", O_RDONLY) as usize;
+    assert_eq!(unlink("sub/file\0"), 0);
+    assert_eq!(open("sub/file\0", O_RDONLY), -ENOENT);
+    assert_eq!(read(fd, &mut buf), msg.len() as isize);
+    assert_eq!(&buf[..msg.len()], msg);
+    close(fd);
+    assert_eq!(rmdir("sub\0"), 0);
+    assert_eq!(rmdir("sub2\0"), 0);
+    assert_eq!(chdir("/\0"), 0);
+    assert_eq!(rmdir("dir_test\0"), 0);
+    assert_eq!(chdir("dir_test\0"), -ENOENT);
+
+    println!("dir_test passed!");
+    0
+}
@@ -0,0 +1,42 @@
+//! ### a program dynamically linked against libuser.so
+//! - the Makefile builds it again as a PIE with "/ld" as its interpreter, linked to
+//!   libuser.so, replacing the static build of cargo
+//! - everything from the library goes through the C ABI of `user_lib::dylib`
+//! - print a line, then wait until stdin is closed, so the driver can inspect it meanwhile
+#![no_std]
+#![no_main]
+
+// the static build takes the C ABI from user_lib itself
+#[cfg(not(dynamic))]
+extern crate user_lib;
+
+extern "C" {
+    fn lib_start(argc: usize, argv: usize, main: extern "C" fn() -> i32) -> !;
+    fn lib_write(fd: usize, buf: *const u8, len: usize) -> isize;
+    fn lib_read(fd: usize, buf: *mut u8, len: usize) -> isize;
+}
+
+const MESSAGE: &[u8] = b"hello from libuser.so\n";
+
+/// the entry of the dynamic build, the static one starts from `_start` of user_lib
+#[no_mangle]
+extern "C" fn dyn_start(argc: usize, argv: usize) -> ! {
+    unsafe { lib_start(argc, argv, dyn_main) }
+}
+
+extern "C" fn dyn_main() -> i32 {
+    let mut buf = [0u8; 16];
+    unsafe {
+        if lib_write(1, MESSAGE.as_ptr(), MESSAGE.len()) != MESSAGE.len() as isize {
+            return -1;
+        }
+        while lib_read(0, buf.as_mut_ptr(), buf.len()) > 0 {}
+    }
+    0
+}
+
+#[cfg(not(dynamic))]
+#[no_mangle]
+fn main() -> i32 {
+    dyn_main()
+}
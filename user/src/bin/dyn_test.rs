@@ -0,0 +1,111 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::{format, string::String};
+use user_lib::api::{close, dup, exec, exit, fork, open, pipe, read, waitpid, O_RDONLY};
+
+// printed by "/dyn_hello", which is linked against libuser.so
+const MESSAGE: &[u8] = b"hello from libuser.so\n";
+
+/// the whole content of a file
+fn read_file(path: &str) -> String {
+    let fd = open(path, O_RDONLY);
+    assert!(fd >= 0, "failed to open the file");
+    let mut content = String::new();
+    let mut buf = [0u8; 128];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        content.push_str(core::str::from_utf8(&buf[..len as usize]).unwrap());
+    }
+    close(fd as usize);
+    content
+}
+
+/// the resident pages (in kB) of the process shared with others
+fn rss_shared(pid: usize) -> usize {
+    let status = read_file(&format!("/proc/{}/status\0", pid));
+    let line = status
+        .lines()
+        .find(|line| line.starts_with("RssShared:"))
+        .expect("no RssShared in the status");
+    line.split_whitespace().nth(1).unwrap().parse().unwrap()
+}
+
+/// run "/dyn_hello" with `stdin` and `stdout` redirected, the `pipes` are closed in it
+fn spawn(stdin: usize, stdout: usize, pipes: &[usize]) -> usize {
+    let pid = fork();
+    if pid == 0 {
+        close(0);
+        assert_eq!(dup(stdin), 0);
+        close(1);
+        assert_eq!(dup(stdout), 1);
+        for &fd in pipes {
+            close(fd);
+        }
+        exit(exec("/dyn_hello\0") as i32);
+    }
+    pid as usize
+}
+
+/// wait for the hello of a child, it has loaded the library by then
+fn expect_message(fd: usize) {
+    let mut buf = [0u8; MESSAGE.len()];
+    let mut total = 0;
+    while total < buf.len() {
+        let len = read(fd, &mut buf[total..]);
+        assert!(len > 0, "no message from the child");
+        total += len as usize;
+    }
+    assert_eq!(buf, MESSAGE);
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\ndyn_test APP running...\n");
+
+    let (mut input, mut output) = ([0i32; 2], [0i32; 2]);
+    assert_eq!(pipe(&mut input), 0);
+    assert_eq!(pipe(&mut output), 0);
+    let pipes = [input[0], input[1], output[0], output[1]].map(|fd| fd as usize);
+    let (stdin, stdin_writer, reader, stdout) = (pipes[0], pipes[1], pipes[2], pipes[3]);
+
+    let first = spawn(stdin, stdout, &pipes);
+    expect_message(reader);
+    let alone = rss_shared(first);
+    let maps = read_file(&format!("/proc/{}/maps\0", first));
+    assert!(
+        maps.contains("File"),
+        "the library is not mapped from the file"
+    );
+
+    // the second one maps the same pages of the library
+    let second = spawn(stdin, stdout, &pipes);
+    expect_message(reader);
+    let (shared, other) = (rss_shared(first), rss_shared(second));
+    println!(
+        "shared : {} kB alone, {} kB and {} kB with two processes",
+        alone, shared, other
+    );
+    assert!(shared > alone, "no frame of the library is shared");
+    assert!(other > 0);
+
+    // the children exit once stdin is closed
+    close(stdin_writer);
+    for pid in [first, second] {
+        let mut exit_code = -1;
+        assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+        assert_eq!(exit_code, 0);
+    }
+    for fd in [stdin, reader, stdout] {
+        close(fd);
+    }
+    println!("dyn_test passed!");
+    0
+}
@@ -0,0 +1,123 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{
+    api::{
+        close, exec, exit, fork, open, read, unlink, waitpid, write, O_CREAT, O_RDONLY, O_TRUNC,
+        O_WRONLY,
+    },
+    env::{getauxval, AT_ENTRY, AT_PAGESZ, AT_PHDR, AT_PHENT, AT_PHNUM, AT_RANDOM},
+};
+
+const ENOEXEC: isize = 8;
+const PT_LOAD: u32 = 1;
+
+fn read_file(path: &str) -> Vec<u8> {
+    let fd = open(path, O_RDONLY);
+    assert!(fd > 2, "failed to open the file");
+    let mut content = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        content.extend_from_slice(&buf[..len as usize]);
+    }
+    close(fd as usize);
+    content
+}
+
+fn write_file(path: &str, data: &[u8]) {
+    let fd = open(path, O_CREAT | O_WRONLY | O_TRUNC);
+    assert!(fd > 2, "failed to create the file");
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+}
+
+/// exec in a child, return the result of exec
+fn try_exec(path: &str) -> isize {
+    let pid = fork();
+    if pid == 0 {
+        exit(exec(path) as i32);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    exit_code as isize
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nelf_test APP running...\n");
+
+    // auxv
+    assert_eq!(getauxval(AT_PAGESZ), Some(0x1000));
+    assert_eq!(getauxval(AT_ENTRY), Some(user_lib::_start as usize));
+    assert_eq!(getauxval(AT_PHENT), Some(56));
+    let phnum = getauxval(AT_PHNUM).unwrap();
+    let phdr = getauxval(AT_PHDR).unwrap() as *const u32;
+    let loads = (0..phnum)
+        .filter(|i| unsafe { *phdr.add(i * 56 / 4) } == PT_LOAD)
+        .count();
+    println!("{} program headers, {} loadable", phnum, loads);
+    assert!(loads > 0);
+    let random =
+        unsafe { core::slice::from_raw_parts(getauxval(AT_RANDOM).unwrap() as *const u8, 16) };
+    assert!(random.iter().any(|&b| b != 0));
+
+    // malformed files
+    write_file("/tmp/not_elf\0", b"just some text, not an elf file");
+    assert_eq!(try_exec("/tmp/not_elf\0"), -ENOEXEC);
+
+    let hello = read_file("/hello\0");
+    write_file("/tmp/truncated_elf\0", &hello[..100]);
+    assert_eq!(try_exec("/tmp/truncated_elf\0"), -ENOEXEC);
+
+    // x86_64
+    let mut wrong_machine = hello.clone();
+    wrong_machine[18..20].copy_from_slice(&62u16.to_le_bytes());
+    write_file("/tmp/wrong_machine\0", &wrong_machine);
+    assert_eq!(try_exec("/tmp/wrong_machine\0"), -ENOEXEC);
+
+    // ELFCLASS32
+    let mut wrong_class = hello.clone();
+    wrong_class[4] = 1;
+    write_file("/tmp/wrong_class\0", &wrong_class);
+    assert_eq!(try_exec("/tmp/wrong_class\0"), -ENOEXEC);
+
+    // the first segment is moved into the trap context
+    let mut bad_segment = hello.clone();
+    let ph_offset = u64::from_le_bytes(hello[32..40].try_into().unwrap()) as usize;
+    let hello_phnum = u16::from_le_bytes(hello[56..58].try_into().unwrap()) as usize;
+    let first_load = (0..hello_phnum)
+        .map(|i| ph_offset + i * 56)
+        .find(|&ph| u32::from_le_bytes(hello[ph..ph + 4].try_into().unwrap()) == PT_LOAD)
+        .unwrap();
+    bad_segment[first_load + 16..first_load + 24]
+        .copy_from_slice(&0xffff_ffff_ffff_d000u64.to_le_bytes());
+    write_file("/tmp/bad_segment\0", &bad_segment);
+    assert_eq!(try_exec("/tmp/bad_segment\0"), -ENOEXEC);
+
+    // a valid copy still runs
+    write_file("/tmp/hello_copy\0", &hello);
+    assert_eq!(try_exec("/tmp/hello_copy\0"), 0);
+
+    for path in [
+        "/tmp/not_elf\0",
+        "/tmp/truncated_elf\0",
+        "/tmp/wrong_machine\0",
+        "/tmp/wrong_class\0",
+        "/tmp/bad_segment\0",
+        "/tmp/hello_copy\0",
+    ] {
+        assert_eq!(unlink(path), 0);
+    }
+
+    println!("elf_test passed!");
+    0
+}
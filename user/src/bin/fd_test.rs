@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::api::{close, dup, read, write};
+
+const EBADF: isize = 9;
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nfd_test APP running...\n");
+
+    // bad descriptors are errors, not kernel panics
+    assert_eq!(write(42, b"lost\n"), -EBADF);
+    assert_eq!(read(1, &mut [0u8; 1]), -EBADF);
+    assert_eq!(close(42), -EBADF);
+
+    // dup stdout to the lowest free fd
+    let fd = dup(1);
+    assert_eq!(fd, 3);
+    let msg = b"written by the dup of stdout\n";
+    assert_eq!(write(fd as usize, msg), msg.len() as isize);
+
+    // after closing, the fd can not be used but it's free for the next dup
+    assert_eq!(close(fd as usize), 0);
+    assert_eq!(write(fd as usize, msg), -EBADF);
+    assert_eq!(dup(2), fd);
+    assert_eq!(close(fd as usize), 0);
+
+    println!("fd_test passed!");
+    0
+}
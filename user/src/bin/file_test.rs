@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::api::{close, open, read, write, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY};
+
+const ENOENT: isize = 2;
+const EBADF: isize = 9;
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nfile_test APP running...\n");
+
+    assert_eq!(open("file_test_not_exist\0", O_RDONLY), -ENOENT);
+
+    // create and write
+    let msg = b"Hello, kfc-fs!";
+    let fd = open("file_test_tmp\0", O_CREAT | O_WRONLY | O_TRUNC);
+    assert!(fd > 2, "failed to create the file");
+    let fd = fd as usize;
+    assert_eq!(read(fd, &mut [0u8; 1]), -EBADF);
+    assert_eq!(write(fd, msg), msg.len() as isize);
+    assert_eq!(write(fd, msg), msg.len() as isize);
+    close(fd);
+
+    // read it back
+    let fd = open("/file_test_tmp\0", O_RDONLY) as usize;
+    let mut buf = [0u8; 64];
+    assert_eq!(read(fd, &mut buf), 2 * msg.len() as isize);
+    assert_eq!(&buf[..msg.len()], msg);
+    assert_eq!(&buf[msg.len()..2 * msg.len()], msg);
+    assert_eq!(read(fd, &mut buf), 0);
+    assert_eq!(write(fd, msg), -EBADF);
+    close(fd);
+
+    // truncate it
+    let fd = open("file_test_tmp\0", O_RDWR | O_TRUNC) as usize;
+    assert_eq!(read(fd, &mut buf), 0);
+    close(fd);
+
+    // apps are files too
+    let fd = open("hello\0", O_RDONLY);
+    assert!(fd > 2, "failed to open the app");
+    assert_eq!(read(fd as usize, &mut buf[..4]), 4);
+    assert_eq!(&buf[..4], b"\x7fELF");
+    close(fd as usize);
+
+    println!("file_test passed!");
+    0
+}
@@ -16,7 +16,7 @@ pub fn main() -> i32 {
             "pid {}: forked child start execing hello app ... ",
             getpid()
         );
-        exec("hello");
+        exec("hello\0");
         100
     } else {
         // parent process
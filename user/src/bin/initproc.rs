@@ -10,7 +10,7 @@ extern crate user_lib;
 pub fn main() -> isize {
     if fork() == 0 {
         // init process only fork and exec "shell"
-        exec("shell");
+        exec("shell\0");
     } else {
         loop {
             let mut exit_code: i32 = 0;
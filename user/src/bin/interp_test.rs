@@ -0,0 +1,119 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{
+    api::{
+        close, execve, exit, fork, open, read, unlink, waitpid, write, O_CREAT, O_RDONLY, O_TRUNC,
+        O_WRONLY,
+    },
+    args,
+    env::{getauxval, AT_BASE, AT_ENTRY},
+};
+
+const ENOENT: isize = 2;
+const PT_LOAD: u32 = 1;
+const PT_INTERP: u32 = 3;
+const PT_PHDR: u32 = 6;
+
+// the load base of interpreters chosen by the kernel
+const INTERP_BASE: usize = 0x2000_0000;
+
+fn read_file(path: &str) -> Vec<u8> {
+    let fd = open(path, O_RDONLY);
+    assert!(fd > 2, "failed to open the file");
+    let mut content = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        content.extend_from_slice(&buf[..len as usize]);
+    }
+    close(fd as usize);
+    content
+}
+
+fn write_file(path: &str, data: &[u8]) {
+    let fd = open(path, O_CREAT | O_WRONLY | O_TRUNC);
+    assert!(fd > 2, "failed to create the file");
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+}
+
+/// a copy of the ELF file with an unused program header turned into PT_INTERP
+fn with_interp(elf: &[u8], interp: &str) -> Vec<u8> {
+    let mut elf = Vec::from(elf);
+    let ph_offset = u64::from_le_bytes(elf[32..40].try_into().unwrap()) as usize;
+    let phnum = u16::from_le_bytes(elf[56..58].try_into().unwrap()) as usize;
+    let ph = (0..phnum)
+        .map(|i| ph_offset + i * 56)
+        .find(|&ph| {
+            let p_type = u32::from_le_bytes(elf[ph..ph + 4].try_into().unwrap());
+            p_type != PT_LOAD && p_type != PT_PHDR
+        })
+        .expect("no program header to replace");
+    let offset = elf.len() as u64;
+    elf[ph..ph + 4].copy_from_slice(&PT_INTERP.to_le_bytes());
+    elf[ph + 8..ph + 16].copy_from_slice(&offset.to_le_bytes());
+    elf[ph + 32..ph + 40].copy_from_slice(&(interp.len() as u64).to_le_bytes());
+    elf.extend_from_slice(interp.as_bytes());
+    elf
+}
+
+/// run the program in a child, return the exit code or the result of exec
+fn run(argv: &[&str]) -> isize {
+    let pid = fork();
+    if pid == 0 {
+        let mut ptrs: Vec<*const u8> = argv.iter().map(|arg| arg.as_ptr()).collect();
+        ptrs.push(core::ptr::null());
+        exit(execve(argv[0], &ptrs, &[core::ptr::null()]) as i32);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    exit_code as isize
+}
+
+/// started by the dynamic loader
+fn check_interp() -> i32 {
+    assert_eq!(getauxval(AT_BASE), Some(INTERP_BASE));
+    assert_eq!(getauxval(AT_ENTRY), Some(user_lib::_start as usize));
+    println!("interp_test : started by the dynamic loader");
+    0
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    if args().nth(1) == Some("child") {
+        return check_interp();
+    }
+    println!("\ninterp_test APP running...\n");
+    assert_eq!(getauxval(AT_BASE), None);
+
+    // the loader checks the shared library
+    assert_eq!(run(&["ld\0", "libuser.so\0"]), 0);
+
+    let this = read_file("/interp_test\0");
+    write_file("/tmp/interp_dyn\0", &with_interp(&this, "/ld\0"));
+    assert_eq!(run(&["/tmp/interp_dyn\0", "child\0"]), 0);
+
+    let hello = read_file("/hello\0");
+    write_file("/tmp/hello_dyn\0", &with_interp(&hello, "/ld\0"));
+    assert_eq!(run(&["/tmp/hello_dyn\0"]), 0);
+
+    // the interpreter should exist
+    write_file("/tmp/no_interp\0", &with_interp(&hello, "/no_ld\0"));
+    assert_eq!(run(&["/tmp/no_interp\0"]), -ENOENT);
+
+    for path in ["/tmp/interp_dyn\0", "/tmp/hello_dyn\0", "/tmp/no_interp\0"] {
+        assert_eq!(unlink(path), 0);
+    }
+
+    println!("interp_test passed!");
+    0
+}
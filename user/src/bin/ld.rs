@@ -0,0 +1,533 @@
+//! ### a minimal dynamic loader
+//! - as the interpreter (`PT_INTERP`) of a program : load the libraries in `DT_NEEDED`
+//!   from "/lib" or "/", relocate the libraries and the program, then jump to AT_ENTRY
+//! - the libraries are mapped from their files, the processes share the pages not written
+//! - run directly as `ld <library>...` : load and relocate the libraries only, to check them
+//! - the loader itself is a static PIE relocated by the kernel
+//! - relocations : R_RISCV_RELATIVE, R_RISCV_64 and R_RISCV_JUMP_SLOT, symbols are
+//!   searched in the program first, then in the libraries in load order
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{arch::asm, mem::size_of, ptr, slice, str};
+use user_lib::{
+    api::{
+        close, mmap, mmap_file, mprotect, munmap, open, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE,
+        O_RDONLY, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE,
+    },
+    args,
+    env::{getauxval, initial_args, AT_BASE, AT_ENTRY, AT_PHDR, AT_PHNUM},
+};
+
+const PAGE_SIZE: usize = 0x1000;
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ET_DYN: u16 = 3;
+const EM_RISCV: u16 = 243;
+const ELF64_PHDR_SIZE: usize = 56;
+const ELF64_SYM_SIZE: usize = 24;
+const ELF64_RELA_SIZE: usize = 24;
+
+// the types of program headers
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_PHDR: u32 = 6;
+
+// the flags of program headers
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+// the tags of the dynamic section
+const DT_NULL: usize = 0;
+const DT_NEEDED: usize = 1;
+const DT_PLTRELSZ: usize = 2;
+const DT_HASH: usize = 4;
+const DT_STRTAB: usize = 5;
+const DT_SYMTAB: usize = 6;
+const DT_RELA: usize = 7;
+const DT_RELASZ: usize = 8;
+const DT_JMPREL: usize = 23;
+const DT_GNU_HASH: usize = 0x6fff_fef5;
+
+// the types of relocations
+const R_RISCV_NONE: u32 = 0;
+const R_RISCV_64: u32 = 2;
+const R_RISCV_RELATIVE: u32 = 3;
+const R_RISCV_JUMP_SLOT: u32 = 5;
+
+const SHN_UNDEF: u16 = 0;
+const STB_LOCAL: u8 = 0;
+const STB_WEAK: u8 = 2;
+
+/// a loadable segment : (start, end, flags), biased
+type Segment = (usize, usize, u32);
+/// a loadable segment in the file : (vaddr, mem_size, offset, file_size, flags)
+type Load = (usize, usize, usize, usize, u32);
+
+/// a loaded ELF image, the program or a library
+struct Image {
+    name: String,
+    bias: usize,
+    segments: Vec<Segment>,
+    /// the offsets of the library names in the string table
+    needed: Vec<usize>,
+    strtab: usize,
+    symtab: usize,
+    sym_count: usize,
+    /// (address, size) of `.rela.dyn` and `.rela.plt`
+    relas: [(usize, usize); 2],
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> usize {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()) as usize
+}
+
+fn read_word(addr: usize) -> usize {
+    unsafe { ptr::read_unaligned(addr as *const usize) }
+}
+
+/// a string ending with '\0' in the memory
+fn c_str(addr: usize) -> &'static str {
+    let ptr = addr as *const u8;
+    let mut len = 0;
+    unsafe {
+        while *ptr.add(len) != 0 {
+            len += 1;
+        }
+        str::from_utf8(slice::from_raw_parts(ptr, len)).unwrap_or("?")
+    }
+}
+
+fn prot_of(flags: u32) -> usize {
+    let mut prot = 0;
+    if flags & PF_R != 0 {
+        prot |= PROT_READ;
+    }
+    if flags & PF_W != 0 {
+        prot |= PROT_WRITE;
+    }
+    if flags & PF_X != 0 {
+        prot |= PROT_EXEC;
+    }
+    prot
+}
+
+fn page_range(start: usize, end: usize) -> (usize, usize) {
+    let start = start & !(PAGE_SIZE - 1);
+    let end = (end + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    (start, end - start)
+}
+
+/// the number of symbols in the dynamic symbol table, from the hash tables
+fn symbol_count(hash: Option<usize>, gnu_hash: Option<usize>) -> usize {
+    if let Some(hash) = hash {
+        // nbucket, nchain : nchain is the number of symbols
+        return unsafe { ptr::read_unaligned((hash + 4) as *const u32) } as usize;
+    }
+    let gnu_hash = match gnu_hash {
+        Some(gnu_hash) => gnu_hash,
+        None => return 0,
+    };
+    let word = |i: usize| unsafe { ptr::read_unaligned((gnu_hash + i * 4) as *const u32) };
+    let (nbuckets, symoffset, bloom_size) = (word(0) as usize, word(1) as usize, word(2) as usize);
+    let buckets = gnu_hash + 16 + bloom_size * size_of::<usize>();
+    let bucket = |i: usize| unsafe { ptr::read_unaligned((buckets + i * 4) as *const u32) };
+    let last = match (0..nbuckets).map(bucket).max() {
+        Some(last) if last as usize >= symoffset => last as usize,
+        _ => return symoffset,
+    };
+    // walk the chain of the last bucket until the end marker
+    let chains = buckets + nbuckets * 4;
+    let mut index = last;
+    while unsafe { ptr::read_unaligned((chains + (index - symoffset) * 4) as *const u32) } & 1 == 0
+    {
+        index += 1;
+    }
+    index + 1
+}
+
+impl Image {
+    /// parse the dynamic section of a loaded image
+    fn new(name: String, bias: usize, segments: Vec<Segment>, dynamic: Option<usize>) -> Self {
+        let mut image = Image {
+            name,
+            bias,
+            segments,
+            needed: Vec::new(),
+            strtab: 0,
+            symtab: 0,
+            sym_count: 0,
+            relas: [(0, 0); 2],
+        };
+        let dynamic = match dynamic {
+            Some(dynamic) => dynamic,
+            None => return image,
+        };
+        let (mut hash, mut gnu_hash) = (None, None);
+        for i in 0.. {
+            let entry = dynamic + i * 2 * size_of::<usize>();
+            let (tag, value) = (read_word(entry), read_word(entry + size_of::<usize>()));
+            match tag {
+                DT_NULL => break,
+                DT_NEEDED => image.needed.push(value),
+                DT_STRTAB => image.strtab = bias + value,
+                DT_SYMTAB => image.symtab = bias + value,
+                DT_HASH => hash = Some(bias + value),
+                DT_GNU_HASH => gnu_hash = Some(bias + value),
+                DT_RELA => image.relas[0].0 = bias + value,
+                DT_RELASZ => image.relas[0].1 = value,
+                DT_JMPREL => image.relas[1].0 = bias + value,
+                DT_PLTRELSZ => image.relas[1].1 = value,
+                _ => {}
+            }
+        }
+        image.sym_count = symbol_count(hash, gnu_hash);
+        image
+    }
+
+    /// the program loaded by the kernel, found by AT_PHDR
+    fn from_auxv() -> Self {
+        let phdr = getauxval(AT_PHDR).expect("no AT_PHDR");
+        let phnum = getauxval(AT_PHNUM).expect("no AT_PHNUM");
+        let phdrs = unsafe { slice::from_raw_parts(phdr as *const u8, phnum * ELF64_PHDR_SIZE) };
+        let headers = (0..phnum).map(|i| i * ELF64_PHDR_SIZE);
+        // ET_EXEC without PT_PHDR is loaded as linked
+        let bias = headers
+            .clone()
+            .find(|&ph| read_u32(phdrs, ph) == PT_PHDR)
+            .map_or(0, |ph| phdr - read_u64(phdrs, ph + 16));
+        let mut segments = Vec::new();
+        let mut dynamic = None;
+        for ph in headers {
+            let vaddr = bias + read_u64(phdrs, ph + 16);
+            match read_u32(phdrs, ph) {
+                PT_LOAD => {
+                    let end = vaddr + read_u64(phdrs, ph + 40);
+                    segments.push((vaddr, end, read_u32(phdrs, ph + 4)));
+                }
+                PT_DYNAMIC => dynamic = Some(vaddr),
+                _ => {}
+            }
+        }
+        Image::new("program".into(), bias, segments, dynamic)
+    }
+
+    /// ### map a shared library from its file
+    /// the segments are private file mappings, so the pages never written (the code
+    /// and read-only data) are shared with the other processes using the library
+    fn load(name: &str) -> Result<Self, String> {
+        let fd = [format!("/lib/{}\0", name), format!("/{}\0", name)]
+            .iter()
+            .map(|path| open(path, O_RDONLY))
+            .find(|&fd| fd >= 0)
+            .ok_or(format!("{} : not found", name))? as usize;
+        let image = Self::map_file(name, fd);
+        // the mappings stay after the file is closed
+        close(fd);
+        image
+    }
+
+    /// (vaddr, mem_size, offset, file_size, flags) of the loadable segments, and the
+    /// vaddr of the dynamic section, from the headers in the first page
+    fn read_headers(name: &str, page: &[u8]) -> Result<(Vec<Load>, Option<usize>), String> {
+        if page[..4] != ELF_MAGIC || read_u16(page, 16) != ET_DYN || read_u16(page, 18) != EM_RISCV
+        {
+            return Err(format!("{} : not a RISC-V shared library", name));
+        }
+        let (ph_offset, phnum) = (read_u64(page, 32), read_u16(page, 56) as usize);
+        if ph_offset + phnum * ELF64_PHDR_SIZE > page.len() {
+            return Err(format!("{} : program headers out of the first page", name));
+        }
+        let headers = (0..phnum).map(|i| ph_offset + i * ELF64_PHDR_SIZE);
+        let loads = headers
+            .clone()
+            .filter(|&ph| read_u32(page, ph) == PT_LOAD)
+            .map(|ph| {
+                (
+                    read_u64(page, ph + 16),
+                    read_u64(page, ph + 40),
+                    read_u64(page, ph + 8),
+                    read_u64(page, ph + 32),
+                    read_u32(page, ph + 4),
+                )
+            })
+            .collect();
+        let dynamic = headers
+            .filter(|&ph| read_u32(page, ph) == PT_DYNAMIC)
+            .map(|ph| read_u64(page, ph + 16))
+            .next();
+        Ok((loads, dynamic))
+    }
+
+    /// ### map the loadable segments of the opened library
+    /// - the space is reserved as a whole first, then each segment is mapped over it
+    /// - the file's bytes after a segment in its last page are cleared as .bss,
+    ///   the other pages of .bss are anonymous
+    fn map_file(name: &str, fd: usize) -> Result<Self, String> {
+        // the headers are read through a temporary mapping
+        let header = mmap_file(0, PAGE_SIZE, PROT_READ, MAP_PRIVATE, fd, 0);
+        if header < 0 {
+            return Err(format!("{} : failed to map", name));
+        }
+        let page = unsafe { slice::from_raw_parts(header as *const u8, PAGE_SIZE) };
+        let headers = Self::read_headers(name, page);
+        munmap(header as usize, PAGE_SIZE);
+        let (loads, dynamic) = headers?;
+
+        if loads
+            .iter()
+            .any(|&(vaddr, mem_size, offset, file_size, _)| {
+                file_size > mem_size || (vaddr - offset) % PAGE_SIZE != 0
+            })
+        {
+            return Err(format!("{} : malformed segments", name));
+        }
+        let low = loads
+            .iter()
+            .map(|load| load.0)
+            .min()
+            .ok_or(format!("{} : no segment", name))?;
+        let high = loads.iter().map(|load| load.0 + load.1).max().unwrap();
+        let (low, len) = page_range(low, high);
+
+        let base = mmap(0, len, PROT_NONE, MAP_PRIVATE | MAP_ANONYMOUS);
+        if base < 0 {
+            return Err(format!("{} : out of memory", name));
+        }
+        let bias = base as usize - low;
+        let mut segments = Vec::new();
+        for &(vaddr, mem_size, offset, file_size, flags) in &loads {
+            let (start, end) = (bias + vaddr, bias + vaddr + mem_size);
+            let file_end = start + file_size;
+            let (map_start, file_len) = page_range(start, file_end);
+            let map_end = map_start + file_len;
+            // .bss in the last page of the file is cleared before relocating
+            let prot = prot_of(flags)
+                | if end > file_end {
+                    PROT_READ | PROT_WRITE
+                } else {
+                    0
+                };
+            let fixed = MAP_PRIVATE | MAP_FIXED;
+            if file_len > 0
+                && mmap_file(
+                    map_start,
+                    file_len,
+                    prot,
+                    fixed,
+                    fd,
+                    offset & !(PAGE_SIZE - 1),
+                ) != map_start as isize
+            {
+                return Err(format!("{} : failed to map a segment", name));
+            }
+            if end > file_end {
+                let zero_end = end.min(map_end);
+                unsafe { ptr::write_bytes(file_end as *mut u8, 0, zero_end - file_end) };
+            }
+            let (_, mem_len) = page_range(map_start, end);
+            if map_start + mem_len > map_end
+                && mmap(
+                    map_end,
+                    map_start + mem_len - map_end,
+                    prot,
+                    fixed | MAP_ANONYMOUS,
+                ) != map_end as isize
+            {
+                return Err(format!("{} : out of memory", name));
+            }
+            segments.push((start, end, flags));
+        }
+        Ok(Image::new(
+            name.into(),
+            bias,
+            segments,
+            dynamic.map(|dynamic| bias + dynamic),
+        ))
+    }
+
+    /// (name, binding, section index, value) of the symbol at `index`
+    fn symbol(&self, index: usize) -> (&'static str, u8, u16, usize) {
+        let sym = self.symtab + index * ELF64_SYM_SIZE;
+        let name = unsafe { ptr::read_unaligned(sym as *const u32) } as usize;
+        let info = unsafe { *((sym + 4) as *const u8) };
+        let shndx = unsafe { ptr::read_unaligned((sym + 6) as *const u16) };
+        (
+            c_str(self.strtab + name),
+            info >> 4,
+            shndx,
+            read_word(sym + 8),
+        )
+    }
+
+    /// the address of a defined global symbol
+    fn lookup(&self, name: &str) -> Option<usize> {
+        (1..self.sym_count)
+            .map(|i| self.symbol(i))
+            .find(|&(sym_name, binding, shndx, _)| {
+                shndx != SHN_UNDEF && binding != STB_LOCAL && sym_name == name
+            })
+            .map(|(_, _, _, value)| self.bias + value)
+    }
+
+    /// the address of the symbol at `index` of this image, searched in `scope`
+    fn resolve(&self, index: usize, scope: &[Image]) -> Result<usize, String> {
+        let (name, binding, shndx, value) = self.symbol(index);
+        if shndx != SHN_UNDEF && binding == STB_LOCAL {
+            return Ok(self.bias + value);
+        }
+        match scope.iter().find_map(|image| image.lookup(name)) {
+            Some(addr) => Ok(addr),
+            None if binding == STB_WEAK => Ok(0),
+            None => Err(format!("{} : undefined symbol {}", self.name, name)),
+        }
+    }
+
+    /// apply the relocations, the segments are writable meanwhile
+    /// - return the number of the relocations applied
+    fn relocate(&self, scope: &[Image]) -> Result<usize, String> {
+        for &(start, end, flags) in &self.segments {
+            let (start, len) = page_range(start, end);
+            mprotect(start, len, prot_of(flags) | PROT_READ | PROT_WRITE);
+        }
+        let mut count = 0;
+        for &(table, size) in &self.relas {
+            for i in 0..size / ELF64_RELA_SIZE {
+                let rela = table + i * ELF64_RELA_SIZE;
+                let (offset, info, addend) =
+                    (read_word(rela), read_word(rela + 8), read_word(rela + 16));
+                let value = match info as u32 {
+                    R_RISCV_NONE => continue,
+                    R_RISCV_RELATIVE => self.bias.wrapping_add(addend),
+                    R_RISCV_64 | R_RISCV_JUMP_SLOT => {
+                        self.resolve(info >> 32, scope)?.wrapping_add(addend)
+                    }
+                    r_type => {
+                        return Err(format!("{} : unsupported relocation {}", self.name, r_type))
+                    }
+                };
+                unsafe { ptr::write_unaligned((self.bias + offset) as *mut usize, value) };
+                count += 1;
+            }
+        }
+        for &(start, end, flags) in &self.segments {
+            let (start, len) = page_range(start, end);
+            mprotect(start, len, prot_of(flags));
+        }
+        Ok(count)
+    }
+}
+
+/// load the libraries needed by `images` and by the libraries themselves, each once
+fn load_needed(images: &mut Vec<Image>) -> Result<(), String> {
+    let mut i = 0;
+    while i < images.len() {
+        let names: Vec<String> = images[i]
+            .needed
+            .iter()
+            .map(|&name| c_str(images[i].strtab + name).to_string())
+            .collect();
+        for name in names {
+            if !images.iter().any(|image| image.name == name) {
+                images.push(Image::load(&name)?);
+            }
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// relocate the libraries before the program, all of them in the same scope
+fn relocate_all(images: &[Image]) -> Result<usize, String> {
+    let mut count = 0;
+    for image in images.iter().rev() {
+        count += image.relocate(images)?;
+    }
+    Ok(count)
+}
+
+/// start the program with the initial stack from the kernel
+fn start_program(entry: usize) -> ! {
+    let (argc, argv) = initial_args();
+    // sp points to argc, just below argv
+    let sp = argv - size_of::<usize>();
+    unsafe {
+        asm!(
+            "mv sp, {sp}",
+            "jr {entry}",
+            sp = in(reg) sp,
+            entry = in(reg) entry,
+            in("a0") argc,
+            in("a1") argv,
+            options(noreturn)
+        )
+    }
+}
+
+/// run directly : check the libraries given by the arguments
+fn check_libraries() -> i32 {
+    let names: Vec<&str> = args().skip(1).collect();
+    if names.is_empty() {
+        println!("usage : ld <library>...");
+        return -1;
+    }
+    let mut images = Vec::new();
+    for name in names {
+        match Image::load(name) {
+            Ok(image) => images.push(image),
+            Err(err) => {
+                println!("ld : {}", err);
+                return -1;
+            }
+        }
+    }
+    let result = load_needed(&mut images).and_then(|_| relocate_all(&images));
+    match result {
+        Ok(count) => {
+            for image in &images {
+                println!(
+                    "{} : loaded at {:#x}, {} symbols",
+                    image.name, image.bias, image.sym_count
+                );
+            }
+            println!("{} relocations applied", count);
+            0
+        }
+        Err(err) => {
+            println!("ld : {}", err);
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    // AT_BASE is only passed to an interpreter
+    if getauxval(AT_BASE).is_none() {
+        return check_libraries();
+    }
+    let mut images = Vec::from([Image::from_auxv()]);
+    if let Err(err) = load_needed(&mut images).and_then(|_| relocate_all(&images)) {
+        println!("ld : {}", err);
+        return -1;
+    }
+    start_program(getauxval(AT_ENTRY).expect("no AT_ENTRY"))
+}
@@ -0,0 +1,223 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::api::{
+    close, exit, fork, mmap, mmap_file, mprotect, munmap, open, read, unlink, waitpid, write,
+    MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, O_CREAT, O_RDONLY, O_TRUNC, O_WRONLY, PROT_NONE,
+    PROT_READ, PROT_WRITE,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+/// the end of the user addresses in Sv39
+const USER_SPACE_END: usize = 0x40_0000_0000;
+const EBADF: isize = 9;
+const ENOMEM: isize = 12;
+const EACCES: isize = 13;
+const EFAULT: isize = 14;
+const EINVAL: isize = 22;
+
+/// a file of 2 pages and a half, each byte is its offset in pages plus `seed`
+fn write_pages(path: &str, seed: u8) {
+    let fd = open(path, O_CREAT | O_WRONLY | O_TRUNC);
+    assert!(fd > 2, "failed to create the file");
+    let mut page = [0u8; PAGE_SIZE];
+    for i in 0..3 {
+        page.fill(i as u8 + seed);
+        let len = if i == 2 { PAGE_SIZE / 2 } else { PAGE_SIZE };
+        assert_eq!(write(fd as usize, &page[..len]), len as isize);
+    }
+    close(fd as usize);
+}
+
+/// map the file read-only, the pages should be the same as written
+fn map_pages(path: &str, seed: u8) -> usize {
+    let fd = open(path, O_RDONLY);
+    assert!(fd > 2, "failed to open the file");
+    let start = mmap_file(0, 3 * PAGE_SIZE, PROT_READ, MAP_PRIVATE, fd as usize, 0);
+    assert!(start > 0, "mmap of a file failed");
+    // the mapping stays after the file is closed
+    close(fd as usize);
+    let start = start as usize;
+    for i in 0..3 {
+        let byte = unsafe { ((start + i * PAGE_SIZE) as *const u8).read_volatile() };
+        assert_eq!(byte, i as u8 + seed);
+    }
+    // beyond the end of the file
+    let byte = unsafe { ((start + 3 * PAGE_SIZE - 1) as *const u8).read_volatile() };
+    assert_eq!(byte, 0);
+    start
+}
+
+/// private mappings of a file : shared until written, never written back
+fn file_mapping() {
+    let path = "/tmp/mmap_test\0";
+    write_pages(path, 1);
+    let first = map_pages(path, 1);
+    let second = map_pages(path, 1);
+
+    // the written page is copied, the others and the file don't change
+    assert_eq!(
+        mprotect(second + PAGE_SIZE, PAGE_SIZE, PROT_READ | PROT_WRITE),
+        0
+    );
+    unsafe { ((second + PAGE_SIZE) as *mut u8).write_volatile(0xcd) };
+    assert_eq!(
+        unsafe { ((first + PAGE_SIZE) as *const u8).read_volatile() },
+        2
+    );
+    let third = map_pages(path, 1);
+
+    // the kernel doesn't write to a read-only mapping
+    let fd = open(path, O_RDONLY) as usize;
+    let buf = unsafe { core::slice::from_raw_parts_mut(first as *mut u8, 16) };
+    assert_eq!(read(fd, buf), -EFAULT);
+    assert_eq!(
+        mmap_file(0, PAGE_SIZE, PROT_READ, MAP_PRIVATE, fd, 1),
+        -EINVAL
+    );
+    close(fd);
+    let fd = open(path, O_WRONLY) as usize;
+    assert_eq!(
+        mmap_file(0, PAGE_SIZE, PROT_READ, MAP_PRIVATE, fd, 0),
+        -EACCES
+    );
+    close(fd);
+    assert_eq!(
+        mmap_file(0, PAGE_SIZE, PROT_READ, MAP_PRIVATE, fd, 0),
+        -EBADF
+    );
+
+    // the mappings made after writing the file see the new content
+    write_pages(path, 7);
+    let fourth = map_pages(path, 7);
+    assert_eq!(unsafe { (first as *const u8).read_volatile() }, 1);
+
+    for start in [first, second, third, fourth] {
+        assert_eq!(munmap(start, 3 * PAGE_SIZE), 0);
+    }
+    assert_eq!(unlink(path), 0);
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nmmap_test APP running...\n");
+
+    // map 4 pages and touch all of them
+    let len = 4 * PAGE_SIZE;
+    let start = mmap(0, len, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS);
+    assert!(start > 0, "mmap failed");
+    let start = start as usize;
+    for i in 0..len {
+        unsafe { ((start + i) as *mut u8).write_volatile((i % 256) as u8) };
+    }
+    for i in 0..len {
+        assert_eq!(
+            unsafe { ((start + i) as *const u8).read_volatile() },
+            (i % 256) as u8
+        );
+    }
+    println!("mmap {:#x} bytes at {:#x} OK", len, start);
+
+    // unmap the second page : the area is split into two parts
+    assert_eq!(munmap(start + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(
+        unsafe { ((start + 2 * PAGE_SIZE) as *const u8).read_volatile() },
+        0
+    );
+    println!("munmap the second page OK");
+
+    // the third page becomes read-only, writing to it should be killed
+    assert_eq!(mprotect(start + 2 * PAGE_SIZE, PAGE_SIZE, PROT_READ), 0);
+    let pid = fork();
+    if pid == 0 {
+        unsafe { ((start + 2 * PAGE_SIZE) as *mut u8).write_volatile(1) };
+        panic!("should be killed by the kernel!");
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -1);
+    println!("mprotect the third page OK");
+
+    // the read-only page is shared with the child, it gets its own copy after mprotect
+    let byte = (start + 2 * PAGE_SIZE + 1) as *mut u8;
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(
+            mprotect(start + 2 * PAGE_SIZE, PAGE_SIZE, PROT_READ | PROT_WRITE),
+            0
+        );
+        unsafe { byte.write_volatile(0xab) };
+        assert_eq!(unsafe { byte.read_volatile() }, 0xab);
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(unsafe { byte.read_volatile() }, 1);
+    println!("mprotect a shared page OK");
+
+    // PROT_NONE : no access, the data is kept
+    assert_eq!(mprotect(start + 3 * PAGE_SIZE, PAGE_SIZE, PROT_NONE), 0);
+    let pid = fork();
+    if pid == 0 {
+        unsafe { ((start + 3 * PAGE_SIZE) as *const u8).read_volatile() };
+        panic!("should be killed by the kernel!");
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -1);
+    assert_eq!(mprotect(start + 3 * PAGE_SIZE, PAGE_SIZE, PROT_READ), 0);
+    assert_eq!(
+        unsafe { ((start + 3 * PAGE_SIZE + 1) as *const u8).read_volatile() },
+        1
+    );
+    println!("mprotect PROT_NONE OK");
+
+    // the errors
+    assert_eq!(mprotect(start + 1, PAGE_SIZE, PROT_READ), -EINVAL);
+    assert_eq!(mprotect(start + PAGE_SIZE, PAGE_SIZE, PROT_READ), -ENOMEM);
+    assert_eq!(mmap(0, 0, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS), -EINVAL);
+
+    // nothing is mapped beyond the user space
+    let fixed = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED;
+    assert_eq!(
+        mmap(USER_SPACE_END - PAGE_SIZE, 2 * PAGE_SIZE, PROT_READ, fixed),
+        -ENOMEM
+    );
+    assert_eq!(munmap(USER_SPACE_END, PAGE_SIZE), -EINVAL);
+    let high = mmap(
+        USER_SPACE_END,
+        PAGE_SIZE,
+        PROT_READ,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+    );
+    assert!(high > 0 && (high as usize) < USER_SPACE_END);
+    assert_eq!(munmap(high as usize, PAGE_SIZE), 0);
+    // the end of the range wraps around
+    let huge = usize::MAX & !(PAGE_SIZE - 1);
+    assert_eq!(
+        mmap(0, huge, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS),
+        -ENOMEM
+    );
+    assert_eq!(mmap(PAGE_SIZE, huge, PROT_READ, fixed), -ENOMEM);
+    assert_eq!(munmap(start, huge), -EINVAL);
+    assert_eq!(mprotect(start, huge, PROT_READ), -ENOMEM);
+    println!("the range out of the user space OK");
+
+    // the unmapped page can be mapped again by the hint
+    let again = mmap(
+        start + PAGE_SIZE,
+        PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+    );
+    assert_eq!(again as usize, start + PAGE_SIZE);
+    assert_eq!(munmap(start, len), 0);
+
+    file_mapping();
+    println!("mmap of a file OK");
+
+    println!("mmap_test passed!");
+    0
+}
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::addr_of_mut;
+
+use user_lib::api::{exit, fork, waitpid};
+
+const PAGE_SIZE: usize = 0x1000;
+// more than the whole memory, the frames are bounded when first touched
+const HUGE: usize = 256 * 1024 * 1024;
+const ROUNDS: usize = 3;
+
+static mut BSS: [u8; HUGE] = [0; HUGE];
+
+/// touch the first `len` bytes of the huge .bss, a page at a time
+fn touch(len: usize) {
+    let start = unsafe { addr_of_mut!(BSS) as *mut u8 };
+    for offset in (0..len).step_by(PAGE_SIZE) {
+        unsafe { start.add(offset).write_volatile(1) };
+    }
+}
+
+/// run `f` in a child, return its exit code
+fn run(f: fn()) -> i32 {
+    let pid = fork();
+    assert!(pid >= 0, "fork failed");
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    exit_code
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\noom_test APP running...\n");
+
+    // the process is killed instead of the kernel panicking, again and again :
+    // the frames of a killed process are all back
+    for round in 0..ROUNDS {
+        assert_eq!(
+            run(|| {
+                touch(HUGE);
+                panic!("not killed");
+            }),
+            -1
+        );
+        println!("round {} : killed when running out of memory", round);
+    }
+
+    // there's still memory for a new process
+    assert_eq!(run(|| touch(64 * PAGE_SIZE)), 0);
+    println!("the memory is back after the kills");
+
+    println!("oom_test passed!");
+    0
+}
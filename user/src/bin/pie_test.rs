@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::env::{getauxval, AT_ENTRY, AT_PHDR};
+
+// the load base of PIE chosen by the kernel
+const PIE_BASE: usize = 0x1000_0000;
+
+fn add(a: usize, b: usize) -> usize {
+    a + b
+}
+
+fn mul(a: usize, b: usize) -> usize {
+    a * b
+}
+
+type BinaryOp = fn(usize, usize) -> usize;
+
+// pointers in static data, relocated by the kernel
+static OPS: [(&str, BinaryOp); 2] = [("add", add), ("mul", mul)];
+static GREETING: &str = "hello from static data";
+static ANSWER: usize = 42;
+static ANSWER_PTR: &usize = &ANSWER;
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\npie_test APP running...\n");
+
+    // always built as PIE by the Makefile
+    let start = user_lib::_start as usize;
+    println!("running as PIE, _start at {:#x}", start);
+    assert!(start >= PIE_BASE);
+    assert_eq!(getauxval(AT_ENTRY), Some(start));
+    assert!(getauxval(AT_PHDR).unwrap() >= PIE_BASE);
+
+    // the pointers in static data point to the loaded program, not to the link address
+    let relocated = |addr: usize| assert!(addr >= PIE_BASE, "{:#x} is not relocated", addr);
+    relocated(OPS[0].0.as_ptr() as usize);
+    relocated(OPS[0].1 as usize);
+    relocated(GREETING.as_ptr() as usize);
+    relocated(ANSWER_PTR as *const usize as usize);
+    assert_eq!(OPS[0].1 as usize, add as usize);
+    assert_eq!(OPS[1].1 as usize, mul as usize);
+    assert_eq!(ANSWER_PTR as *const usize, &ANSWER as *const usize);
+
+    assert_eq!(OPS[0].0, "add");
+    assert_eq!((OPS[0].1)(3, 4), 7);
+    assert_eq!(OPS[1].0, "mul");
+    assert_eq!((OPS[1].1)(3, 4), 12);
+    assert_eq!(GREETING.len(), 22);
+    println!("{}", GREETING);
+    assert_eq!(*ANSWER_PTR, 42);
+
+    println!("pie_test passed!");
+    0
+}
@@ -0,0 +1,104 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::api::{close, exit, fork, pipe, read, sleep, waitpid, write};
+
+const EPIPE: isize = 32;
+// the size of the kernel's pipe buffer
+const PIPE_BUFFER_SIZE: usize = 0x1000;
+// larger than the kernel's pipe buffer, so both sides have to wait
+const DATA_LEN: usize = 0x3000;
+
+fn data_at(i: usize) -> u8 {
+    (i * 7 % 251) as u8
+}
+
+/// writing to a pipe without readers fails with EPIPE, instead of blocking forever
+fn no_reader() {
+    let mut pipe_fd = [0i32; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let (read_end, write_end) = (pipe_fd[0] as usize, pipe_fd[1] as usize);
+
+    let pid = fork();
+    if pid == 0 {
+        // child : blocked on the full pipe until the reader is gone
+        close(read_end);
+        let buf = [0u8; 0x100];
+        for _ in 0..PIPE_BUFFER_SIZE / buf.len() {
+            assert_eq!(write(write_end, &buf), buf.len() as isize);
+        }
+        assert_eq!(write(write_end, &buf), -EPIPE);
+        close(write_end);
+        exit(0);
+    }
+    close(write_end);
+    // leave the child time to fill the pipe, then close the only read end
+    sleep(100);
+    close(read_end);
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // no reader from the start
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    close(pipe_fd[0] as usize);
+    assert_eq!(write(pipe_fd[1] as usize, b"lost"), -EPIPE);
+    close(pipe_fd[1] as usize);
+    println!("write to a pipe without readers : EPIPE");
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\npipe_test APP running...\n");
+
+    let mut pipe_fd = [0i32; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let (read_end, write_end) = (pipe_fd[0] as usize, pipe_fd[1] as usize);
+
+    let pid = fork();
+    if pid == 0 {
+        // child : the reader
+        close(write_end);
+        let mut buf = [0u8; 0x100];
+        let mut total = 0;
+        loop {
+            let len = read(read_end, &mut buf);
+            assert!(len >= 0, "read from pipe failed");
+            if len == 0 {
+                // EOF : the parent closed the write end
+                break;
+            }
+            for (i, &c) in buf[..len as usize].iter().enumerate() {
+                assert_eq!(c, data_at(total + i), "wrong data from pipe");
+            }
+            total += len as usize;
+        }
+        close(read_end);
+        assert_eq!(total, DATA_LEN);
+        println!("child read {:#x} bytes from pipe", total);
+        exit(0);
+    }
+
+    // parent : the writer
+    close(read_end);
+    let mut buf = [0u8; 0x100];
+    for start in (0..DATA_LEN).step_by(buf.len()) {
+        for (i, c) in buf.iter_mut().enumerate() {
+            *c = data_at(start + i);
+        }
+        assert_eq!(write(write_end, &buf), buf.len() as isize);
+    }
+    close(write_end);
+
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    no_reader();
+
+    println!("pipe_test passed!");
+    0
+}
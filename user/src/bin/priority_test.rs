@@ -0,0 +1,148 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr;
+use user_lib::api::{exec, exit, fork, get_priority, get_time, set_priority, wait, waitpid};
+
+const EINVAL: isize = 22;
+
+// (app, priority, iterations of the app)
+const APPS: [(&str, isize, usize); 3] = [
+    ("power_3\0", 3, 300000),
+    ("power_5\0", 5, 210000),
+    ("power_7\0", 7, 240000),
+];
+
+// the spinning tasks, alternately of LOW and HIGH priority
+// - more than the harts (at most 4), none of them can take a hart alone,
+//   so their shares of CPU follow the priorities
+const SPINNERS: usize = 8;
+const LOW: isize = 4;
+const HIGH: isize = 8;
+// the time the spinners run together, in ms
+const SPIN_TIME: isize = 1000;
+// the iterations of a round of work
+const ROUND: usize = 1000;
+
+/// spin between the times `begin` and `end` (ms), return the rounds of work done
+fn spin(begin: isize, end: isize) -> i32 {
+    while get_time() < begin {}
+    let mut rounds = 0;
+    let mut counter = 0usize;
+    while get_time() < end {
+        for _ in 0..ROUND {
+            unsafe { ptr::write_volatile(&mut counter, ptr::read_volatile(&counter) + 1) };
+        }
+        rounds += 1;
+    }
+    rounds
+}
+
+/// ### the shares of CPU of the spinners
+/// with the stride scheduler, those of HIGH priority should run about HIGH / LOW times
+/// as much as those of LOW priority
+fn cpu_shares() {
+    let begin = get_time() + 50;
+    let mut pids = [0; SPINNERS];
+    for (i, pid) in pids.iter_mut().enumerate() {
+        let priority = if i % 2 == 0 { LOW } else { HIGH };
+        *pid = fork();
+        if *pid == 0 {
+            assert_eq!(set_priority(priority), priority);
+            exit(spin(begin, begin + SPIN_TIME));
+        }
+    }
+    let (mut low, mut high) = (0, 0);
+    for (i, &pid) in pids.iter().enumerate() {
+        let mut rounds = 0;
+        assert_eq!(waitpid(pid as usize, &mut rounds), pid);
+        if i % 2 == 0 {
+            low += rounds as usize;
+        } else {
+            high += rounds as usize;
+        }
+    }
+    println!(
+        "rounds of work : {} with priority {}, {} with priority {}",
+        low, LOW, high, HIGH
+    );
+    assert!(low > 0);
+    if cfg!(feature = "stride") {
+        // HIGH / LOW = 2, within 25%
+        let ratio = high * 100 / low;
+        assert!(
+            (150..=250).contains(&ratio),
+            "the shares are not proportional to the priorities : {}%",
+            ratio
+        );
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\npriority_test APP running...\n");
+
+    assert_eq!(set_priority(1), -EINVAL);
+    // the others would hardly run beside a task with a huge priority
+    assert_eq!(set_priority(1 << 20), -EINVAL);
+    assert_eq!(set_priority(1024), 1024);
+    assert_eq!(set_priority(8), 8);
+    assert_eq!(get_priority(), 8);
+
+    // inherited by fork
+    let pid = fork();
+    if pid == 0 {
+        exit(get_priority() as i32);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 8);
+
+    // run power_3/5/7 together, with the priority of 3/5/7, kept over exec
+    let start = get_time();
+    let mut pids = [0; 3];
+    for (i, (app, priority, _)) in APPS.iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            assert_eq!(set_priority(*priority), *priority);
+            exec(app);
+            exit(-1);
+        }
+        pids[i] = pid;
+    }
+
+    // the speed of each app : with the stride scheduler it's proportional to the priority
+    // until the first one exits
+    let mut speeds = [0; 3];
+    for _ in 0..APPS.len() {
+        let mut exit_code = 0;
+        let pid = wait(&mut exit_code);
+        assert_eq!(exit_code, 0);
+        let i = pids.iter().position(|&p| p == pid).unwrap();
+        let (app, priority, iterations) = APPS[i];
+        let time = (get_time() - start).max(1) as usize;
+        speeds[i] = iterations / time;
+        println!(
+            "{} : priority {}, exited after {} ms, {} iterations/ms",
+            app.trim_end_matches('\0'),
+            priority,
+            time,
+            speeds[i]
+        );
+    }
+    for (i, (app, priority, _)) in APPS.iter().enumerate() {
+        println!(
+            "{} : iterations/ms / priority = {}",
+            app.trim_end_matches('\0'),
+            speeds[i] / *priority as usize
+        );
+    }
+
+    cpu_shares();
+
+    println!("priority_test passed!");
+    0
+}
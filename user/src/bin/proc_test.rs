@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::{format, string::String};
+use user_lib::api::{
+    close, exit, fork, getpid, mkdir, open, read, waitpid, write, yield_, O_RDONLY, O_WRONLY,
+};
+
+const ENOENT: isize = 2;
+const EPERM: isize = 1;
+
+/// the whole content of a file
+fn read_file(path: &str) -> Option<String> {
+    let fd = open(path, O_RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut content = String::new();
+    let mut buf = [0u8; 128];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        content.push_str(core::str::from_utf8(&buf[..len as usize]).unwrap());
+    }
+    close(fd as usize);
+    Some(content)
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nproc_test APP running...\n");
+
+    let status = read_file("/proc/self/status\0").unwrap();
+    print!("{}", status);
+    assert!(status.contains("Name:\tproc_test"));
+    assert!(status.contains(&format!("Pid:\t{}\n", getpid())));
+    assert!(status.contains("State:\tRunning"));
+
+    let maps = read_file("/proc/self/maps\0").unwrap();
+    print!("{}", maps);
+    assert!(maps.lines().any(|line| line.contains("r-xu")));
+    assert!(maps.lines().any(|line| line.contains("rw-u")));
+
+    let meminfo = read_file("/proc/meminfo\0").unwrap();
+    print!("{}", meminfo);
+    assert!(meminfo.starts_with("FrameTotal:"));
+    assert!(meminfo.contains("HeapTotal:"));
+
+    let uptime = read_file("/proc/uptime\0").unwrap();
+    print!("uptime : {}", uptime);
+    assert!(uptime.trim_end().parse::<f64>().unwrap() > 0.0);
+
+    // the zombie child is listed until reaped
+    let pid = fork();
+    if pid == 0 {
+        exit(7);
+    }
+    let path = format!("/proc/{}/status\0", pid);
+    loop {
+        let status = read_file(&path).unwrap();
+        if status.contains("State:\tZombie") {
+            assert!(status.contains("ExitCode:\t7"));
+            break;
+        }
+        yield_();
+    }
+    let status = read_file("/proc/self/status\0").unwrap();
+    let children = status
+        .lines()
+        .find(|line| line.starts_with("Children:"))
+        .unwrap();
+    assert!(children
+        .split_whitespace()
+        .any(|child| child == format!("{}", pid)));
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(open(&path, O_RDONLY), -ENOENT);
+
+    // read-only
+    assert_eq!(mkdir("/proc/new_dir\0"), -EPERM);
+    let fd = open("/proc/uptime\0", O_WRONLY) as usize;
+    assert_eq!(write(fd, b"0"), 0);
+    close(fd);
+
+    println!("proc_test passed!");
+    0
+}
@@ -0,0 +1,95 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::api::{exit, fork, get_time, sleep, waitpid};
+
+// the spinning tasks
+// - more than the harts (at most 4), so they queue up for the CPU
+const SPINNERS: usize = 12;
+// the time the tasks run together, in ms
+const RUN_TIME: isize = 1000;
+// the time the interactive task sleeps each round, in ms
+const SLEEP_TIME: isize = 10;
+// a longer gap between two readings of the time : switched out
+const SWITCHED_OUT: isize = 2;
+
+/// ### spin between the times `begin` and `end` (ms)
+/// return the average time (ms) waiting for the CPU once switched out
+fn spinner(begin: isize, end: isize) -> i32 {
+    while get_time() < begin {}
+    let (mut waited, mut switches) = (0, 0);
+    let mut last = get_time();
+    while last < end {
+        let now = get_time();
+        if now - last >= SWITCHED_OUT {
+            waited += now - last;
+            switches += 1;
+        }
+        last = now;
+    }
+    (waited / switches.max(1)) as i32
+}
+
+/// ### sleep again and again between the times `begin` and `end` (ms)
+/// return the average time (ms) from the end of a sleep to running again
+fn interactive(begin: isize, end: isize) -> i32 {
+    while get_time() < begin {
+        sleep(1);
+    }
+    let (mut late, mut rounds) = (0, 0);
+    while get_time() < end {
+        let start = get_time();
+        sleep(SLEEP_TIME as usize);
+        late += get_time() - start - SLEEP_TIME;
+        rounds += 1;
+    }
+    (late / rounds.max(1)) as i32
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nresponse_test APP running...\n");
+
+    let begin = get_time() + 50;
+    let end = begin + RUN_TIME;
+    let mut spinners = [0; SPINNERS];
+    for pid in spinners.iter_mut() {
+        *pid = fork();
+        if *pid == 0 {
+            exit(spinner(begin, end));
+        }
+    }
+    let pid = fork();
+    if pid == 0 {
+        exit(interactive(begin, end));
+    }
+
+    let mut late = 0;
+    assert_eq!(waitpid(pid as usize, &mut late), pid);
+    let mut waited = 0;
+    for &pid in spinners.iter() {
+        let mut exit_code = 0;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        waited += exit_code as usize;
+    }
+    let waited = waited / SPINNERS;
+    println!(
+        "waiting for the CPU : {} ms after a sleep, {} ms for a spinner switched out",
+        late, waited
+    );
+    if cfg!(feature = "mlfq") {
+        // the interactive task stays in the highest level, ahead of the spinners
+        assert!(
+            (late as usize) < waited,
+            "the interactive task waits no less than the spinners : {} ms, {} ms",
+            late,
+            waited
+        );
+    }
+
+    println!("response_test passed!");
+    0
+}
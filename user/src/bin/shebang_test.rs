@@ -0,0 +1,90 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{
+    api::{close, execve, exit, fork, open, unlink, waitpid, write, O_CREAT, O_TRUNC, O_WRONLY},
+    args,
+};
+
+const ENOENT: isize = 2;
+const ENOEXEC: isize = 8;
+const ELOOP: isize = 40;
+
+fn write_file(path: &str, data: &[u8]) {
+    let fd = open(path, O_CREAT | O_WRONLY | O_TRUNC);
+    assert!(fd > 2, "failed to create the file");
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+}
+
+/// run the program in a child, return the exit code or the result of exec
+fn run(argv: &[&str]) -> isize {
+    let pid = fork();
+    if pid == 0 {
+        let mut ptrs: Vec<*const u8> = argv.iter().map(|arg| arg.as_ptr()).collect();
+        ptrs.push(core::ptr::null());
+        exit(execve(argv[0], &ptrs, &[core::ptr::null()]) as i32);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    exit_code as isize
+}
+
+/// run as the interpreter of a script : check argv, exit with argc
+fn interpret(argv: &[&str]) -> i32 {
+    println!("shebang_test : interpreting {:?}", argv);
+    assert_eq!(argv[0], "/shebang_test");
+    assert_eq!(argv[1], "interp");
+    match argv.len() {
+        // run.sh a b
+        5 if argv[3] == "a" => assert_eq!(&argv[2..], ["/tmp/run.sh", "a", "b"]),
+        // nested.sh x
+        5 => assert_eq!(&argv[2..], ["/tmp/run.sh", "/tmp/nested.sh", "x"]),
+        _ => return -1,
+    }
+    argv.len() as i32
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let argv: Vec<&str> = args().collect();
+    if argv.get(1) == Some(&"interp") {
+        return interpret(&argv);
+    }
+    println!("\nshebang_test APP running...\n");
+
+    write_file("/tmp/run.sh\0", b"#! /shebang_test interp \necho not run\n");
+    assert_eq!(run(&["/tmp/run.sh\0", "a\0", "b\0"]), 5);
+
+    // a script as the interpreter of a script
+    write_file("/tmp/nested.sh\0", b"#!/tmp/run.sh\n");
+    assert_eq!(run(&["/tmp/nested.sh\0", "x\0"]), 5);
+
+    // the depth of nesting is bounded
+    write_file("/tmp/loop.sh\0", b"#!/tmp/loop.sh\n");
+    assert_eq!(run(&["/tmp/loop.sh\0"]), -ELOOP);
+
+    write_file("/tmp/no_interp.sh\0", b"#!/not_exist\n");
+    assert_eq!(run(&["/tmp/no_interp.sh\0"]), -ENOENT);
+
+    write_file("/tmp/empty.sh\0", b"#!  \n");
+    assert_eq!(run(&["/tmp/empty.sh\0"]), -ENOEXEC);
+
+    for path in [
+        "/tmp/run.sh\0",
+        "/tmp/nested.sh\0",
+        "/tmp/loop.sh\0",
+        "/tmp/no_interp.sh\0",
+        "/tmp/empty.sh\0",
+    ] {
+        assert_eq!(unlink(path), 0);
+    }
+
+    println!("shebang_test passed!");
+    0
+}
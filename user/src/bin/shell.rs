@@ -1,9 +1,9 @@
 #![no_std]
 #![no_main]
 
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 use user_lib::{
-    api::{exec, fork, waitpid},
+    api::{execve, fork, waitpid},
     console::getchar,
 };
 
@@ -34,15 +34,22 @@ pub fn main() -> isize {
             LF | CR => {
                 println!("");
 
-                if line.is_empty() {
+                if line.trim().is_empty() {
+                    line.clear();
                     print!("{}", SHELL);
                     continue;
                 }
 
-                line.push('\0');
+                // the arguments are separated by spaces
+                let args: Vec<String> = line
+                    .split_whitespace()
+                    .map(|arg| String::from(arg) + "\0")
+                    .collect();
+                let mut argv: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+                argv.push(core::ptr::null());
                 let pid = fork();
                 if pid == 0 {
-                    if exec(line.as_str()) == -1 {
+                    if execve(args[0].as_str(), &argv, &[core::ptr::null()]) < 0 {
                         error!("App {} exec failed!", line);
                         return -4;
                     }
@@ -0,0 +1,129 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+use user_lib::{
+    api::{exit, futex_wait, futex_wake, getpid, sleep, thread_create, waittid, EAGAIN, ETIMEDOUT},
+    sync::{Condvar, Mutex, Semaphore},
+};
+
+const THREAD_NUM: usize = 4;
+const ADD_TIMES: usize = 1000;
+const ITEM_NUM: usize = 100;
+
+static FLAG: AtomicU32 = AtomicU32::new(0);
+static COUNTER: Mutex<usize> = Mutex::new(0);
+static QUEUE: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+static NOT_EMPTY: Condvar = Condvar::new();
+static SEM: Semaphore = Semaphore::new(0);
+
+/// block until FLAG is set
+fn flag_waiter(_arg: usize) -> ! {
+    while FLAG.load(Ordering::Acquire) == 0 {
+        futex_wait(&FLAG, 0, None);
+    }
+    exit(1);
+    unreachable!()
+}
+
+/// add to COUNTER without an atomic add
+fn adder(_arg: usize) -> ! {
+    for _ in 0..ADD_TIMES {
+        let mut counter = COUNTER.lock();
+        let value = *counter;
+        // switched out with the lock held sometimes
+        if value % 100 == 0 {
+            sleep(1);
+        }
+        *counter = value + 1;
+    }
+    exit(0);
+    unreachable!()
+}
+
+fn producer(_arg: usize) -> ! {
+    for i in 0..ITEM_NUM {
+        QUEUE.lock().push(i);
+        NOT_EMPTY.notify_one();
+    }
+    exit(0);
+    unreachable!()
+}
+
+fn releaser(arg: usize) -> ! {
+    for _ in 0..arg {
+        sleep(5);
+        SEM.release();
+    }
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nsync_test APP running...\n");
+
+    // futex
+    let word = AtomicU32::new(1);
+    assert_eq!(futex_wait(&word, 0, None), -EAGAIN);
+    assert_eq!(futex_wait(&word, 1, Some(10)), -ETIMEDOUT);
+    // a timeout too long to count in ticks doesn't overflow the deadline
+    assert_eq!(futex_wait(&word, 0, Some(usize::MAX / 2)), -EAGAIN);
+    assert_eq!(futex_wake(&word, 1), 0);
+    let tid = thread_create(flag_waiter as usize, 0);
+    sleep(10);
+    FLAG.store(1, Ordering::Release);
+    futex_wake(&FLAG, 1);
+    assert_eq!(waittid(tid as usize), 1);
+    println!("futex passed");
+
+    // mutex
+    let tids: Vec<isize> = (0..THREAD_NUM)
+        .map(|_| thread_create(adder as usize, 0))
+        .collect();
+    for tid in tids {
+        assert_eq!(waittid(tid as usize), 0);
+    }
+    assert_eq!(*COUNTER.lock(), THREAD_NUM * ADD_TIMES);
+    let guard = COUNTER.lock();
+    assert!(COUNTER.try_lock().is_none());
+    drop(guard);
+    assert!(COUNTER.try_lock().is_some());
+    println!("mutex passed");
+
+    // condvar
+    let tid = thread_create(producer as usize, 0);
+    let mut received = Vec::new();
+    while received.len() < ITEM_NUM {
+        let mut queue = QUEUE.lock();
+        while queue.is_empty() {
+            queue = NOT_EMPTY.wait(queue);
+        }
+        received.append(&mut queue);
+    }
+    assert!(received.iter().copied().eq(0..ITEM_NUM));
+    assert_eq!(waittid(tid as usize), 0);
+    let (_queue, timed_out) = NOT_EMPTY.wait_timeout(QUEUE.lock(), 10);
+    assert!(timed_out);
+    println!("condvar passed");
+
+    // semaphore
+    assert!(!SEM.try_acquire());
+    let tid = thread_create(releaser as usize, 3);
+    for _ in 0..3 {
+        SEM.acquire();
+    }
+    assert_eq!(waittid(tid as usize), 0);
+    assert!(!SEM.try_acquire());
+    SEM.release();
+    assert!(SEM.try_acquire());
+    println!("semaphore passed");
+
+    println!("sync_test passed! pid = {}", getpid());
+    0
+}
@@ -0,0 +1,114 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::api::{exit, fork, getpid, gettid, sleep, thread_create, waitpid, waittid, yield_};
+
+const EAGAIN: isize = 11;
+const THREAD_NUM: usize = 8;
+const ADD_TIMES: usize = 1000;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// add to the shared counter, exit with the argument
+fn adder(arg: usize) -> ! {
+    for _ in 0..ADD_TIMES {
+        COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+    // the heap is shared with the other threads
+    let buf: Vec<usize> = (0..arg).collect();
+    assert_eq!(buf.len(), arg);
+    exit(arg as i32);
+    unreachable!()
+}
+
+fn spinner(_arg: usize) -> ! {
+    loop {
+        yield_();
+    }
+}
+
+/// blocked in the kernel far longer than the test
+fn sleeper(_arg: usize) -> ! {
+    sleep(1_000_000);
+    exit(0);
+    unreachable!()
+}
+
+fn forker(_arg: usize) -> ! {
+    let pid = fork();
+    if pid == 0 {
+        // the forking thread is the main thread of the child
+        assert_eq!(gettid(), 0);
+        exit(3);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    exit(exit_code);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nthread_test APP running...\n");
+    assert_eq!(gettid(), 0);
+    assert_eq!(waittid(0), -1);
+
+    let tids: Vec<isize> = (1..=THREAD_NUM)
+        .map(|i| thread_create(adder as usize, i))
+        .collect();
+    for (i, &tid) in tids.iter().enumerate() {
+        assert!(tid > 0, "failed to create a thread");
+        assert_eq!(waittid(tid as usize), (i + 1) as isize);
+    }
+    assert_eq!(COUNTER.load(Ordering::Relaxed), THREAD_NUM * ADD_TIMES);
+    println!("{} threads added to {}", THREAD_NUM, THREAD_NUM * ADD_TIMES);
+
+    // reaped
+    assert_eq!(waittid(tids[0] as usize), -1);
+
+    // fork in a thread
+    let tid = thread_create(forker as usize, 0);
+    assert_eq!(waittid(tid as usize), 3);
+
+    // the trap contexts run out
+    let mut spinners = Vec::new();
+    loop {
+        let tid = thread_create(spinner as usize, 0);
+        if tid < 0 {
+            assert_eq!(tid, -EAGAIN);
+            break;
+        }
+        spinners.push(tid);
+    }
+    println!("{} threads at most", spinners.len() + 1);
+
+    // the other threads are killed when the main thread exits
+    let pid = fork();
+    if pid == 0 {
+        assert!(thread_create(spinner as usize, 0) > 0);
+        exit(7);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+
+    // the blocked threads are woken up to exit, the child is reaped after them
+    let pid = fork();
+    if pid == 0 {
+        assert!(thread_create(sleeper as usize, 0) > 0);
+        sleep(10);
+        exit(8);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 8);
+
+    println!("thread_test passed! pid = {}", getpid());
+    // the spinners are killed with the process
+    0
+}
@@ -5,11 +5,26 @@
 extern crate user_lib;
 
 // not in SUCC_TESTS & FAIL_TESTS
-// count_lines, infloop, user_shell, usertests
+// count_lines, infloop, ld, user_shell, usertests
 
 // item of TESTS : app_name(argv_0), argv_1, argv_2, argv_3, exit_code
 static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("exit\0", "\0", "\0", "\0", 0),
+    ("block_test\0", "\0", "\0", "\0", 0),
+    ("console_test\0", "\0", "\0", "\0", 0),
+    ("fd_test\0", "\0", "\0", "\0", 0),
+    ("args_test\0", "arg1\0", "arg2\0", "\0", 0),
+    ("dir_test\0", "\0", "\0", "\0", 0),
+    ("dyn_test\0", "\0", "\0", "\0", 0),
+    ("elf_test\0", "\0", "\0", "\0", 0),
+    ("file_test\0", "\0", "\0", "\0", 0),
+    ("interp_test\0", "\0", "\0", "\0", 0),
+    ("priority_test\0", "\0", "\0", "\0", 0),
+    ("proc_test\0", "\0", "\0", "\0", 0),
+    ("response_test\0", "\0", "\0", "\0", 0),
+    ("sync_test\0", "\0", "\0", "\0", 0),
+    ("thread_test\0", "\0", "\0", "\0", 0),
+    ("vfs_test\0", "\0", "\0", "\0", 0),
     ("fantastic_text\0", "\0", "\0", "\0", 0),
     ("forktest_simple\0", "\0", "\0", "\0", 0),
     ("forktest\0", "\0", "\0", "\0", 0),
@@ -17,6 +32,11 @@ static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("forktree\0", "\0", "\0", "\0", 0),
     ("hello\0", "\0", "\0", "\0", 0),
     ("matrix\0", "\0", "\0", "\0", 0),
+    ("mmap_test\0", "\0", "\0", "\0", 0),
+    ("oom_test\0", "\0", "\0", "\0", 0),
+    ("pipe_test\0", "\0", "\0", "\0", 0),
+    ("pie_test\0", "\0", "\0", "\0", 0),
+    ("shebang_test\0", "\0", "\0", "\0", 0),
     ("sleep_simple\0", "\0", "\0", "\0", 0),
     ("sleep\0", "\0", "\0", "\0", 0),
     ("yield\0", "\0", "\0", "\0", 0),
@@ -24,45 +44,25 @@ static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
 
 static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] = &[("stack_overflow\0", "\0", "\0", "\0", -1)];
 
-use user_lib::api::{exec, fork, waitpid};
+use user_lib::api::{execve, fork, waitpid};
 
 fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
     let mut pass_num = 0;
-    let mut arr: [*const u8; 4] = [
-        core::ptr::null::<u8>(),
-        core::ptr::null::<u8>(),
-        core::ptr::null::<u8>(),
-        core::ptr::null::<u8>(),
-    ];
-
     for test in tests {
         println!("Usertests: Running {}", test.0);
-        arr[0] = test.0.as_ptr();
-        if test.1 != "\0" {
-            arr[1] = test.1.as_ptr();
-            arr[2] = core::ptr::null::<u8>();
-            arr[3] = core::ptr::null::<u8>();
-            if test.2 != "\0" {
-                arr[2] = test.2.as_ptr();
-                arr[3] = core::ptr::null::<u8>();
-                if test.3 != "\0" {
-                    arr[3] = test.3.as_ptr();
-                } else {
-                    arr[3] = core::ptr::null::<u8>();
-                }
-            } else {
-                arr[2] = core::ptr::null::<u8>();
-                arr[3] = core::ptr::null::<u8>();
-            }
-        } else {
-            arr[1] = core::ptr::null::<u8>();
-            arr[2] = core::ptr::null::<u8>();
-            arr[3] = core::ptr::null::<u8>();
+        // argv ends with NULL
+        let mut argv = [core::ptr::null::<u8>(); 5];
+        for (i, arg) in [test.0, test.1, test.2, test.3]
+            .iter()
+            .take_while(|arg| **arg != "\0")
+            .enumerate()
+        {
+            argv[i] = arg.as_ptr();
         }
 
         let pid = fork();
         if pid == 0 {
-            exec(test.0);
+            execve(test.0, &argv, &[core::ptr::null()]);
             panic!("unreachable!");
         } else {
             let mut exit_code: i32 = Default::default();
@@ -0,0 +1,91 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::api::{
+    close, dir_entries, getdents64, mkdir, mount, open, read, rmdir, umount, unlink, write, DT_CHR,
+    DT_REG, O_CREAT, O_RDONLY, O_RDWR, O_WRONLY,
+};
+
+const EPERM: isize = 1;
+const ENOENT: isize = 2;
+const EBUSY: isize = 16;
+const ENODEV: isize = 19;
+const EINVAL: isize = 22;
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nvfs_test APP running...\n");
+
+    // devfs
+    let mut buf = [0xffu8; 32];
+    let fd = open("/dev/zero\0", O_RDONLY) as usize;
+    assert_eq!(read(fd, &mut buf), buf.len() as isize);
+    assert!(buf.iter().all(|&b| b == 0));
+    close(fd);
+
+    let fd = open("/dev/null\0", O_RDWR) as usize;
+    assert_eq!(write(fd, b"discarded"), 9);
+    assert_eq!(read(fd, &mut buf), 0);
+    close(fd);
+
+    let fd = open("/dev/console\0", O_WRONLY) as usize;
+    let msg = b"hello from /dev/console\n";
+    assert_eq!(write(fd, msg), msg.len() as isize);
+    close(fd);
+
+    assert_eq!(open("/dev/disk\0", O_CREAT | O_RDWR), -EPERM);
+    let fd = open("/dev\0", O_RDONLY) as usize;
+    let mut dents = [0u8; 256];
+    let len = getdents64(fd, &mut dents);
+    let mut count = 0;
+    for entry in dir_entries(&dents[..len as usize]) {
+        assert_eq!(entry.d_type, DT_CHR);
+        count += 1;
+    }
+    assert_eq!(count, 3);
+    close(fd);
+
+    // tmpfs at /tmp
+    let msg = b"in memory";
+    let fd = open("/tmp/vfs_test\0", O_CREAT | O_WRONLY) as usize;
+    assert_eq!(write(fd, msg), msg.len() as isize);
+    close(fd);
+    let fd = open("/tmp/vfs_test\0", O_RDONLY) as usize;
+    assert_eq!(read(fd, &mut buf), msg.len() as isize);
+    assert_eq!(&buf[..msg.len()], msg);
+    close(fd);
+    let fd = open("/tmp\0", O_RDONLY) as usize;
+    let len = getdents64(fd, &mut dents);
+    let entry = dir_entries(&dents[..len as usize]).next().unwrap();
+    assert_eq!(entry.name, "vfs_test");
+    assert_eq!(entry.d_type, DT_REG);
+    close(fd);
+    assert_eq!(unlink("/tmp/vfs_test\0"), 0);
+    assert_eq!(rmdir("/tmp\0"), -EBUSY);
+
+    // a new tmpfs hides the directory on the disk
+    assert_eq!(mkdir("/vfs_test_mnt\0"), 0);
+    let fd = open("/vfs_test_mnt/on_disk\0", O_CREAT | O_WRONLY) as usize;
+    close(fd);
+    assert_eq!(mount("none\0", "/vfs_test_mnt\0", "unknownfs\0"), -ENODEV);
+    assert_eq!(mount("none\0", "/vfs_test_mnt\0", "tmpfs\0"), 0);
+    assert_eq!(mount("none\0", "/vfs_test_mnt\0", "tmpfs\0"), -EBUSY);
+    assert_eq!(open("/vfs_test_mnt/on_disk\0", O_RDONLY), -ENOENT);
+    let fd = open("/vfs_test_mnt/in_tmpfs\0", O_CREAT | O_WRONLY);
+    assert!(fd > 2);
+    close(fd as usize);
+    assert_eq!(umount("/vfs_test_mnt\0"), 0);
+    assert_eq!(umount("/vfs_test_mnt\0"), -EINVAL);
+    assert_eq!(umount("/\0"), -EBUSY);
+    assert_eq!(open("/vfs_test_mnt/in_tmpfs\0", O_RDONLY), -ENOENT);
+
+    // clean up
+    assert_eq!(unlink("/vfs_test_mnt/on_disk\0"), 0);
+    assert_eq!(rmdir("/vfs_test_mnt\0"), 0);
+
+    println!("vfs_test passed!");
+    0
+}
@@ -0,0 +1,35 @@
+//! ### the C ABI of libuser.so
+//! the Rust functions of user_lib are mangled, a program dynamically linked against
+//! the library calls these instead
+
+use core::slice;
+
+use crate::{
+    api::{exit, read, write},
+    env::init_env,
+    user_heap::heap_init,
+};
+
+/// ### start the program as `_start` does, with its `main` passed in
+/// the library can't refer to the main function of the program linked against it
+#[no_mangle]
+pub extern "C" fn lib_start(argc: usize, argv: usize, main: extern "C" fn() -> i32) -> ! {
+    init_env(argc, argv);
+    heap_init();
+    exit(main());
+    panic!("app should exit!");
+}
+
+/// ### Safety
+/// `buf` should be valid for reads of `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn lib_write(fd: usize, buf: *const u8, len: usize) -> isize {
+    write(fd, slice::from_raw_parts(buf, len))
+}
+
+/// ### Safety
+/// `buf` should be valid for writes of `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn lib_read(fd: usize, buf: *mut u8, len: usize) -> isize {
+    read(fd, slice::from_raw_parts_mut(buf, len))
+}
@@ -0,0 +1,90 @@
+//! the arguments and environment variables on the initial user stack
+//!
+//! the System V layout from sp : argc, argv[argc], NULL, envp[..], NULL, auxv[..], AT_NULL
+
+use core::{mem::size_of, slice, str};
+
+// set by `_start` before `main`, read-only after that
+static mut ARGC: usize = 0;
+static mut ARGV: usize = 0;
+
+pub(crate) fn init_env(argc: usize, argv: usize) {
+    unsafe {
+        ARGC = argc;
+        ARGV = argv;
+    }
+}
+
+/// (argc, argv) passed by the kernel, for a dynamic loader to pass them on
+pub fn initial_args() -> (usize, usize) {
+    unsafe { (ARGC, ARGV) }
+}
+
+/// ### a string ending with '\0' placed by the kernel
+/// the kernel only passes UTF-8, otherwise the valid prefix is taken
+fn c_str(ptr: usize) -> &'static str {
+    let ptr = ptr as *const u8;
+    let mut len = 0;
+    let bytes = unsafe {
+        while *ptr.add(len) != 0 {
+            len += 1;
+        }
+        slice::from_raw_parts(ptr, len)
+    };
+    match str::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(err) => str::from_utf8(&bytes[..err.valid_up_to()]).unwrap_or_default(),
+    }
+}
+
+/// the pointers in a NULL-terminated array
+fn ptr_array(start: usize) -> impl Iterator<Item = usize> {
+    (0..)
+        .map(move |i| unsafe { *((start + i * size_of::<usize>()) as *const usize) })
+        .take_while(|&ptr| ptr != 0)
+}
+
+/// the arguments of the program, starting with argv[0]
+pub fn args() -> impl Iterator<Item = &'static str> {
+    let (argc, argv) = unsafe { (ARGC, ARGV) };
+    ptr_array(argv).take(argc).map(c_str)
+}
+
+/// the environment variables as (key, value)
+pub fn vars() -> impl Iterator<Item = (&'static str, &'static str)> {
+    let (argc, argv) = unsafe { (ARGC, ARGV) };
+    let envp = argv + (argc + 1) * size_of::<usize>();
+    ptr_array(envp)
+        .map(c_str)
+        .map(|var| match var.split_once('=') {
+            Some((key, value)) => (key, value),
+            None => (var, ""),
+        })
+}
+
+// the keys of auxv
+pub const AT_NULL: usize = 0;
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_BASE: usize = 7;
+pub const AT_ENTRY: usize = 9;
+pub const AT_RANDOM: usize = 25;
+
+/// the value of an auxv entry, None : not provided by the kernel
+pub fn getauxval(key: usize) -> Option<usize> {
+    let (argc, argv) = unsafe { (ARGC, ARGV) };
+    let envp = argv + (argc + 1) * size_of::<usize>();
+    let envc = ptr_array(envp).count();
+    let auxv = (envp + (envc + 1) * size_of::<usize>()) as *const [usize; 2];
+    (0..)
+        .map(|i| unsafe { *auxv.add(i) })
+        .take_while(|&[k, _]| k != AT_NULL)
+        .find(|&[k, _]| k == key)
+        .map(|[_, value]| value)
+}
+
+pub fn var(key: &str) -> Option<&'static str> {
+    vars().find(|(k, _)| *k == key).map(|(_, value)| value)
+}
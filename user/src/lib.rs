@@ -9,16 +9,23 @@ pub mod console;
 mod kfc_logger;
 
 pub mod api;
+pub mod dylib;
+pub mod env;
 mod lang_items;
+pub mod sync;
 mod syscall;
 mod up_safe_cell;
 mod user_heap;
 
-use crate::{api::exit, user_heap::heap_init};
+pub use env::args;
 
+use crate::{api::exit, env::init_env, user_heap::heap_init};
+
+/// the kernel puts argc in a0 and argv in a1, sp points to argc
 #[no_mangle]
 #[link_section = ".text.entry"]
-pub extern "C" fn _start() -> ! {
+pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
+    init_env(argc, argv);
     heap_init();
     exit(main());
     panic!("app should exit!");
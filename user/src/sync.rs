@@ -0,0 +1,175 @@
+//! blocking synchronization between the threads, built on futex
+//!
+//! a waiter blocks in the kernel on an `AtomicU32` word, until the word changes
+//! and the thread changing it wakes it up
+
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::api::{futex_wait, futex_wake, ETIMEDOUT};
+
+// the states of a Mutex
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+/// locked, and there may be waiters to wake up
+const CONTENDED: u32 = 2;
+
+/// ### a lock blocking the threads waiting for it
+/// only the unlock with waiters makes a syscall
+pub struct Mutex<T> {
+    state: AtomicU32,
+    inner: UnsafeCell<T>,
+}
+
+// the threads are serialized by `state`
+unsafe impl<T: Send> Sync for Mutex<T> {}
+unsafe impl<T: Send> Send for Mutex<T> {}
+
+/// unlocked when dropped
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(item: T) -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+            inner: UnsafeCell::new(item),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<T> {
+        if self
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // the waiter can not tell whether others are waiting, so it marks CONTENDED
+            while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+                futex_wait(&self.state, CONTENDED, None);
+            }
+        }
+        MutexGuard { mutex: self }
+    }
+
+    /// None : locked by others
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        self.state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| MutexGuard { mutex: self })
+    }
+
+    fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            futex_wake(&self.state, 1);
+        }
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.inner.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.inner.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+/// ### wait for a condition protected by a Mutex
+/// the waiter should check the condition again after woken up
+pub struct Condvar {
+    /// bumped by every notify, a waiter blocks only if it has not changed since unlocking
+    seq: AtomicU32,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+        }
+    }
+
+    /// unlock the mutex and block until notified, then lock it again
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        self.wait_timeout_inner(guard, None).0
+    }
+
+    /// ### `wait` for at most `timeout` ms
+    /// true : timed out
+    pub fn wait_timeout<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: usize,
+    ) -> (MutexGuard<'a, T>, bool) {
+        self.wait_timeout_inner(guard, Some(timeout))
+    }
+
+    fn wait_timeout_inner<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Option<usize>,
+    ) -> (MutexGuard<'a, T>, bool) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        let mutex = guard.mutex;
+        drop(guard);
+        let ret = futex_wait(&self.seq, seq, timeout);
+        (mutex.lock(), ret == -ETIMEDOUT)
+    }
+
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        futex_wake(&self.seq, 1);
+    }
+
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        futex_wake(&self.seq, usize::MAX);
+    }
+}
+
+/// a counter of the resources, `acquire` blocks while it is 0
+pub struct Semaphore {
+    count: AtomicU32,
+}
+
+impl Semaphore {
+    pub const fn new(count: u32) -> Self {
+        Self {
+            count: AtomicU32::new(count),
+        }
+    }
+
+    pub fn acquire(&self) {
+        while !self.try_acquire() {
+            futex_wait(&self.count, 0, None);
+        }
+    }
+
+    /// false : no resource left
+    pub fn try_acquire(&self) -> bool {
+        self.count
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |count| {
+                count.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    pub fn release(&self) {
+        self.count.fetch_add(1, Ordering::Release);
+        futex_wake(&self.count, 1);
+    }
+}
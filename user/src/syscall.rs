@@ -1,16 +1,38 @@
 #![allow(unused)]
 
-use core::arch::asm;
+use core::{arch::asm, sync::atomic::AtomicU32};
 
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_MKDIRAT: usize = 34;
+const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_UMOUNT2: usize = 39;
+const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_OPENAT: usize = 56;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS64: usize = 61;
 const SYSCALL_WRITE: usize = 64;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_NANOSLEEP: usize = 101;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_SETPRIORITY: usize = 140;
+const SYSCALL_GETPRIORITY: usize = 141;
 const SYSCALL_TIMES: usize = 153;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETTID: usize = 178;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_FORK: usize = 220;
-const SYSCALL_EXEC: usize = 221;
+const SYSCALL_EXECVE: usize = 221;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_THREAD_CREATE: usize = 1000;
+const SYSCALL_WAITTID: usize = 1002;
 
 // syscall return type is isize
 #[inline(never)]
@@ -28,6 +50,98 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     ret
 }
 
+// for syscalls with more than 3 arguments
+#[inline(never)]
+#[no_mangle]
+fn syscall6(id: usize, args: [usize; 6]) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!("ecall",
+        inlateout("a0") args[0] => ret,
+        in("a1") args[1],
+        in("a2") args[2],
+        in("a3") args[3],
+        in("a4") args[4],
+        in("a5") args[5],
+        in("a7") id,
+        );
+    }
+    ret
+}
+
+pub fn sys_getcwd(buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_GETCWD, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+pub fn sys_mkdirat(dirfd: isize, path: &str, mode: u32) -> isize {
+    syscall(
+        SYSCALL_MKDIRAT,
+        [dirfd as usize, path.as_ptr() as usize, mode as usize],
+    )
+}
+
+pub fn sys_unlinkat(dirfd: isize, path: &str, flags: u32) -> isize {
+    syscall(
+        SYSCALL_UNLINKAT,
+        [dirfd as usize, path.as_ptr() as usize, flags as usize],
+    )
+}
+
+pub fn sys_umount2(target: &str, flags: usize) -> isize {
+    syscall(SYSCALL_UMOUNT2, [target.as_ptr() as usize, flags, 0])
+}
+
+pub fn sys_mount(source: &str, target: &str, fs_type: &str, flags: usize) -> isize {
+    syscall6(
+        SYSCALL_MOUNT,
+        [
+            source.as_ptr() as usize,
+            target.as_ptr() as usize,
+            fs_type.as_ptr() as usize,
+            flags,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_chdir(path: &str) -> isize {
+    syscall(SYSCALL_CHDIR, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_GETDENTS64,
+        [fd, buf.as_mut_ptr() as usize, buf.len()],
+    )
+}
+
+pub fn sys_dup(fd: usize) -> isize {
+    syscall(SYSCALL_DUP, [fd, 0, 0])
+}
+
+pub fn sys_openat(dirfd: isize, path: &str, flags: u32, mode: u32) -> isize {
+    syscall6(
+        SYSCALL_OPENAT,
+        [
+            dirfd as usize,
+            path.as_ptr() as usize,
+            flags as usize,
+            mode as usize,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    syscall(SYSCALL_CLOSE, [fd, 0, 0])
+}
+
+pub fn sys_pipe(pipe_fd: &mut [i32; 2]) -> isize {
+    syscall(SYSCALL_PIPE, [pipe_fd.as_mut_ptr() as usize, 0, 0])
+}
+
 pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
@@ -36,10 +150,31 @@ pub fn sys_exit(exit_code: i32) -> isize {
     syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0])
 }
 
+/// `req` points to (tv_sec, tv_nsec)
+pub fn sys_nanosleep(req: &[usize; 2]) -> isize {
+    syscall(SYSCALL_NANOSLEEP, [req.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_futex(uaddr: &AtomicU32, op: usize, val: usize, timeout: Option<&[usize; 2]>) -> isize {
+    let timeout = timeout.map_or(0, |timeout| timeout.as_ptr() as usize);
+    syscall6(
+        SYSCALL_FUTEX,
+        [uaddr as *const AtomicU32 as usize, op, val, timeout, 0, 0],
+    )
+}
+
 pub fn sys_yield() -> isize {
     syscall(SYSCALL_YIELD, [0, 0, 0])
 }
 
+pub fn sys_set_priority(priority: isize) -> isize {
+    syscall(SYSCALL_SETPRIORITY, [priority as usize, 0, 0])
+}
+
+pub fn sys_get_priority() -> isize {
+    syscall(SYSCALL_GETPRIORITY, [0, 0, 0])
+}
+
 pub fn sys_times() -> isize {
     syscall(SYSCALL_TIMES, [0, 0, 0])
 }
@@ -52,14 +187,56 @@ pub fn sys_getpid() -> isize {
     syscall(SYSCALL_GETPID, [0, 0, 0])
 }
 
+pub fn sys_gettid() -> isize {
+    syscall(SYSCALL_GETTID, [0, 0, 0])
+}
+
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    syscall(SYSCALL_THREAD_CREATE, [entry, arg, 0])
+}
+
+pub fn sys_waittid(tid: usize) -> isize {
+    syscall(SYSCALL_WAITTID, [tid, 0, 0])
+}
+
 pub fn sys_fork() -> isize {
     syscall(SYSCALL_FORK, [0, 0, 0])
 }
 
-pub fn sys_exec(path: &str) -> isize {
-    syscall(SYSCALL_EXEC, [path.as_ptr() as usize, 0, 0])
+pub fn sys_execve(path: &str, argv: &[*const u8], envp: &[*const u8]) -> isize {
+    syscall(
+        SYSCALL_EXECVE,
+        [
+            path.as_ptr() as usize,
+            argv.as_ptr() as usize,
+            envp.as_ptr() as usize,
+        ],
+    )
 }
 
 pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
     syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, 0])
 }
+
+pub fn sys_brk(addr: usize) -> isize {
+    syscall(SYSCALL_BRK, [addr, 0, 0])
+}
+
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    syscall6(SYSCALL_MMAP, [addr, len, prot, flags, fd, offset])
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, [addr, len, 0])
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MPROTECT, [addr, len, prot])
+}
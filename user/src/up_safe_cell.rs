@@ -1,22 +1,61 @@
-use core::cell::{RefCell, RefMut};
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-// multiple threads : not allowed
-// we just assert that UPSafeCell is Sync
+use crate::api::yield_;
+
+// multiple threads : wait until the other thread releases it,
+// yielding the hart as the holder may be switched out
 
 pub struct UPSafeCell<T> {
-    inner: RefCell<T>,
+    borrowed: AtomicBool,
+    inner: UnsafeCell<T>,
 }
 
-// unsafe impl Sync so that compiler would not check it
+// the threads are serialized by `borrowed`
 unsafe impl<T> Sync for UPSafeCell<T> {}
 
+/// released when dropped
+pub struct UPSafeRefMut<'a, T> {
+    cell: &'a UPSafeCell<T>,
+}
+
 impl<T> UPSafeCell<T> {
     pub const fn new(item: T) -> Self {
         Self {
-            inner: RefCell::new(item),
+            borrowed: AtomicBool::new(false),
+            inner: UnsafeCell::new(item),
         }
     }
-    pub fn exclusive_access(&self) -> RefMut<T> {
-        self.inner.borrow_mut()
+    pub fn exclusive_access(&self) -> UPSafeRefMut<T> {
+        while self
+            .borrowed
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            yield_();
+        }
+        UPSafeRefMut { cell: self }
+    }
+}
+
+impl<T> Deref for UPSafeRefMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.cell.inner.get() }
+    }
+}
+
+impl<T> DerefMut for UPSafeRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.cell.inner.get() }
+    }
+}
+
+impl<T> Drop for UPSafeRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.cell.borrowed.store(false, Ordering::Release);
     }
 }
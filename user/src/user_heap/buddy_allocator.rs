@@ -148,7 +148,7 @@ fn prev_power_of_two(x: usize) -> usize {
     1 << (size_of::<usize>() * 8 - x.leading_zeros() as usize - 1)
 }
 
-fn get_real_size(layout: Layout) -> usize {
+pub fn get_real_size(layout: Layout) -> usize {
     let size = max(
         layout.size().next_power_of_two(),
         max(layout.align(), TYPE_ALIGN_SIZE),
@@ -1,4 +1,14 @@
-use self::up_safe_allocator::UPSafeHeap;
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cmp::max,
+};
+
+use crate::api::sbrk;
+
+use self::{
+    buddy_allocator::{get_real_size, Heap},
+    up_safe_allocator::UPSafeHeap,
+};
 
 mod buddy_allocator;
 mod instrusive_linked_list;
@@ -6,6 +16,8 @@ mod up_safe_allocator;
 
 const USER_HEAP_SIZE: usize = 0x8000; // 32KB
 const BUDDY_MAX_ORDER: usize = 32; // as large as possible...
+const PAGE_SIZE: usize = 0x1000;
+const HEAP_GROW_SIZE: usize = 0x10000; // 64KB at least for each extension
 
 // user heap space
 static mut USER_HEAP: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
@@ -19,6 +31,42 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:#X?}", layout);
 }
 
+// rustc generates the allocator functions below when linking a program, libuser.so has
+// to define its own for the `alloc` code in it, weak so a static program keeps rustc's
+#[no_mangle]
+#[linkage = "weak"]
+unsafe fn __rust_alloc(size: usize, align: usize) -> *mut u8 {
+    HEAP_ALLOCATOR.alloc(Layout::from_size_align_unchecked(size, align))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe fn __rust_alloc_zeroed(size: usize, align: usize) -> *mut u8 {
+    HEAP_ALLOCATOR.alloc_zeroed(Layout::from_size_align_unchecked(size, align))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe fn __rust_dealloc(ptr: *mut u8, size: usize, align: usize) {
+    HEAP_ALLOCATOR.dealloc(ptr, Layout::from_size_align_unchecked(size, align))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe fn __rust_realloc(ptr: *mut u8, size: usize, align: usize, new_size: usize) -> *mut u8 {
+    HEAP_ALLOCATOR.realloc(
+        ptr,
+        Layout::from_size_align_unchecked(size, align),
+        new_size,
+    )
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe fn __rust_alloc_error_handler(size: usize, align: usize) -> ! {
+    handle_alloc_error(Layout::from_size_align_unchecked(size, align))
+}
+
 pub fn heap_init() {
     unsafe {
         HEAP_ALLOCATOR
@@ -26,3 +74,25 @@ pub fn heap_init() {
             .init(USER_HEAP.as_ptr() as usize, USER_HEAP_SIZE)
     };
 }
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+/// ### extend the heap by moving the program break
+/// the new space should contain a block of the layout's real size, aligned to the size
+pub fn extend_heap(heap: &mut Heap, layout: Layout) -> Result<(), ()> {
+    let size = get_real_size(layout);
+    let old_brk = sbrk(0);
+    if old_brk < 0 {
+        return Err(());
+    }
+    let old_brk = old_brk as usize;
+    let block_end = align_up(old_brk, size) + size;
+    let new_brk = align_up(max(block_end, old_brk + HEAP_GROW_SIZE), PAGE_SIZE);
+    if sbrk((new_brk - old_brk) as isize) == -1 {
+        return Err(());
+    }
+    unsafe { heap.add_to_heap(old_brk, new_brk) };
+    Ok(())
+}
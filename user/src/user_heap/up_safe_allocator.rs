@@ -6,7 +6,7 @@ use core::{
 
 use crate::up_safe_cell::UPSafeCell;
 
-use super::buddy_allocator::Heap;
+use super::{buddy_allocator::Heap, extend_heap};
 
 // roughly implementation for `locked` heap
 // use UPSafeCell to wrap Heap : Sync
@@ -27,10 +27,15 @@ impl UPSafeHeap {
 
 unsafe impl GlobalAlloc for UPSafeHeap {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        self.exclusive_access()
-            .alloc(layout)
-            .ok()
-            .map_or(null_mut(), |x| x.as_ptr())
+        let mut heap = self.exclusive_access();
+        if let Ok(ptr) = heap.alloc(layout) {
+            return ptr.as_ptr();
+        }
+        // run out of heap space, ask the kernel for more
+        if extend_heap(&mut heap, layout).is_err() {
+            return null_mut();
+        }
+        heap.alloc(layout).ok().map_or(null_mut(), |x| x.as_ptr())
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {